@@ -4,4 +4,7 @@ use clap::Parser;
 pub struct CliArgs {
     #[arg(short, long, default_value = "6379")]
     pub port: u16,
+    /// Keyspace event classes to publish, as for CONFIG SET.
+    #[arg(long, default_value = "")]
+    pub notify_keyspace_events: String,
 }
@@ -1,3 +1,3 @@
 pub mod args;
 
-pub use args::CliArgs;
\ No newline at end of file
+pub use args::CliArgs;
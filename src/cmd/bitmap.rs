@@ -0,0 +1,340 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        bitmap::{BitFieldOp, BitFieldType, BitOp, BitOverflow, BitRange, BitUnit, MAX_BITS},
+        cache::{Db, DbError, Keyspace},
+    },
+};
+
+use super::command::{check_arity, lower, parse_int, CommandApply, CommandError};
+
+#[derive(Debug)]
+pub struct SetBitCommand {
+    pub key: Bytes,
+    pub offset: u64,
+    pub on: bool,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct GetBitCommand {
+    pub key: Bytes,
+    pub offset: u64,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct BitCountCommand {
+    pub key: Bytes,
+    pub range: BitRange,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct BitPosCommand {
+    pub key: Bytes,
+    pub bit: bool,
+    pub range: BitRange,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct BitOpCommand {
+    pub op: BitOp,
+    pub destination: Bytes,
+    pub keys: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+/// BITFIELD, and BITFIELD_RO which only accepts GET.
+#[derive(Debug)]
+pub struct BitFieldCommand {
+    pub key: Bytes,
+    pub ops: Vec<BitFieldOp>,
+    pub cache: Arc<Db>,
+}
+
+/// A SETBIT or GETBIT offset, which must address a bit inside the largest
+/// string allowed.
+fn parse_bit_offset(arg: &[u8]) -> Result<u64, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|offset| offset.parse::<u64>().ok())
+        .filter(|&offset| offset < MAX_BITS)
+        .ok_or(CommandError::BitOffsetOutOfRange)
+}
+
+/// The optional `BYTE | BIT` that ends a BITCOUNT or BITPOS range.
+fn parse_bit_unit(arg: Option<&Bytes>) -> Result<BitUnit, CommandError> {
+    match arg.map(|unit| lower(unit)).as_deref() {
+        None | Some("byte") => Ok(BitUnit::Byte),
+        Some("bit") => Ok(BitUnit::Bit),
+        Some(_) => Err(CommandError::InvalidCommand),
+    }
+}
+
+impl SetBitCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        let offset = parse_bit_offset(&args[1])?;
+        let on = match &args[2][..] {
+            b"0" => false,
+            b"1" => true,
+            _ => return Err(CommandError::BitValueOutOfRange),
+        };
+        Ok(SetBitCommand {
+            key: args[0].clone(),
+            offset,
+            on,
+            cache,
+        })
+    }
+}
+
+impl GetBitCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(GetBitCommand {
+            key: args[0].clone(),
+            offset: parse_bit_offset(&args[1])?,
+            cache,
+        })
+    }
+}
+
+impl BitCountCommand {
+    /// Parses `BITCOUNT key [start end [BYTE | BIT]]`. A start without an
+    /// end is a syntax error.
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 4)?;
+        let range = match args.len() {
+            1 => BitRange::default(),
+            2 => return Err(CommandError::InvalidCommand),
+            _ => BitRange {
+                start: parse_int(&args[1])?,
+                end: Some(parse_int(&args[2])?),
+                unit: parse_bit_unit(args.get(3))?,
+            },
+        };
+        Ok(BitCountCommand {
+            key: args[0].clone(),
+            range,
+            cache,
+        })
+    }
+}
+
+impl BitPosCommand {
+    /// Parses `BITPOS key bit [start [end [BYTE | BIT]]]`.
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 5)?;
+        let bit = match &args[1][..] {
+            b"0" => false,
+            b"1" => true,
+            _ => return Err(CommandError::BitPosNotBit),
+        };
+        let range = BitRange {
+            start: args
+                .get(2)
+                .map(|start| parse_int(start))
+                .transpose()?
+                .unwrap_or(0),
+            end: args.get(3).map(|end| parse_int(end)).transpose()?,
+            unit: parse_bit_unit(args.get(4))?,
+        };
+        Ok(BitPosCommand {
+            key: args[0].clone(),
+            bit,
+            range,
+            cache,
+        })
+    }
+}
+
+impl BitOpCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, usize::MAX)?;
+        let op = match lower(&args[0]).as_str() {
+            "and" => BitOp::And,
+            "or" => BitOp::Or,
+            "xor" => BitOp::Xor,
+            "not" => BitOp::Not,
+            _ => return Err(CommandError::InvalidCommand),
+        };
+        let keys = args[2..].to_vec();
+        if op == BitOp::Not && keys.len() != 1 {
+            return Err(CommandError::BitOpNotSingleSource);
+        }
+        Ok(BitOpCommand {
+            op,
+            destination: args[1].clone(),
+            keys,
+            cache,
+        })
+    }
+}
+
+fn parse_bitfield_type(arg: &[u8]) -> Result<BitFieldType, CommandError> {
+    let (signed, max_bits) = match arg.first() {
+        Some(b'i' | b'I') => (true, 64),
+        Some(b'u' | b'U') => (false, 63),
+        _ => return Err(CommandError::InvalidBitfieldType),
+    };
+    std::str::from_utf8(&arg[1..])
+        .ok()
+        .and_then(|bits| bits.parse::<u32>().ok())
+        .filter(|bits| (1..=max_bits).contains(bits))
+        .map(|bits| BitFieldType { signed, bits })
+        .ok_or(CommandError::InvalidBitfieldType)
+}
+
+/// A BITFIELD offset: a bit offset, or `#N` for the Nth field of the type.
+fn parse_bitfield_offset(arg: &[u8], ty: BitFieldType) -> Result<u64, CommandError> {
+    let (multiplier, digits) = match arg.strip_prefix(b"#") {
+        Some(digits) => (ty.bits as u64, digits),
+        None => (1, arg),
+    };
+    std::str::from_utf8(digits)
+        .ok()
+        .and_then(|offset| offset.parse::<u64>().ok())
+        .and_then(|offset| offset.checked_mul(multiplier))
+        .filter(|&offset| offset < MAX_BITS)
+        .ok_or(CommandError::BitOffsetOutOfRange)
+}
+
+impl BitFieldCommand {
+    /// Parses `BITFIELD key [GET type offset] [SET type offset value]
+    /// [INCRBY type offset increment] [OVERFLOW WRAP | SAT | FAIL] ...`.
+    /// OVERFLOW applies to every SET and INCRBY after it.
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        readonly: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        let mut ops = Vec::new();
+        let mut overflow = BitOverflow::default();
+        let mut idx = 1;
+        while idx < args.len() {
+            let sub = lower(&args[idx]);
+            if sub == "overflow" {
+                let mode = args.get(idx + 1).ok_or(CommandError::InvalidCommand)?;
+                overflow = match lower(mode).as_str() {
+                    "wrap" => BitOverflow::Wrap,
+                    "sat" => BitOverflow::Sat,
+                    "fail" => BitOverflow::Fail,
+                    _ => return Err(CommandError::InvalidOverflowType),
+                };
+                idx += 2;
+                continue;
+            }
+            let arity = match sub.as_str() {
+                "get" => 2,
+                "set" | "incrby" => 3,
+                _ => return Err(CommandError::InvalidCommand),
+            };
+            if idx + arity >= args.len() {
+                return Err(CommandError::InvalidCommand);
+            }
+            if readonly && sub != "get" {
+                return Err(CommandError::BitfieldRoGetOnly);
+            }
+            let ty = parse_bitfield_type(&args[idx + 1])?;
+            let offset = parse_bitfield_offset(&args[idx + 2], ty)?;
+            ops.push(match sub.as_str() {
+                "get" => BitFieldOp::Get { ty, offset },
+                "set" => BitFieldOp::Set {
+                    ty,
+                    offset,
+                    value: parse_int(&args[idx + 3])?,
+                    overflow,
+                },
+                _ => BitFieldOp::IncrBy {
+                    ty,
+                    offset,
+                    incr: parse_int(&args[idx + 3])?,
+                    overflow,
+                },
+            });
+            idx += arity + 1;
+        }
+        Ok(BitFieldCommand {
+            key: args[0].clone(),
+            ops,
+            cache,
+        })
+    }
+}
+
+impl CommandApply for SetBitCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let previous = ks.setbit(&self.key, self.offset, self.on)?;
+        Ok(RespDT::Integer(previous as i64))
+    }
+}
+
+impl CommandApply for GetBitCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.getbit(&self.key, self.offset)? as i64))
+    }
+}
+
+impl CommandApply for BitCountCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.bitcount(&self.key, self.range)? as i64))
+    }
+}
+
+impl CommandApply for BitPosCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.bitpos(&self.key, self.bit, self.range)?))
+    }
+}
+
+impl CommandApply for BitOpCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let len = ks.bitop(self.op, &self.destination, &self.keys)?;
+        Ok(RespDT::Integer(len as i64))
+    }
+}
+
+impl CommandApply for BitFieldCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let replies = ks.bitfield(&self.key, &self.ops)?;
+        Ok(RespDT::Array(
+            replies
+                .into_iter()
+                .map(|reply| reply.map_or(RespDT::Null, RespDT::Integer))
+                .collect(),
+        ))
+    }
+}
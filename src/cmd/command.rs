@@ -3,63 +3,241 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use crate::{resp::RespDT, store::cache::Db};
+use bytes::Bytes;
+use thiserror::Error;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        list::ListEnd,
+        numeric,
+        pubsub::SubscriptionKind,
+        set::SetOp,
+        string::{SetCondition, SetExpiry, SetOptions},
+    },
+};
+
+use super::list::{
+    BlockingPopCommand, LIndexCommand, LInsertCommand, LLenCommand, LMPopCommand, LMoveCommand,
+    LRangeCommand, LRemCommand, LSetCommand, LTrimCommand, PopCommand, PushCommand,
+};
+
+use super::hash::{
+    HDelCommand, HExistsCommand, HGetAllCommand, HGetCommand, HIncrByCommand, HIncrByFloatCommand,
+    HKeysCommand, HLenCommand, HMGetCommand, HRandFieldCommand, HSetCommand, HSetNxCommand,
+    HStrLenCommand, HValsCommand,
+};
+
+use super::set::{
+    SAddCommand, SCardCommand, SInterCardCommand, SIsMemberCommand, SMIsMemberCommand,
+    SMembersCommand, SMoveCommand, SPopCommand, SRandMemberCommand, SRemCommand, SetAlgebraCommand,
+    SetAlgebraStoreCommand,
+};
+
+use super::zset::{
+    RangeKind, ZAddCommand, ZCardCommand, ZCountCommand, ZIncrByCommand, ZLexCountCommand,
+    ZMScoreCommand, ZPopCommand, ZRangeCommand, ZRankCommand, ZRemCommand, ZRemRangeCommand,
+    ZScoreCommand, ZStoreCommand,
+};
+
+use super::stream::{
+    XAddCommand, XDelCommand, XLenCommand, XRangeCommand, XReadCommand, XTrimCommand,
+};
+
+use super::stream_group::{
+    XAckCommand, XAutoClaimCommand, XClaimCommand, XGroupCommand, XInfoCommand, XPendingCommand,
+    XReadGroupCommand,
+};
+
+use super::expire::{ExpireCommand, PersistCommand, TimeUnit, TtlCommand};
+
+use super::keys::{
+    CopyCommand, DbSizeCommand, DelCommand, ExistsCommand, FlushCommand, KeysCommand,
+    RandomKeyCommand, RenameCommand, TypeCommand,
+};
+
+use super::scan::{ScanCommand, ScanKind};
+
+use super::string::{
+    AppendCommand, GetDelCommand, GetExCommand, GetRangeCommand, IncrByFloatCommand, IncrCommand,
+    LcsCommand, MGetCommand, MSetCommand, SetNxCommand, SetRangeCommand, StrLenCommand,
+};
+
+use super::bitmap::{
+    BitCountCommand, BitFieldCommand, BitOpCommand, BitPosCommand, GetBitCommand, SetBitCommand,
+};
+
+use super::hyperloglog::{PfAddCommand, PfCountCommand, PfMergeCommand};
+
+use super::geo::{GeoAddCommand, GeoDistCommand, GeoHashCommand, GeoPosCommand, GeoSearchCommand};
+
+use super::pubsub::{PubSubCommand, PublishCommand, SubscribeCommand};
+
+use super::server::{ConfigCommand, HelloCommand, InfoCommand};
 
 const SET_CMD_RESP: &str = "OK";
 const PONG_CMD_RESP: &str = "PONG";
+pub(crate) const OK_RESP: &str = "OK";
+
+pub(crate) trait CommandRespond {
+    async fn response(&self) -> Result<RespDT, Box<dyn std::error::Error>>;
+}
 
-trait CommandRespond {
-    async fn response_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
+/// A command that only reads or writes the key space. It is applied while the
+/// `Db` lock is held, and a `DbError` becomes an error reply to the client.
+pub(crate) trait CommandApply {
+    fn db(&self) -> &Db;
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError>;
 }
+
+impl<T: CommandApply> CommandRespond for T {
+    async fn response(&self) -> Result<RespDT, Box<dyn std::error::Error>> {
+        let mut ks = self.db().cache.lock().await;
+        let resp = self
+            .apply(&mut ks)
+            .unwrap_or_else(|err| RespDT::SimpleError(err.to_string()));
+        ks.blocked.wake_ready();
+        Ok(resp)
+    }
+}
+
 #[derive(Debug)]
 pub struct PingCommand;
 
 #[derive(Debug)]
 pub struct EchoCommand {
-    pub message: String,
+    pub message: Bytes,
 }
 
 #[derive(Debug)]
 pub struct SetCommand {
-    pub key: String,
-    pub value: String,
-    pub expiry: Option<SystemTime>,
+    pub key: Bytes,
+    pub value: Bytes,
+    pub options: SetOptions,
     pub cache: Arc<Db>,
 }
 
 #[derive(Debug)]
 pub struct GetCommand {
-    pub key: String,
+    pub key: Bytes,
     pub cache: Arc<Db>,
 }
 
 impl CommandRespond for PingCommand {
-    async fn response_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        Ok(RespDT::SimpleString(PONG_CMD_RESP.to_string()).encode_raw())
+    async fn response(&self) -> Result<RespDT, Box<dyn std::error::Error>> {
+        Ok(RespDT::SimpleString(PONG_CMD_RESP.to_string()))
     }
 }
 
 impl CommandRespond for EchoCommand {
-    async fn response_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        Ok(RespDT::SimpleString(self.message.clone()).encode_raw())
+    async fn response(&self) -> Result<RespDT, Box<dyn std::error::Error>> {
+        Ok(RespDT::Bulk(self.message.clone()))
     }
 }
 
-impl CommandRespond for SetCommand {
-    async fn response_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        self.cache
-            .store(self.key.clone(), self.value.clone(), self.expiry)
-            .await;
-        Ok(RespDT::SimpleString(SET_CMD_RESP.to_string()).encode_raw())
+impl SetCommand {
+    /// Parses `SET key value [NX | XX] [GET] [EX s | PX ms | EXAT s | PXAT ms
+    /// | KEEPTTL]`. Repeating an option is allowed, mixing alternatives is not.
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let mut options = SetOptions::default();
+        let mut condition_opt: Option<String> = None;
+        let mut expiry_opt: Option<String> = None;
+        let mut idx = 2;
+        while idx < args.len() {
+            let opt = lower(&args[idx]);
+            match opt.as_str() {
+                "nx" | "xx" => {
+                    if condition_opt.as_ref().is_some_and(|prev| *prev != opt) {
+                        return Err(CommandError::InvalidCommand);
+                    }
+                    options.condition = if opt == "nx" {
+                        SetCondition::IfAbsent
+                    } else {
+                        SetCondition::IfPresent
+                    };
+                    condition_opt = Some(opt);
+                }
+                "get" => options.get = true,
+                "keepttl" | "ex" | "px" | "exat" | "pxat" => {
+                    if expiry_opt.as_ref().is_some_and(|prev| *prev != opt) {
+                        return Err(CommandError::InvalidCommand);
+                    }
+                    options.expiry = if opt == "keepttl" {
+                        SetExpiry::Keep
+                    } else {
+                        idx += 1;
+                        let arg = args.get(idx).ok_or(CommandError::InvalidCommand)?;
+                        parse_set_expiry(cmd, &opt, arg)?
+                    };
+                    expiry_opt = Some(opt);
+                }
+                _ => return Err(CommandError::InvalidCommand),
+            }
+            idx += 1;
+        }
+        Ok(SetCommand {
+            key: args[0].clone(),
+            value: args[1].clone(),
+            options,
+            cache,
+        })
     }
 }
 
-impl CommandRespond for GetCommand {
-    async fn response_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        match self.cache.fetch(self.key.clone()).await {
-            Some(val) => Ok(RespDT::SimpleString(val).encode_raw()),
-            None => Ok(RespDT::Null.encode_raw()),
-        }
+/// Parses the argument of an EX, PX, EXAT or PXAT option, which must be a
+/// positive time that fits in milliseconds.
+pub(crate) fn parse_set_expiry(
+    cmd: &str,
+    opt: &str,
+    arg: &[u8],
+) -> Result<SetExpiry, CommandError> {
+    let value = parse_int(arg)?;
+    let millis = match opt {
+        "ex" | "exat" => value.checked_mul(1000),
+        _ => Some(value),
+    };
+    let invalid = || CommandError::InvalidExpireTime(cmd.to_string());
+    let millis = millis.filter(|ms| *ms > 0).ok_or_else(invalid)?;
+    let duration = Duration::from_millis(millis as u64);
+    let expiry = match opt {
+        "ex" | "px" => SetExpiry::After(duration),
+        _ => SetExpiry::At(duration),
+    };
+    // Reject deadlines the clock cannot represent.
+    SystemTime::now()
+        .checked_add(duration)
+        .map(|_| expiry)
+        .ok_or_else(invalid)
+}
+
+impl CommandApply for SetCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let outcome = ks.set(&self.key, self.value.clone(), &self.options)?;
+        Ok(if self.options.get {
+            outcome.previous.map_or(RespDT::Null, RespDT::Bulk)
+        } else if outcome.written {
+            RespDT::SimpleString(SET_CMD_RESP.to_string())
+        } else {
+            RespDT::Null
+        })
+    }
+}
+
+impl CommandApply for GetCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(ks
+            .get_str(&self.key)?
+            .map_or(RespDT::Null, |value| RespDT::Bulk(value.to_bytes())))
     }
 }
 
@@ -67,26 +245,541 @@ impl CommandRespond for GetCommand {
 pub enum Command {
     Ping(PingCommand),
     Echo(EchoCommand),
+    Multi,
+    Exec,
+    Discard,
+    Watch(Vec<Bytes>),
+    Unwatch,
+    Subscribe(SubscribeCommand),
+    Unsubscribe(SubscribeCommand),
+    Hello(HelloCommand),
     Set(SetCommand),
     Get(GetCommand),
+    Push(PushCommand),
+    Pop(PopCommand),
+    LRange(LRangeCommand),
+    LLen(LLenCommand),
+    LIndex(LIndexCommand),
+    LSet(LSetCommand),
+    LRem(LRemCommand),
+    LTrim(LTrimCommand),
+    LInsert(LInsertCommand),
+    HSet(HSetCommand),
+    HSetNx(HSetNxCommand),
+    HGet(HGetCommand),
+    HMGet(HMGetCommand),
+    HGetAll(HGetAllCommand),
+    HDel(HDelCommand),
+    HExists(HExistsCommand),
+    HLen(HLenCommand),
+    HKeys(HKeysCommand),
+    HVals(HValsCommand),
+    HIncrBy(HIncrByCommand),
+    HIncrByFloat(HIncrByFloatCommand),
+    HStrLen(HStrLenCommand),
+    HRandField(HRandFieldCommand),
+    SAdd(SAddCommand),
+    SRem(SRemCommand),
+    SMembers(SMembersCommand),
+    SIsMember(SIsMemberCommand),
+    SMIsMember(SMIsMemberCommand),
+    SCard(SCardCommand),
+    SPop(SPopCommand),
+    SRandMember(SRandMemberCommand),
+    SMove(SMoveCommand),
+    SetAlgebra(SetAlgebraCommand),
+    SetAlgebraStore(SetAlgebraStoreCommand),
+    SInterCard(SInterCardCommand),
+    ZAdd(ZAddCommand),
+    ZIncrBy(ZIncrByCommand),
+    ZRem(ZRemCommand),
+    ZCard(ZCardCommand),
+    ZScore(ZScoreCommand),
+    ZMScore(ZMScoreCommand),
+    ZRank(ZRankCommand),
+    ZCount(ZCountCommand),
+    ZLexCount(ZLexCountCommand),
+    ZRange(ZRangeCommand),
+    ZPop(ZPopCommand),
+    ZRemRange(ZRemRangeCommand),
+    ZStore(ZStoreCommand),
+    XAdd(XAddCommand),
+    XRange(XRangeCommand),
+    XLen(XLenCommand),
+    XDel(XDelCommand),
+    XTrim(XTrimCommand),
+    XGroup(XGroupCommand),
+    XReadGroup(XReadGroupCommand),
+    XAck(XAckCommand),
+    XPending(XPendingCommand),
+    XClaim(XClaimCommand),
+    XAutoClaim(XAutoClaimCommand),
+    XInfo(XInfoCommand),
+    XRead(XReadCommand),
+    BlockingPop(BlockingPopCommand),
+    LMove(LMoveCommand),
+    LMPop(LMPopCommand),
+    Expire(ExpireCommand),
+    Ttl(TtlCommand),
+    Persist(PersistCommand),
+    Info(InfoCommand),
+    Del(DelCommand),
+    Exists(ExistsCommand),
+    Type(TypeCommand),
+    Keys(KeysCommand),
+    Rename(RenameCommand),
+    Copy(CopyCommand),
+    RandomKey(RandomKeyCommand),
+    DbSize(DbSizeCommand),
+    Scan(ScanCommand),
+    Incr(IncrCommand),
+    IncrByFloat(IncrByFloatCommand),
+    Append(AppendCommand),
+    StrLen(StrLenCommand),
+    GetRange(GetRangeCommand),
+    SetRange(SetRangeCommand),
+    GetDel(GetDelCommand),
+    GetEx(GetExCommand),
+    SetNx(SetNxCommand),
+    MSet(MSetCommand),
+    MGet(MGetCommand),
+    Lcs(LcsCommand),
+    SetBit(SetBitCommand),
+    GetBit(GetBitCommand),
+    BitCount(BitCountCommand),
+    BitPos(BitPosCommand),
+    BitOp(BitOpCommand),
+    BitField(BitFieldCommand),
+    PfAdd(PfAddCommand),
+    PfCount(PfCountCommand),
+    PfMerge(PfMergeCommand),
+    GeoAdd(GeoAddCommand),
+    GeoDist(GeoDistCommand),
+    GeoHash(GeoHashCommand),
+    GeoPos(GeoPosCommand),
+    GeoSearch(GeoSearchCommand),
+    Flush(FlushCommand),
+    Publish(PublishCommand),
+    PubSub(PubSubCommand),
+    Config(ConfigCommand),
 }
 
 impl Command {
-    pub async fn execute(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    pub async fn execute(&self) -> Result<RespDT, Box<dyn std::error::Error>> {
         match self {
-            Command::Ping(cmd) => cmd.response_bytes().await,
-            Command::Echo(cmd) => cmd.response_bytes().await,
-            Command::Set(cmd) => cmd.response_bytes().await,
-            Command::Get(cmd) => cmd.response_bytes().await,
+            Command::Ping(cmd) => cmd.response().await,
+            Command::Echo(cmd) => cmd.response().await,
+            // Inside a transaction the connection handles these itself;
+            // outside one, MULTI starts it and the others have nothing to
+            // act on.
+            Command::Multi => Ok(RespDT::SimpleString(OK_RESP.to_string())),
+            Command::Exec => Ok(RespDT::SimpleError(
+                CommandError::ExecWithoutMulti.to_string(),
+            )),
+            Command::Discard => Ok(RespDT::SimpleError(
+                CommandError::DiscardWithoutMulti.to_string(),
+            )),
+            // The connection registers or drops its watches before this.
+            Command::Watch(_) | Command::Unwatch => Ok(RespDT::SimpleString(OK_RESP.to_string())),
+            Command::Subscribe(_) | Command::Unsubscribe(_) => {
+                unreachable!("the connection runs (un)subscriptions itself")
+            }
+            Command::Hello(_) => unreachable!("the connection runs HELLO itself"),
+            Command::Set(cmd) => cmd.response().await,
+            Command::Get(cmd) => cmd.response().await,
+            Command::Push(cmd) => cmd.response().await,
+            Command::Pop(cmd) => cmd.response().await,
+            Command::LRange(cmd) => cmd.response().await,
+            Command::LLen(cmd) => cmd.response().await,
+            Command::LIndex(cmd) => cmd.response().await,
+            Command::LSet(cmd) => cmd.response().await,
+            Command::LRem(cmd) => cmd.response().await,
+            Command::LTrim(cmd) => cmd.response().await,
+            Command::LInsert(cmd) => cmd.response().await,
+            Command::HSet(cmd) => cmd.response().await,
+            Command::HSetNx(cmd) => cmd.response().await,
+            Command::HGet(cmd) => cmd.response().await,
+            Command::HMGet(cmd) => cmd.response().await,
+            Command::HGetAll(cmd) => cmd.response().await,
+            Command::HDel(cmd) => cmd.response().await,
+            Command::HExists(cmd) => cmd.response().await,
+            Command::HLen(cmd) => cmd.response().await,
+            Command::HKeys(cmd) => cmd.response().await,
+            Command::HVals(cmd) => cmd.response().await,
+            Command::HIncrBy(cmd) => cmd.response().await,
+            Command::HIncrByFloat(cmd) => cmd.response().await,
+            Command::HStrLen(cmd) => cmd.response().await,
+            Command::HRandField(cmd) => cmd.response().await,
+            Command::SAdd(cmd) => cmd.response().await,
+            Command::SRem(cmd) => cmd.response().await,
+            Command::SMembers(cmd) => cmd.response().await,
+            Command::SIsMember(cmd) => cmd.response().await,
+            Command::SMIsMember(cmd) => cmd.response().await,
+            Command::SCard(cmd) => cmd.response().await,
+            Command::SPop(cmd) => cmd.response().await,
+            Command::SRandMember(cmd) => cmd.response().await,
+            Command::SMove(cmd) => cmd.response().await,
+            Command::SetAlgebra(cmd) => cmd.response().await,
+            Command::SetAlgebraStore(cmd) => cmd.response().await,
+            Command::SInterCard(cmd) => cmd.response().await,
+            Command::ZAdd(cmd) => cmd.response().await,
+            Command::ZIncrBy(cmd) => cmd.response().await,
+            Command::ZRem(cmd) => cmd.response().await,
+            Command::ZCard(cmd) => cmd.response().await,
+            Command::ZScore(cmd) => cmd.response().await,
+            Command::ZMScore(cmd) => cmd.response().await,
+            Command::ZRank(cmd) => cmd.response().await,
+            Command::ZCount(cmd) => cmd.response().await,
+            Command::ZLexCount(cmd) => cmd.response().await,
+            Command::ZRange(cmd) => cmd.response().await,
+            Command::ZPop(cmd) => cmd.response().await,
+            Command::ZRemRange(cmd) => cmd.response().await,
+            Command::ZStore(cmd) => cmd.response().await,
+            Command::XAdd(cmd) => cmd.response().await,
+            Command::XRange(cmd) => cmd.response().await,
+            Command::XLen(cmd) => cmd.response().await,
+            Command::XDel(cmd) => cmd.response().await,
+            Command::XTrim(cmd) => cmd.response().await,
+            Command::XGroup(cmd) => cmd.response().await,
+            Command::XReadGroup(cmd) => cmd.respond().await,
+            Command::XAck(cmd) => cmd.response().await,
+            Command::XPending(cmd) => cmd.response().await,
+            Command::XClaim(cmd) => cmd.response().await,
+            Command::XAutoClaim(cmd) => cmd.response().await,
+            Command::XInfo(cmd) => cmd.response().await,
+            Command::XRead(cmd) => cmd.respond().await,
+            Command::BlockingPop(cmd) => cmd.respond().await,
+            Command::LMove(cmd) => cmd.respond().await,
+            Command::LMPop(cmd) => cmd.respond().await,
+            Command::Expire(cmd) => cmd.response().await,
+            Command::Ttl(cmd) => cmd.response().await,
+            Command::Persist(cmd) => cmd.response().await,
+            Command::Info(cmd) => cmd.response().await,
+            Command::Del(cmd) => cmd.response().await,
+            Command::Exists(cmd) => cmd.response().await,
+            Command::Type(cmd) => cmd.response().await,
+            Command::Keys(cmd) => cmd.response().await,
+            Command::Rename(cmd) => cmd.response().await,
+            Command::Copy(cmd) => cmd.response().await,
+            Command::RandomKey(cmd) => cmd.response().await,
+            Command::DbSize(cmd) => cmd.response().await,
+            Command::Scan(cmd) => cmd.response().await,
+            Command::Incr(cmd) => cmd.response().await,
+            Command::IncrByFloat(cmd) => cmd.response().await,
+            Command::Append(cmd) => cmd.response().await,
+            Command::StrLen(cmd) => cmd.response().await,
+            Command::GetRange(cmd) => cmd.response().await,
+            Command::SetRange(cmd) => cmd.response().await,
+            Command::GetDel(cmd) => cmd.response().await,
+            Command::GetEx(cmd) => cmd.response().await,
+            Command::SetNx(cmd) => cmd.response().await,
+            Command::MSet(cmd) => cmd.response().await,
+            Command::MGet(cmd) => cmd.response().await,
+            Command::Lcs(cmd) => cmd.response().await,
+            Command::SetBit(cmd) => cmd.response().await,
+            Command::GetBit(cmd) => cmd.response().await,
+            Command::BitCount(cmd) => cmd.response().await,
+            Command::BitPos(cmd) => cmd.response().await,
+            Command::BitOp(cmd) => cmd.response().await,
+            Command::BitField(cmd) => cmd.response().await,
+            Command::PfAdd(cmd) => cmd.response().await,
+            Command::PfCount(cmd) => cmd.response().await,
+            Command::PfMerge(cmd) => cmd.response().await,
+            Command::GeoAdd(cmd) => cmd.response().await,
+            Command::GeoDist(cmd) => cmd.response().await,
+            Command::GeoHash(cmd) => cmd.response().await,
+            Command::GeoPos(cmd) => cmd.response().await,
+            Command::GeoSearch(cmd) => cmd.response().await,
+            Command::Flush(cmd) => cmd.response().await,
+            Command::Publish(cmd) => cmd.response().await,
+            Command::PubSub(cmd) => cmd.response().await,
+            Command::Config(cmd) => cmd.response().await,
         }
     }
+
+    /// Applies a command queued by MULTI to the key space EXEC has locked.
+    /// Blocking commands behave like their non-blocking forms, as in Redis.
+    pub(crate) fn apply_queued(&self, ks: &mut Keyspace) -> RespDT {
+        let result = match self {
+            Command::Multi
+            | Command::Exec
+            | Command::Discard
+            | Command::Watch(_)
+            | Command::Subscribe(_)
+            | Command::Unsubscribe(_)
+            | Command::Hello(_) => {
+                unreachable!("connection state commands are never queued")
+            }
+            // EXEC has dropped the watches by the time this runs.
+            Command::Unwatch => Ok(RespDT::SimpleString(OK_RESP.to_string())),
+            Command::Ping(_) => Ok(RespDT::SimpleString(PONG_CMD_RESP.to_string())),
+            Command::Echo(cmd) => Ok(RespDT::Bulk(cmd.message.clone())),
+            Command::Set(cmd) => cmd.apply(ks),
+            Command::Get(cmd) => cmd.apply(ks),
+            Command::Push(cmd) => cmd.apply(ks),
+            Command::Pop(cmd) => cmd.apply(ks),
+            Command::LRange(cmd) => cmd.apply(ks),
+            Command::LLen(cmd) => cmd.apply(ks),
+            Command::LIndex(cmd) => cmd.apply(ks),
+            Command::LSet(cmd) => cmd.apply(ks),
+            Command::LRem(cmd) => cmd.apply(ks),
+            Command::LTrim(cmd) => cmd.apply(ks),
+            Command::LInsert(cmd) => cmd.apply(ks),
+            Command::HSet(cmd) => cmd.apply(ks),
+            Command::HSetNx(cmd) => cmd.apply(ks),
+            Command::HGet(cmd) => cmd.apply(ks),
+            Command::HMGet(cmd) => cmd.apply(ks),
+            Command::HGetAll(cmd) => cmd.apply(ks),
+            Command::HDel(cmd) => cmd.apply(ks),
+            Command::HExists(cmd) => cmd.apply(ks),
+            Command::HLen(cmd) => cmd.apply(ks),
+            Command::HKeys(cmd) => cmd.apply(ks),
+            Command::HVals(cmd) => cmd.apply(ks),
+            Command::HIncrBy(cmd) => cmd.apply(ks),
+            Command::HIncrByFloat(cmd) => cmd.apply(ks),
+            Command::HStrLen(cmd) => cmd.apply(ks),
+            Command::HRandField(cmd) => cmd.apply(ks),
+            Command::SAdd(cmd) => cmd.apply(ks),
+            Command::SRem(cmd) => cmd.apply(ks),
+            Command::SMembers(cmd) => cmd.apply(ks),
+            Command::SIsMember(cmd) => cmd.apply(ks),
+            Command::SMIsMember(cmd) => cmd.apply(ks),
+            Command::SCard(cmd) => cmd.apply(ks),
+            Command::SPop(cmd) => cmd.apply(ks),
+            Command::SRandMember(cmd) => cmd.apply(ks),
+            Command::SMove(cmd) => cmd.apply(ks),
+            Command::SetAlgebra(cmd) => cmd.apply(ks),
+            Command::SetAlgebraStore(cmd) => cmd.apply(ks),
+            Command::SInterCard(cmd) => cmd.apply(ks),
+            Command::ZAdd(cmd) => cmd.apply(ks),
+            Command::ZIncrBy(cmd) => cmd.apply(ks),
+            Command::ZRem(cmd) => cmd.apply(ks),
+            Command::ZCard(cmd) => cmd.apply(ks),
+            Command::ZScore(cmd) => cmd.apply(ks),
+            Command::ZMScore(cmd) => cmd.apply(ks),
+            Command::ZRank(cmd) => cmd.apply(ks),
+            Command::ZCount(cmd) => cmd.apply(ks),
+            Command::ZLexCount(cmd) => cmd.apply(ks),
+            Command::ZRange(cmd) => cmd.apply(ks),
+            Command::ZPop(cmd) => cmd.apply(ks),
+            Command::ZRemRange(cmd) => cmd.apply(ks),
+            Command::ZStore(cmd) => cmd.apply(ks),
+            Command::XAdd(cmd) => cmd.apply(ks),
+            Command::XRange(cmd) => cmd.apply(ks),
+            Command::XLen(cmd) => cmd.apply(ks),
+            Command::XDel(cmd) => cmd.apply(ks),
+            Command::XTrim(cmd) => cmd.apply(ks),
+            Command::XGroup(cmd) => cmd.apply(ks),
+            Command::XReadGroup(cmd) => cmd.apply(ks),
+            Command::XAck(cmd) => cmd.apply(ks),
+            Command::XPending(cmd) => cmd.apply(ks),
+            Command::XClaim(cmd) => cmd.apply(ks),
+            Command::XAutoClaim(cmd) => cmd.apply(ks),
+            Command::XInfo(cmd) => cmd.apply(ks),
+            Command::XRead(cmd) => cmd.apply(ks),
+            Command::BlockingPop(cmd) => cmd.apply(ks),
+            Command::LMove(cmd) => cmd.apply(ks),
+            Command::LMPop(cmd) => cmd.apply(ks),
+            Command::Expire(cmd) => cmd.apply(ks),
+            Command::Ttl(cmd) => cmd.apply(ks),
+            Command::Persist(cmd) => cmd.apply(ks),
+            Command::Info(cmd) => cmd.apply(ks),
+            Command::Del(cmd) => cmd.apply(ks),
+            Command::Exists(cmd) => cmd.apply(ks),
+            Command::Type(cmd) => cmd.apply(ks),
+            Command::Keys(cmd) => cmd.apply(ks),
+            Command::Rename(cmd) => cmd.apply(ks),
+            Command::Copy(cmd) => cmd.apply(ks),
+            Command::RandomKey(cmd) => cmd.apply(ks),
+            Command::DbSize(cmd) => cmd.apply(ks),
+            Command::Scan(cmd) => cmd.apply(ks),
+            Command::Incr(cmd) => cmd.apply(ks),
+            Command::IncrByFloat(cmd) => cmd.apply(ks),
+            Command::Append(cmd) => cmd.apply(ks),
+            Command::StrLen(cmd) => cmd.apply(ks),
+            Command::GetRange(cmd) => cmd.apply(ks),
+            Command::SetRange(cmd) => cmd.apply(ks),
+            Command::GetDel(cmd) => cmd.apply(ks),
+            Command::GetEx(cmd) => cmd.apply(ks),
+            Command::SetNx(cmd) => cmd.apply(ks),
+            Command::MSet(cmd) => cmd.apply(ks),
+            Command::MGet(cmd) => cmd.apply(ks),
+            Command::Lcs(cmd) => cmd.apply(ks),
+            Command::SetBit(cmd) => cmd.apply(ks),
+            Command::GetBit(cmd) => cmd.apply(ks),
+            Command::BitCount(cmd) => cmd.apply(ks),
+            Command::BitPos(cmd) => cmd.apply(ks),
+            Command::BitOp(cmd) => cmd.apply(ks),
+            Command::BitField(cmd) => cmd.apply(ks),
+            Command::PfAdd(cmd) => cmd.apply(ks),
+            Command::PfCount(cmd) => cmd.apply(ks),
+            Command::PfMerge(cmd) => cmd.apply(ks),
+            Command::GeoAdd(cmd) => cmd.apply(ks),
+            Command::GeoDist(cmd) => cmd.apply(ks),
+            Command::GeoHash(cmd) => cmd.apply(ks),
+            Command::GeoPos(cmd) => cmd.apply(ks),
+            Command::GeoSearch(cmd) => cmd.apply(ks),
+            Command::Flush(cmd) => cmd.apply(ks),
+            Command::Publish(cmd) => cmd.apply(ks),
+            Command::PubSub(cmd) => cmd.apply(ks),
+            Command::Config(cmd) => cmd.apply(ks),
+        };
+        result.unwrap_or_else(|err| RespDT::SimpleError(err.to_string()))
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Error, PartialEq, Eq)]
 pub enum CommandError {
-    UnknownCommand,
+    #[error("ERR unknown command '{0}'")]
+    UnknownCommand(String),
+    #[error("ERR syntax error")]
     InvalidCommand,
-    InvalidArguments,
+    #[error("ERR Protocol error: expected an array of bulk strings")]
+    ProtocolError,
+    #[error("ERR wrong number of arguments for '{0}' command")]
+    InvalidArguments(String),
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERR value is out of range, must be positive")]
+    NotPositive,
+    #[error("ERR value is out of range")]
+    OutOfRange,
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+    #[error("ERR numkeys should be greater than 0")]
+    NumKeysNotPositive,
+    #[error("ERR Number of keys can't be greater than number of args")]
+    NumKeysTooMany,
+    #[error("ERR LIMIT can't be negative")]
+    NegativeLimit,
+    #[error("ERR XX and NX options at the same time are not compatible")]
+    ZAddXxNx,
+    #[error("ERR GT, LT, and/or NX options at the same time are not compatible")]
+    ZAddGtLtNx,
+    #[error("ERR INCR option supports a single increment-element pair")]
+    ZAddIncrPair,
+    #[error("ERR min or max is not a float")]
+    MinMaxNotFloat,
+    #[error("ERR min or max not valid string range item")]
+    MinMaxNotLex,
+    #[error(
+        "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+    )]
+    LimitWithoutBy,
+    #[error("ERR syntax error, WITHSCORES not supported in combination with BYLEX")]
+    WithScoresByLex,
+    #[error("ERR at least 1 input key is needed for '{0}' command")]
+    NoInputKeys(String),
+    #[error("ERR weight value is not a float")]
+    WeightNotFloat,
+    #[error("ERR Invalid stream ID specified as stream command argument")]
+    InvalidStreamId,
+    #[error("ERR The MAXLEN argument must be >= 0.")]
+    NegativeMaxLen,
+    #[error("ERR syntax error, LIMIT cannot be used without the special ~ option")]
+    LimitWithoutApprox,
+    #[error("ERR unknown subcommand '{1}'. Try {0} HELP.")]
+    UnknownSubcommand(String, String),
+    #[error(
+        "ERR Unbalanced '{0}' list of streams: for each stream key an ID or '$' must be specified."
+    )]
+    UnbalancedStreams(String),
+    #[error("ERR The $ ID is meaningless in the context of XREADGROUP: you want to read the history of this consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID would just return an empty result set.")]
+    DollarInReadGroup,
+    #[error("ERR COUNT must be > 0")]
+    CountNotPositive,
+    #[error("ERR timeout is negative")]
+    NegativeTimeout,
+    #[error("ERR timeout is not a float or out of range")]
+    TimeoutNotFloat,
+    #[error("ERR count should be greater than 0")]
+    CountNotGreaterThanZero,
+    #[error("ERR invalid expire time in '{0}' command")]
+    InvalidExpireTime(String),
+    #[error("ERR Unsupported option {0}")]
+    UnsupportedOption(String),
+    #[error("ERR NX and XX, GT or LT options at the same time are not compatible")]
+    ExpireNxWithOthers,
+    #[error("ERR GT and LT options at the same time are not compatible")]
+    ExpireGtLt,
+    #[error("ERR DB index is out of range")]
+    DbIndexOutOfRange,
+    #[error("ERR source and destination objects are the same")]
+    SameObject,
+    #[error("ERR invalid cursor")]
+    InvalidCursor,
+    #[error("ERR decrement would overflow")]
+    DecrementOverflow,
+    #[error("ERR offset is out of range")]
+    OffsetOutOfRange,
+    #[error("ERR If you want both the length and indexes, please just use IDX.")]
+    LcsLenAndIdx,
+    #[error("ERR bit offset is not an integer or out of range")]
+    BitOffsetOutOfRange,
+    #[error("ERR bit is not an integer or out of range")]
+    BitValueOutOfRange,
+    #[error("ERR The bit argument must be 1 or 0.")]
+    BitPosNotBit,
+    #[error("ERR BITOP NOT must be called with a single source key.")]
+    BitOpNotSingleSource,
+    #[error(
+        "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+    )]
+    InvalidBitfieldType,
+    #[error("ERR BITFIELD_RO only supports the GET subcommand")]
+    BitfieldRoGetOnly,
+    #[error("ERR Invalid OVERFLOW type specified")]
+    InvalidOverflowType,
+    #[error("ERR invalid longitude,latitude pair {0}")]
+    InvalidLonLat(String),
+    #[error("ERR unsupported unit provided. please use M, KM, FT, MI")]
+    UnsupportedUnit,
+    #[error("ERR need numeric {0}")]
+    NeedNumeric(&'static str),
+    #[error("ERR radius cannot be negative")]
+    NegativeRadius,
+    #[error("ERR height or width cannot be negative")]
+    NegativeBox,
+    #[error("ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for {0}")]
+    GeoSearchFrom(String),
+    #[error("ERR exactly one of BYRADIUS and BYBOX can be specified for {0}")]
+    GeoSearchBy(String),
+    #[error(
+        "ERR STORE option in {0} is not compatible with WITHDIST, WITHHASH and WITHCOORD options"
+    )]
+    GeoStoreWithOptions(String),
+    #[error("ERR MULTI calls can not be nested")]
+    NestedMulti,
+    #[error("ERR EXEC without MULTI")]
+    ExecWithoutMulti,
+    #[error("ERR DISCARD without MULTI")]
+    DiscardWithoutMulti,
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+    #[error("ERR WATCH inside MULTI is not allowed")]
+    WatchInsideMulti,
+    #[error("ERR Command not allowed inside a transaction")]
+    NotAllowedInMulti,
+    #[error(
+        "ERR Can't execute '{0}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context"
+    )]
+    SubscribedMode(String),
+    #[error("CROSSSLOT Keys in request don't hash to the same slot")]
+    CrossSlot,
+    #[error("ERR Unknown option or number of arguments for CONFIG SET - '{0}'")]
+    UnknownConfig(String),
+    #[error("ERR CONFIG SET failed (possibly related to argument '{0}') - {1}")]
+    InvalidConfig(String, &'static str),
+    #[error("ERR Protocol version is not an integer or out of range")]
+    InvalidProtocolVersion,
+    #[error("NOPROTO unsupported protocol version")]
+    UnsupportedProtocol,
+    #[error("ERR Syntax error in HELLO option '{0}'")]
+    HelloSyntax(String),
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("ERR Client names cannot contain spaces, newlines or special characters.")]
+    InvalidClientName,
 }
 
 pub struct RespCache {
@@ -100,71 +793,392 @@ impl RespCache {
     }
 }
 
+pub(crate) fn bulk_array(items: Vec<Bytes>) -> RespDT {
+    RespDT::Array(items.into_iter().map(RespDT::Bulk).collect())
+}
+
+/// Like `bulk_array`, for replies that are sets in RESP3.
+pub(crate) fn bulk_set(items: Vec<Bytes>) -> RespDT {
+    RespDT::Set(items.into_iter().map(RespDT::Bulk).collect())
+}
+
+/// Lowercases an option or subcommand name for matching. Anything that is
+/// not valid UTF-8 cannot name an option, so a lossy conversion is enough.
+pub(crate) fn lower(arg: &[u8]) -> String {
+    String::from_utf8_lossy(arg).to_ascii_lowercase()
+}
+
+pub(crate) fn parse_int(arg: &[u8]) -> Result<i64, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse::<i64>().ok())
+        .ok_or(CommandError::NotInteger)
+}
+
+/// The count of HRANDFIELD and SRANDMEMBER. A negative count asks for that
+/// many picks with repeats, so like Redis it is held to half the `i64` range.
+pub(crate) fn parse_random_count(arg: &[u8]) -> Result<i64, CommandError> {
+    let count = parse_int(arg)?;
+    if !(-i64::MAX / 2..=i64::MAX / 2).contains(&count) {
+        return Err(CommandError::OutOfRange);
+    }
+    Ok(count)
+}
+
+pub(crate) fn parse_float(arg: &[u8]) -> Result<f64, CommandError> {
+    numeric::parse_float(arg).ok_or(CommandError::NotFloat)
+}
+
+/// Fails with the Redis arity error unless `args` has between `min` and `max`
+/// entries (inclusive).
+pub(crate) fn check_arity(
+    cmd: &str,
+    args: &[Bytes],
+    min: usize,
+    max: usize,
+) -> Result<(), CommandError> {
+    if args.len() < min || args.len() > max {
+        return Err(CommandError::InvalidArguments(cmd.to_string()));
+    }
+    Ok(())
+}
+
+/// The reply for the outcome of `Db::block_on`, replying `on_timeout` if the
+/// command gave up waiting.
+pub(crate) fn blocking_reply(
+    result: Result<Option<RespDT>, DbError>,
+    on_timeout: RespDT,
+) -> RespDT {
+    match result {
+        Ok(Some(resp)) => resp,
+        Ok(None) => on_timeout,
+        Err(err) => RespDT::SimpleError(err.to_string()),
+    }
+}
+
+/// Replies like `apply`, unless `timeout` is set and `apply` comes back
+/// with `empty`: then the connection parks on `keys` and retries whenever
+/// one is written to, in FIFO order with other blocked consumers.
+pub(crate) async fn respond_blocking<C: CommandApply>(
+    cmd: &C,
+    keys: &[Bytes],
+    timeout: Option<Duration>,
+    empty: RespDT,
+) -> Result<RespDT, Box<dyn std::error::Error>> {
+    let Some(timeout) = timeout else {
+        return cmd.response().await;
+    };
+    let result = cmd
+        .db()
+        .block_on(keys, timeout, true, |ks| {
+            let resp = cmd.apply(ks)?;
+            Ok((resp != empty).then_some(resp))
+        })
+        .await;
+    Ok(blocking_reply(result, empty))
+}
+
 impl TryFrom<RespCache> for Command {
     type Error = CommandError;
 
     fn try_from(value: RespCache) -> Result<Self, Self::Error> {
-        let (cmd, args) = value.resp.extract_array().unwrap();
+        let (cmd, args) = value
+            .resp
+            .extract_array()
+            .ok_or(CommandError::ProtocolError)?;
+        let args = args
+            .iter()
+            .map(|arg| arg.extract_bulk_str())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| CommandError::ProtocolError)?;
+        let cache = value.cache;
         match cmd.as_str() {
             "ping" => Ok(Command::Ping(PingCommand)),
             "echo" => {
-                if args.len() != 1 {
-                    return Err(CommandError::InvalidArguments);
-                }
-                match args.first().unwrap().extract_bulk_str() {
-                    Ok(message) => Ok(Command::Echo(EchoCommand { message })),
-                    Err(_) => Err(CommandError::InvalidCommand),
-                }
+                check_arity(&cmd, &args, 1, 1)?;
+                Ok(Command::Echo(EchoCommand {
+                    message: args[0].clone(),
+                }))
             }
-            "set" => {
-                if args.len() < 2 {
-                    Err(CommandError::InvalidArguments)
-                } else {
-                    if args.len() == 4
-                        && args
-                            .get(2)
-                            .unwrap()
-                            .extract_bulk_str()
-                            .unwrap()
-                            .to_lowercase()
-                            .eq("px")
-                    {
-                        let expiry = args
-                            .get(3)
-                            .unwrap()
-                            .extract_bulk_str()
-                            .unwrap()
-                            .parse::<u64>()
-                            .unwrap();
-                        let expiry = SystemTime::now()
-                            .checked_add(Duration::from_millis(expiry))
-                            .unwrap();
-                        return Ok(Command::Set(SetCommand {
-                            key: args.first().unwrap().extract_bulk_str().unwrap(),
-                            value: args.get(1).unwrap().extract_bulk_str().unwrap(),
-                            expiry: Some(expiry),
-                            cache: value.cache,
-                        }));
-                    }
-                    Ok(Command::Set(SetCommand {
-                        key: args.first().unwrap().extract_bulk_str().unwrap(),
-                        value: args.get(1).unwrap().extract_bulk_str().unwrap(),
-                        expiry: None,
-                        cache: value.cache,
-                    }))
-                }
+            "multi" | "exec" | "discard" => {
+                check_arity(&cmd, &args, 0, 0)?;
+                Ok(match cmd.as_str() {
+                    "multi" => Command::Multi,
+                    "exec" => Command::Exec,
+                    _ => Command::Discard,
+                })
+            }
+            "watch" => {
+                check_arity(&cmd, &args, 1, usize::MAX)?;
+                Ok(Command::Watch(args))
+            }
+            "unwatch" => {
+                check_arity(&cmd, &args, 0, 0)?;
+                Ok(Command::Unwatch)
+            }
+            "subscribe" => SubscribeCommand::parse(&cmd, args, SubscriptionKind::Channel)
+                .map(Command::Subscribe),
+            "psubscribe" => SubscribeCommand::parse(&cmd, args, SubscriptionKind::Pattern)
+                .map(Command::Subscribe),
+            "ssubscribe" => {
+                SubscribeCommand::parse(&cmd, args, SubscriptionKind::Shard).map(Command::Subscribe)
             }
+            "unsubscribe" => SubscribeCommand::parse_unsubscribe(args, SubscriptionKind::Channel)
+                .map(Command::Unsubscribe),
+            "punsubscribe" => SubscribeCommand::parse_unsubscribe(args, SubscriptionKind::Pattern)
+                .map(Command::Unsubscribe),
+            "sunsubscribe" => SubscribeCommand::parse_unsubscribe(args, SubscriptionKind::Shard)
+                .map(Command::Unsubscribe),
+            "hello" => HelloCommand::parse(args).map(Command::Hello),
+            "set" => SetCommand::parse(&cmd, args, cache).map(Command::Set),
             "get" => {
-                if args.len() == 1 {
-                    Ok(Command::Get(GetCommand {
-                        key: args.first().unwrap().extract_bulk_str().unwrap(),
-                        cache: value.cache,
-                    }))
-                } else {
-                    Err(CommandError::InvalidArguments)
-                }
+                check_arity(&cmd, &args, 1, 1)?;
+                Ok(Command::Get(GetCommand {
+                    key: args[0].clone(),
+                    cache,
+                }))
+            }
+            "lpush" => PushCommand::parse(&cmd, args, ListEnd::Left, cache).map(Command::Push),
+            "rpush" => PushCommand::parse(&cmd, args, ListEnd::Right, cache).map(Command::Push),
+            "lpop" => PopCommand::parse(&cmd, args, ListEnd::Left, cache).map(Command::Pop),
+            "rpop" => PopCommand::parse(&cmd, args, ListEnd::Right, cache).map(Command::Pop),
+            "lrange" => LRangeCommand::parse(&cmd, args, cache).map(Command::LRange),
+            "llen" => LLenCommand::parse(&cmd, args, cache).map(Command::LLen),
+            "lindex" => LIndexCommand::parse(&cmd, args, cache).map(Command::LIndex),
+            "lset" => LSetCommand::parse(&cmd, args, cache).map(Command::LSet),
+            "lrem" => LRemCommand::parse(&cmd, args, cache).map(Command::LRem),
+            "ltrim" => LTrimCommand::parse(&cmd, args, cache).map(Command::LTrim),
+            "linsert" => LInsertCommand::parse(&cmd, args, cache).map(Command::LInsert),
+            "hset" => HSetCommand::parse(&cmd, args, cache).map(Command::HSet),
+            "hsetnx" => HSetNxCommand::parse(&cmd, args, cache).map(Command::HSetNx),
+            "hget" => HGetCommand::parse(&cmd, args, cache).map(Command::HGet),
+            "hmget" => HMGetCommand::parse(&cmd, args, cache).map(Command::HMGet),
+            "hgetall" => HGetAllCommand::parse(&cmd, args, cache).map(Command::HGetAll),
+            "hdel" => HDelCommand::parse(&cmd, args, cache).map(Command::HDel),
+            "hexists" => HExistsCommand::parse(&cmd, args, cache).map(Command::HExists),
+            "hlen" => HLenCommand::parse(&cmd, args, cache).map(Command::HLen),
+            "hkeys" => HKeysCommand::parse(&cmd, args, cache).map(Command::HKeys),
+            "hvals" => HValsCommand::parse(&cmd, args, cache).map(Command::HVals),
+            "hincrby" => HIncrByCommand::parse(&cmd, args, cache).map(Command::HIncrBy),
+            "hincrbyfloat" => {
+                HIncrByFloatCommand::parse(&cmd, args, cache).map(Command::HIncrByFloat)
+            }
+            "hstrlen" => HStrLenCommand::parse(&cmd, args, cache).map(Command::HStrLen),
+            "hrandfield" => HRandFieldCommand::parse(&cmd, args, cache).map(Command::HRandField),
+            "sadd" => SAddCommand::parse(&cmd, args, cache).map(Command::SAdd),
+            "srem" => SRemCommand::parse(&cmd, args, cache).map(Command::SRem),
+            "smembers" => SMembersCommand::parse(&cmd, args, cache).map(Command::SMembers),
+            "sismember" => SIsMemberCommand::parse(&cmd, args, cache).map(Command::SIsMember),
+            "smismember" => SMIsMemberCommand::parse(&cmd, args, cache).map(Command::SMIsMember),
+            "scard" => SCardCommand::parse(&cmd, args, cache).map(Command::SCard),
+            "spop" => SPopCommand::parse(&cmd, args, cache).map(Command::SPop),
+            "srandmember" => SRandMemberCommand::parse(&cmd, args, cache).map(Command::SRandMember),
+            "smove" => SMoveCommand::parse(&cmd, args, cache).map(Command::SMove),
+            "sinter" => {
+                SetAlgebraCommand::parse(&cmd, args, SetOp::Inter, cache).map(Command::SetAlgebra)
             }
-            _ => Err(CommandError::UnknownCommand),
+            "sunion" => {
+                SetAlgebraCommand::parse(&cmd, args, SetOp::Union, cache).map(Command::SetAlgebra)
+            }
+            "sdiff" => {
+                SetAlgebraCommand::parse(&cmd, args, SetOp::Diff, cache).map(Command::SetAlgebra)
+            }
+            "sinterstore" => SetAlgebraStoreCommand::parse(&cmd, args, SetOp::Inter, cache)
+                .map(Command::SetAlgebraStore),
+            "sunionstore" => SetAlgebraStoreCommand::parse(&cmd, args, SetOp::Union, cache)
+                .map(Command::SetAlgebraStore),
+            "sdiffstore" => SetAlgebraStoreCommand::parse(&cmd, args, SetOp::Diff, cache)
+                .map(Command::SetAlgebraStore),
+            "sintercard" => SInterCardCommand::parse(&cmd, args, cache).map(Command::SInterCard),
+            "zadd" => ZAddCommand::parse(&cmd, args, cache).map(Command::ZAdd),
+            "zincrby" => ZIncrByCommand::parse(&cmd, args, cache).map(Command::ZIncrBy),
+            "zrem" => ZRemCommand::parse(&cmd, args, cache).map(Command::ZRem),
+            "zcard" => ZCardCommand::parse(&cmd, args, cache).map(Command::ZCard),
+            "zscore" => ZScoreCommand::parse(&cmd, args, cache).map(Command::ZScore),
+            "zmscore" => ZMScoreCommand::parse(&cmd, args, cache).map(Command::ZMScore),
+            "zrank" => ZRankCommand::parse(&cmd, args, false, cache).map(Command::ZRank),
+            "zrevrank" => ZRankCommand::parse(&cmd, args, true, cache).map(Command::ZRank),
+            "zcount" => ZCountCommand::parse(&cmd, args, cache).map(Command::ZCount),
+            "zlexcount" => ZLexCountCommand::parse(&cmd, args, cache).map(Command::ZLexCount),
+            "zrange" => ZRangeCommand::parse(&cmd, args, &[], cache).map(Command::ZRange),
+            "zrevrange" => ZRangeCommand::parse(&cmd, args, &["rev"], cache).map(Command::ZRange),
+            "zrangebyscore" => {
+                ZRangeCommand::parse(&cmd, args, &["byscore"], cache).map(Command::ZRange)
+            }
+            "zrevrangebyscore" => {
+                ZRangeCommand::parse(&cmd, args, &["byscore", "rev"], cache).map(Command::ZRange)
+            }
+            "zrangebylex" => {
+                ZRangeCommand::parse(&cmd, args, &["bylex"], cache).map(Command::ZRange)
+            }
+            "zrevrangebylex" => {
+                ZRangeCommand::parse(&cmd, args, &["bylex", "rev"], cache).map(Command::ZRange)
+            }
+            "zpopmin" => ZPopCommand::parse(&cmd, args, false, cache).map(Command::ZPop),
+            "zpopmax" => ZPopCommand::parse(&cmd, args, true, cache).map(Command::ZPop),
+            "zremrangebyrank" => {
+                ZRemRangeCommand::parse(&cmd, args, RangeKind::Rank, cache).map(Command::ZRemRange)
+            }
+            "zremrangebyscore" => {
+                ZRemRangeCommand::parse(&cmd, args, RangeKind::Score, cache).map(Command::ZRemRange)
+            }
+            "zremrangebylex" => {
+                ZRemRangeCommand::parse(&cmd, args, RangeKind::Lex, cache).map(Command::ZRemRange)
+            }
+            "zunionstore" => ZStoreCommand::parse(&cmd, args, true, cache).map(Command::ZStore),
+            "zinterstore" => ZStoreCommand::parse(&cmd, args, false, cache).map(Command::ZStore),
+            "xadd" => XAddCommand::parse(&cmd, args, cache).map(Command::XAdd),
+            "xrange" => XRangeCommand::parse(&cmd, args, false, cache).map(Command::XRange),
+            "xrevrange" => XRangeCommand::parse(&cmd, args, true, cache).map(Command::XRange),
+            "xlen" => XLenCommand::parse(&cmd, args, cache).map(Command::XLen),
+            "xdel" => XDelCommand::parse(&cmd, args, cache).map(Command::XDel),
+            "xtrim" => XTrimCommand::parse(&cmd, args, cache).map(Command::XTrim),
+            "xgroup" => XGroupCommand::parse(&cmd, args, cache).map(Command::XGroup),
+            "xreadgroup" => XReadGroupCommand::parse(&cmd, args, cache).map(Command::XReadGroup),
+            "xack" => XAckCommand::parse(&cmd, args, cache).map(Command::XAck),
+            "xpending" => XPendingCommand::parse(&cmd, args, cache).map(Command::XPending),
+            "xclaim" => XClaimCommand::parse(&cmd, args, cache).map(Command::XClaim),
+            "xautoclaim" => XAutoClaimCommand::parse(&cmd, args, cache).map(Command::XAutoClaim),
+            "xinfo" => XInfoCommand::parse(&cmd, args, cache).map(Command::XInfo),
+            "xread" => XReadCommand::parse(&cmd, args, cache).map(Command::XRead),
+            "blpop" => BlockingPopCommand::parse(&cmd, args, ListEnd::Left, cache)
+                .map(Command::BlockingPop),
+            "brpop" => BlockingPopCommand::parse(&cmd, args, ListEnd::Right, cache)
+                .map(Command::BlockingPop),
+            "lmove" => LMoveCommand::parse(&cmd, args, false, cache).map(Command::LMove),
+            "blmove" => LMoveCommand::parse(&cmd, args, true, cache).map(Command::LMove),
+            "lmpop" => LMPopCommand::parse(&cmd, args, false, cache).map(Command::LMPop),
+            "blmpop" => LMPopCommand::parse(&cmd, args, true, cache).map(Command::LMPop),
+            "expire" => ExpireCommand::parse(&cmd, args, TimeUnit::Seconds, false, cache)
+                .map(Command::Expire),
+            "pexpire" => ExpireCommand::parse(&cmd, args, TimeUnit::Millis, false, cache)
+                .map(Command::Expire),
+            "expireat" => ExpireCommand::parse(&cmd, args, TimeUnit::Seconds, true, cache)
+                .map(Command::Expire),
+            "pexpireat" => {
+                ExpireCommand::parse(&cmd, args, TimeUnit::Millis, true, cache).map(Command::Expire)
+            }
+            "ttl" => {
+                TtlCommand::parse(&cmd, args, TimeUnit::Seconds, false, cache).map(Command::Ttl)
+            }
+            "pttl" => {
+                TtlCommand::parse(&cmd, args, TimeUnit::Millis, false, cache).map(Command::Ttl)
+            }
+            "expiretime" => {
+                TtlCommand::parse(&cmd, args, TimeUnit::Seconds, true, cache).map(Command::Ttl)
+            }
+            "pexpiretime" => {
+                TtlCommand::parse(&cmd, args, TimeUnit::Millis, true, cache).map(Command::Ttl)
+            }
+            "persist" => PersistCommand::parse(&cmd, args, cache).map(Command::Persist),
+            "info" => InfoCommand::parse(&cmd, args, cache).map(Command::Info),
+            "del" => DelCommand::parse(&cmd, args, cache).map(Command::Del),
+            "unlink" => DelCommand::parse(&cmd, args, cache).map(Command::Del),
+            "exists" => ExistsCommand::parse(&cmd, args, cache).map(Command::Exists),
+            "touch" => ExistsCommand::parse(&cmd, args, cache).map(Command::Exists),
+            "type" => TypeCommand::parse(&cmd, args, cache).map(Command::Type),
+            "keys" => KeysCommand::parse(&cmd, args, cache).map(Command::Keys),
+            "rename" => RenameCommand::parse(&cmd, args, false, cache).map(Command::Rename),
+            "renamenx" => RenameCommand::parse(&cmd, args, true, cache).map(Command::Rename),
+            "copy" => CopyCommand::parse(&cmd, args, cache).map(Command::Copy),
+            "randomkey" => RandomKeyCommand::parse(&cmd, args, cache).map(Command::RandomKey),
+            "dbsize" => DbSizeCommand::parse(&cmd, args, cache).map(Command::DbSize),
+            "scan" => ScanCommand::parse(&cmd, args, ScanKind::Keys, cache).map(Command::Scan),
+            "hscan" => ScanCommand::parse(&cmd, args, ScanKind::Hash, cache).map(Command::Scan),
+            "sscan" => ScanCommand::parse(&cmd, args, ScanKind::Set, cache).map(Command::Scan),
+            "zscan" => ScanCommand::parse(&cmd, args, ScanKind::ZSet, cache).map(Command::Scan),
+            "incr" => IncrCommand::parse(&cmd, args, false, false, cache).map(Command::Incr),
+            "decr" => IncrCommand::parse(&cmd, args, false, true, cache).map(Command::Incr),
+            "incrby" => IncrCommand::parse(&cmd, args, true, false, cache).map(Command::Incr),
+            "decrby" => IncrCommand::parse(&cmd, args, true, true, cache).map(Command::Incr),
+            "incrbyfloat" => IncrByFloatCommand::parse(&cmd, args, cache).map(Command::IncrByFloat),
+            "append" => AppendCommand::parse(&cmd, args, cache).map(Command::Append),
+            "strlen" => StrLenCommand::parse(&cmd, args, cache).map(Command::StrLen),
+            "getrange" => GetRangeCommand::parse(&cmd, args, cache).map(Command::GetRange),
+            "substr" => GetRangeCommand::parse(&cmd, args, cache).map(Command::GetRange),
+            "setrange" => SetRangeCommand::parse(&cmd, args, cache).map(Command::SetRange),
+            "getdel" => GetDelCommand::parse(&cmd, args, cache).map(Command::GetDel),
+            "getex" => GetExCommand::parse(&cmd, args, cache).map(Command::GetEx),
+            "getset" => SetCommand::parse_getset(&cmd, args, cache).map(Command::Set),
+            "setnx" => SetNxCommand::parse(&cmd, args, cache).map(Command::SetNx),
+            "setex" => SetCommand::parse_setex(&cmd, args, "ex", cache).map(Command::Set),
+            "psetex" => SetCommand::parse_setex(&cmd, args, "px", cache).map(Command::Set),
+            "mset" => MSetCommand::parse(&cmd, args, false, cache).map(Command::MSet),
+            "msetnx" => MSetCommand::parse(&cmd, args, true, cache).map(Command::MSet),
+            "mget" => MGetCommand::parse(&cmd, args, cache).map(Command::MGet),
+            "lcs" => LcsCommand::parse(&cmd, args, cache).map(Command::Lcs),
+            "setbit" => SetBitCommand::parse(&cmd, args, cache).map(Command::SetBit),
+            "getbit" => GetBitCommand::parse(&cmd, args, cache).map(Command::GetBit),
+            "bitcount" => BitCountCommand::parse(&cmd, args, cache).map(Command::BitCount),
+            "bitpos" => BitPosCommand::parse(&cmd, args, cache).map(Command::BitPos),
+            "bitop" => BitOpCommand::parse(&cmd, args, cache).map(Command::BitOp),
+            "bitfield" => BitFieldCommand::parse(&cmd, args, false, cache).map(Command::BitField),
+            "bitfield_ro" => BitFieldCommand::parse(&cmd, args, true, cache).map(Command::BitField),
+            "pfadd" => PfAddCommand::parse(&cmd, args, cache).map(Command::PfAdd),
+            "pfcount" => PfCountCommand::parse(&cmd, args, cache).map(Command::PfCount),
+            "pfmerge" => PfMergeCommand::parse(&cmd, args, cache).map(Command::PfMerge),
+            "geoadd" => GeoAddCommand::parse(&cmd, args, cache).map(Command::GeoAdd),
+            "geodist" => GeoDistCommand::parse(&cmd, args, cache).map(Command::GeoDist),
+            "geohash" => GeoHashCommand::parse(&cmd, args, cache).map(Command::GeoHash),
+            "geopos" => GeoPosCommand::parse(&cmd, args, cache).map(Command::GeoPos),
+            "geosearch" => {
+                GeoSearchCommand::parse(&cmd, args, false, cache).map(Command::GeoSearch)
+            }
+            "geosearchstore" => {
+                GeoSearchCommand::parse(&cmd, args, true, cache).map(Command::GeoSearch)
+            }
+            "flushdb" => FlushCommand::parse(&cmd, args, cache).map(Command::Flush),
+            "flushall" => FlushCommand::parse(&cmd, args, cache).map(Command::Flush),
+            "publish" => PublishCommand::parse(&cmd, args, false, cache).map(Command::Publish),
+            "spublish" => PublishCommand::parse(&cmd, args, true, cache).map(Command::Publish),
+            "pubsub" => PubSubCommand::parse(&cmd, args, cache).map(Command::PubSub),
+            "config" => ConfigCommand::parse(&cmd, args, cache).map(Command::Config),
+            _ => Err(CommandError::UnknownCommand(cmd)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use tokio::io::BufReader;
+
+    use super::*;
+    use crate::resp::{Protocol, RespHandler};
+
+    /// Sends `args` through the parser and the command layer as one frame
+    /// and returns the encoded reply.
+    async fn run(db: &Arc<Db>, args: &[&[u8]]) -> Vec<u8> {
+        let mut frame = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            frame.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            frame.extend_from_slice(arg);
+            frame.extend_from_slice(b"\r\n");
         }
+        let mut handler = RespHandler::new(BufReader::new(Cursor::new(frame)));
+        let resp = handler.decode().await.unwrap().unwrap();
+        let cmd: Command = RespCache::new(db.clone(), resp).try_into().unwrap();
+        cmd.execute().await.unwrap().encode(Protocol::Resp2)
+    }
+
+    #[tokio::test]
+    async fn test_binary_keys_and_values_round_trip() {
+        let db = Arc::new(Db::new());
+        let (key, value) = (&b"\xff\xfe\x00k"[..], &b"\x1f\x8b\x00\xff"[..]);
+        let bulk = b"$4\r\n\x1f\x8b\x00\xff\r\n";
+
+        assert_eq!(run(&db, &[b"SET", key, value]).await, b"+OK\r\n");
+        assert_eq!(run(&db, &[b"GET", key]).await, bulk);
+
+        let hash = &b"h\x80"[..];
+        assert_eq!(run(&db, &[b"HSET", hash, key, value]).await, b":1\r\n");
+        assert_eq!(run(&db, &[b"HGET", hash, key]).await, bulk);
+
+        let list = &b"l\xc3\x28"[..];
+        assert_eq!(run(&db, &[b"RPUSH", list, value]).await, b":1\r\n");
+        let mut range = b"*1\r\n".to_vec();
+        range.extend_from_slice(bulk);
+        assert_eq!(run(&db, &[b"LRANGE", list, b"0", b"-1"]).await, range);
     }
 }
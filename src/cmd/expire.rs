@@ -0,0 +1,181 @@
+use std::{sync::Arc, time::SystemTime};
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        expire::{from_unix_ms, unix_ms, ExpireCondition, KeyTtl},
+    },
+};
+
+use super::command::{check_arity, lower, parse_int, CommandApply, CommandError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Seconds,
+    Millis,
+}
+
+impl TimeUnit {
+    fn to_millis(self, value: i64) -> Option<i64> {
+        match self {
+            TimeUnit::Seconds => value.checked_mul(1000),
+            TimeUnit::Millis => Some(value),
+        }
+    }
+
+    fn of_millis(self, ms: i64) -> i64 {
+        match self {
+            TimeUnit::Seconds => ms / 1000,
+            TimeUnit::Millis => ms,
+        }
+    }
+}
+
+/// EXPIRE, PEXPIRE, EXPIREAT and PEXPIREAT. `millis` is relative to the
+/// moment the command runs unless `absolute` is set.
+#[derive(Debug)]
+pub struct ExpireCommand {
+    pub key: Bytes,
+    pub millis: i64,
+    pub absolute: bool,
+    pub condition: ExpireCondition,
+    pub cache: Arc<Db>,
+}
+
+/// TTL, PTTL, EXPIRETIME and PEXPIRETIME.
+#[derive(Debug)]
+pub struct TtlCommand {
+    pub key: Bytes,
+    pub unit: TimeUnit,
+    pub absolute: bool,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct PersistCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+impl ExpireCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        unit: TimeUnit,
+        absolute: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let invalid = || CommandError::InvalidExpireTime(cmd.to_string());
+        let millis = unit.to_millis(parse_int(&args[1])?).ok_or_else(invalid)?;
+        if !absolute && millis.checked_add(unix_ms(SystemTime::now())).is_none() {
+            return Err(invalid());
+        }
+        let mut condition = ExpireCondition::default();
+        for opt in &args[2..] {
+            match lower(opt).as_str() {
+                "nx" => condition.nx = true,
+                "xx" => condition.xx = true,
+                "gt" => condition.gt = true,
+                "lt" => condition.lt = true,
+                _ => {
+                    return Err(CommandError::UnsupportedOption(
+                        String::from_utf8_lossy(opt).into_owned(),
+                    ))
+                }
+            }
+        }
+        if condition.nx && (condition.xx || condition.gt || condition.lt) {
+            return Err(CommandError::ExpireNxWithOthers);
+        }
+        if condition.gt && condition.lt {
+            return Err(CommandError::ExpireGtLt);
+        }
+        Ok(ExpireCommand {
+            key: args[0].clone(),
+            millis,
+            absolute,
+            condition,
+            cache,
+        })
+    }
+}
+
+impl TtlCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        unit: TimeUnit,
+        absolute: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(TtlCommand {
+            key: args[0].clone(),
+            unit,
+            absolute,
+            cache,
+        })
+    }
+}
+
+impl PersistCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(PersistCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl CommandApply for ExpireCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let deadline = match self.absolute {
+            true => self.millis,
+            false => unix_ms(SystemTime::now()).saturating_add(self.millis),
+        };
+        let set = ks.expire_at(&self.key, from_unix_ms(deadline), self.condition);
+        Ok(RespDT::Integer(set as i64))
+    }
+}
+
+impl CommandApply for TtlCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let reply = match ks.ttl(&self.key) {
+            KeyTtl::Missing => -2,
+            KeyTtl::Persistent => -1,
+            KeyTtl::ExpiresAt(deadline) if self.absolute => self.unit.of_millis(unix_ms(deadline)),
+            KeyTtl::ExpiresAt(deadline) => {
+                let remaining = (unix_ms(deadline) - unix_ms(SystemTime::now())).max(0);
+                match self.unit {
+                    // Round to the nearest second, as Redis does.
+                    TimeUnit::Seconds => (remaining + 500) / 1000,
+                    TimeUnit::Millis => remaining,
+                }
+            }
+        };
+        Ok(RespDT::Integer(reply))
+    }
+}
+
+impl CommandApply for PersistCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.persist(&self.key) as i64))
+    }
+}
@@ -0,0 +1,381 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        geo::{GeoOrigin, GeoQuery, GeoSort},
+        geohash::{self, GeoShape, GEO_STEP_MAX},
+        numeric,
+        zset::{ZAddFlags, ZAddOutcome},
+    },
+};
+
+use super::command::{check_arity, lower, parse_float, parse_int, CommandApply, CommandError};
+
+#[derive(Debug)]
+pub struct GeoAddCommand {
+    pub key: Bytes,
+    pub flags: ZAddFlags,
+    /// `(geohash score, member)` pairs, ready for ZADD.
+    pub elements: Vec<(f64, Bytes)>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct GeoDistCommand {
+    pub key: Bytes,
+    pub a: Bytes,
+    pub b: Bytes,
+    /// Meters per unit of the reply.
+    pub unit: f64,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct GeoHashCommand {
+    pub key: Bytes,
+    pub members: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct GeoPosCommand {
+    pub key: Bytes,
+    pub members: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+/// GEOSEARCH, and GEOSEARCHSTORE when `destination` is set.
+#[derive(Debug)]
+pub struct GeoSearchCommand {
+    pub key: Bytes,
+    pub destination: Option<Bytes>,
+    pub query: GeoQuery,
+    /// Meters per unit of the shape, distances in the reply and STOREDIST.
+    pub unit: f64,
+    pub with_coord: bool,
+    pub with_dist: bool,
+    pub with_hash: bool,
+    pub store_dist: bool,
+    pub cache: Arc<Db>,
+}
+
+fn parse_unit(arg: &[u8]) -> Result<f64, CommandError> {
+    match lower(arg).as_str() {
+        "m" => Ok(1.0),
+        "km" => Ok(1000.0),
+        "ft" => Ok(0.3048),
+        "mi" => Ok(1609.34),
+        _ => Err(CommandError::UnsupportedUnit),
+    }
+}
+
+fn parse_lon_lat(lon: &[u8], lat: &[u8]) -> Result<(f64, f64), CommandError> {
+    let (lon, lat) = (parse_float(lon)?, parse_float(lat)?);
+    if !geohash::valid(lon, lat) {
+        return Err(CommandError::InvalidLonLat(format!("{lon:.6},{lat:.6}")));
+    }
+    Ok((lon, lat))
+}
+
+fn parse_size(arg: &[u8], what: &'static str) -> Result<f64, CommandError> {
+    numeric::parse_float(arg).ok_or(CommandError::NeedNumeric(what))
+}
+
+/// A coordinate as Redis prints it: 17 decimals, trailing zeros dropped.
+fn coord_resp(value: f64) -> RespDT {
+    let formatted = format!("{value:.17}");
+    RespDT::bulk(
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string(),
+    )
+}
+
+fn position_resp((lon, lat): (f64, f64)) -> RespDT {
+    RespDT::Array(vec![coord_resp(lon), coord_resp(lat)])
+}
+
+impl GeoAddCommand {
+    /// Parses `GEOADD key [NX | XX] [CH] longitude latitude member ...`.
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 4, usize::MAX)?;
+        let mut flags = ZAddFlags::default();
+        let mut idx = 1;
+        while idx < args.len() {
+            match lower(&args[idx]).as_str() {
+                "nx" => flags.nx = true,
+                "xx" => flags.xx = true,
+                "ch" => flags.ch = true,
+                _ => break,
+            }
+            idx += 1;
+        }
+        let triples = args[idx..].chunks_exact(3);
+        if !triples.remainder().is_empty() || idx == args.len() || (flags.nx && flags.xx) {
+            return Err(CommandError::InvalidCommand);
+        }
+        let elements = triples
+            .map(|triple| {
+                let (lon, lat) = parse_lon_lat(&triple[0], &triple[1])?;
+                let score = geohash::encode(lon, lat, GEO_STEP_MAX) as f64;
+                Ok((score, triple[2].clone()))
+            })
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        Ok(GeoAddCommand {
+            key: args[0].clone(),
+            flags,
+            elements,
+            cache,
+        })
+    }
+}
+
+impl GeoDistCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 4)?;
+        Ok(GeoDistCommand {
+            key: args[0].clone(),
+            a: args[1].clone(),
+            b: args[2].clone(),
+            unit: args.get(3).map_or(Ok(1.0), |unit| parse_unit(unit))?,
+            cache,
+        })
+    }
+}
+
+impl GeoHashCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(GeoHashCommand {
+            key,
+            members: args,
+            cache,
+        })
+    }
+}
+
+impl GeoPosCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(GeoPosCommand {
+            key,
+            members: args,
+            cache,
+        })
+    }
+}
+
+impl GeoSearchCommand {
+    /// Parses `GEOSEARCH key FROMMEMBER member | FROMLONLAT lon lat
+    /// BYRADIUS radius unit | BYBOX width height unit [ASC | DESC]
+    /// [COUNT count [ANY]] [WITHCOORD] [WITHDIST] [WITHHASH]`. With `store`,
+    /// the destination comes first and STOREDIST replaces the WITH options.
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        store: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        let start = 1 + store as usize;
+        check_arity(cmd, &args, start + 5, usize::MAX)?;
+        let (mut from_member, mut from_lon_lat) = (None, None);
+        let (mut by_radius, mut by_box) = (None, None);
+        let (mut sort, mut count) = (GeoSort::None, None);
+        let (mut with_coord, mut with_dist, mut with_hash) = (false, false, false);
+        let mut store_dist = false;
+        let mut idx = start;
+        while idx < args.len() {
+            let opt = lower(&args[idx]);
+            let remaining = args.len() - idx - 1;
+            match opt.as_str() {
+                "frommember" if remaining >= 1 => {
+                    from_member = Some(GeoOrigin::Member(args[idx + 1].clone()));
+                    idx += 1;
+                }
+                "fromlonlat" if remaining >= 2 => {
+                    let (lon, lat) = parse_lon_lat(&args[idx + 1], &args[idx + 2])?;
+                    from_lon_lat = Some(GeoOrigin::LonLat(lon, lat));
+                    idx += 2;
+                }
+                "byradius" if remaining >= 2 => {
+                    let radius = parse_size(&args[idx + 1], "radius")?;
+                    if radius < 0.0 {
+                        return Err(CommandError::NegativeRadius);
+                    }
+                    let unit = parse_unit(&args[idx + 2])?;
+                    by_radius = Some((GeoShape::Radius(radius * unit), unit));
+                    idx += 2;
+                }
+                "bybox" if remaining >= 3 => {
+                    let width = parse_size(&args[idx + 1], "width")?;
+                    let height = parse_size(&args[idx + 2], "height")?;
+                    if width < 0.0 || height < 0.0 {
+                        return Err(CommandError::NegativeBox);
+                    }
+                    let unit = parse_unit(&args[idx + 3])?;
+                    let shape = GeoShape::Box {
+                        width: width * unit,
+                        height: height * unit,
+                    };
+                    by_box = Some((shape, unit));
+                    idx += 3;
+                }
+                "asc" => sort = GeoSort::Asc,
+                "desc" => sort = GeoSort::Desc,
+                "count" if remaining >= 1 => {
+                    let n = parse_int(&args[idx + 1])
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or(CommandError::CountNotPositive)?;
+                    // ANY only counts as such right after COUNT.
+                    let any = args
+                        .get(idx + 2)
+                        .is_some_and(|arg| arg.eq_ignore_ascii_case(b"any"));
+                    count = Some((n as usize, any));
+                    idx += 1 + any as usize;
+                }
+                "withcoord" => with_coord = true,
+                "withdist" => with_dist = true,
+                "withhash" => with_hash = true,
+                "storedist" if store => store_dist = true,
+                _ => return Err(CommandError::InvalidCommand),
+            }
+            idx += 1;
+        }
+        let origin = match (from_member, from_lon_lat) {
+            (Some(origin), None) | (None, Some(origin)) => origin,
+            _ => return Err(CommandError::GeoSearchFrom(cmd.to_string())),
+        };
+        let (shape, unit) = match (by_radius, by_box) {
+            (Some(by), None) | (None, Some(by)) => by,
+            _ => return Err(CommandError::GeoSearchBy(cmd.to_string())),
+        };
+        if store && (with_coord || with_dist || with_hash) {
+            return Err(CommandError::GeoStoreWithOptions(cmd.to_string()));
+        }
+        Ok(GeoSearchCommand {
+            key: args[start - 1].clone(),
+            destination: store.then(|| args[0].clone()),
+            query: GeoQuery {
+                origin,
+                shape,
+                sort,
+                count,
+            },
+            unit,
+            with_coord,
+            with_dist,
+            with_hash,
+            store_dist,
+            cache,
+        })
+    }
+}
+
+impl CommandApply for GeoAddCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let outcomes = ks.zadd(&self.key, self.flags, &self.elements)?;
+        let changed = outcomes
+            .iter()
+            .filter(|outcome| match outcome {
+                ZAddOutcome::Added(_) => true,
+                ZAddOutcome::Updated(_) => self.flags.ch,
+                _ => false,
+            })
+            .count();
+        Ok(RespDT::Integer(changed as i64))
+    }
+}
+
+impl CommandApply for GeoDistCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(match ks.geodist(&self.key, &self.a, &self.b)? {
+            Some(meters) => RespDT::bulk(format!("{:.4}", meters / self.unit)),
+            None => RespDT::Null,
+        })
+    }
+}
+
+impl CommandApply for GeoHashCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let hashes = ks.geohash(&self.key, &self.members)?;
+        Ok(RespDT::Array(
+            hashes
+                .into_iter()
+                .map(|hash| hash.map_or(RespDT::Null, RespDT::bulk))
+                .collect(),
+        ))
+    }
+}
+
+impl CommandApply for GeoPosCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let positions = ks.geopos(&self.key, &self.members)?;
+        Ok(RespDT::Array(
+            positions
+                .into_iter()
+                .map(|position| position.map_or(RespDT::NullArray, position_resp))
+                .collect(),
+        ))
+    }
+}
+
+impl CommandApply for GeoSearchCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        if let Some(destination) = &self.destination {
+            let store_dist = self.store_dist.then_some(self.unit);
+            let stored = ks.geosearchstore(destination, &self.key, &self.query, store_dist)?;
+            return Ok(RespDT::Integer(stored as i64));
+        }
+        let matches = ks.geosearch(&self.key, &self.query)?;
+        let plain = !(self.with_coord || self.with_dist || self.with_hash);
+        Ok(RespDT::Array(
+            matches
+                .into_iter()
+                .map(|found| {
+                    if plain {
+                        return RespDT::Bulk(found.member);
+                    }
+                    let mut item = vec![RespDT::Bulk(found.member)];
+                    if self.with_dist {
+                        item.push(RespDT::bulk(format!("{:.4}", found.distance / self.unit)));
+                    }
+                    if self.with_hash {
+                        item.push(RespDT::Integer(found.hash as i64));
+                    }
+                    if self.with_coord {
+                        item.push(position_resp((found.lon, found.lat)));
+                    }
+                    RespDT::Array(item)
+                })
+                .collect(),
+        ))
+    }
+}
@@ -0,0 +1,493 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::cache::{Db, DbError, Keyspace},
+};
+
+use super::command::{
+    bulk_array, check_arity, parse_float, parse_int, parse_random_count, CommandApply, CommandError,
+};
+
+fn flatten_pairs(pairs: Vec<(Bytes, Bytes)>, with_values: bool) -> RespDT {
+    let mut items = Vec::with_capacity(pairs.len() * 2);
+    for (field, value) in pairs {
+        items.push(RespDT::Bulk(field));
+        if with_values {
+            items.push(RespDT::Bulk(value));
+        }
+    }
+    RespDT::Array(items)
+}
+
+#[derive(Debug)]
+pub struct HSetCommand {
+    pub key: Bytes,
+    pub pairs: Vec<(Bytes, Bytes)>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HSetNxCommand {
+    pub key: Bytes,
+    pub field: Bytes,
+    pub value: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HGetCommand {
+    pub key: Bytes,
+    pub field: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HMGetCommand {
+    pub key: Bytes,
+    pub fields: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HGetAllCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HDelCommand {
+    pub key: Bytes,
+    pub fields: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HExistsCommand {
+    pub key: Bytes,
+    pub field: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HLenCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HKeysCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HValsCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HIncrByCommand {
+    pub key: Bytes,
+    pub field: Bytes,
+    pub delta: i64,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HIncrByFloatCommand {
+    pub key: Bytes,
+    pub field: Bytes,
+    pub delta: f64,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HStrLenCommand {
+    pub key: Bytes,
+    pub field: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct HRandFieldCommand {
+    pub key: Bytes,
+    pub count: Option<i64>,
+    pub with_values: bool,
+    pub cache: Arc<Db>,
+}
+
+impl HSetCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, usize::MAX)?;
+        let chunks = args[1..].chunks_exact(2);
+        if !chunks.remainder().is_empty() {
+            return Err(CommandError::InvalidArguments(cmd.to_string()));
+        }
+        let pairs = chunks
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        Ok(HSetCommand {
+            key: args[0].clone(),
+            pairs,
+            cache,
+        })
+    }
+}
+
+impl HSetNxCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(HSetNxCommand {
+            key: args[0].clone(),
+            field: args[1].clone(),
+            value: args[2].clone(),
+            cache,
+        })
+    }
+}
+
+impl HGetCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(HGetCommand {
+            key: args[0].clone(),
+            field: args[1].clone(),
+            cache,
+        })
+    }
+}
+
+impl HMGetCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(HMGetCommand {
+            key,
+            fields: args,
+            cache,
+        })
+    }
+}
+
+impl HGetAllCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(HGetAllCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl HDelCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(HDelCommand {
+            key,
+            fields: args,
+            cache,
+        })
+    }
+}
+
+impl HExistsCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(HExistsCommand {
+            key: args[0].clone(),
+            field: args[1].clone(),
+            cache,
+        })
+    }
+}
+
+impl HLenCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(HLenCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl HKeysCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(HKeysCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl HValsCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(HValsCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl HIncrByCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(HIncrByCommand {
+            key: args[0].clone(),
+            field: args[1].clone(),
+            delta: parse_int(&args[2])?,
+            cache,
+        })
+    }
+}
+
+impl HIncrByFloatCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        let delta = parse_float(&args[2])?;
+        if delta.is_infinite() {
+            return Err(CommandError::NotFloat);
+        }
+        Ok(HIncrByFloatCommand {
+            key: args[0].clone(),
+            field: args[1].clone(),
+            delta,
+            cache,
+        })
+    }
+}
+
+impl HStrLenCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(HStrLenCommand {
+            key: args[0].clone(),
+            field: args[1].clone(),
+            cache,
+        })
+    }
+}
+
+impl HRandFieldCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 3)?;
+        let count = args.get(1).map(|c| parse_random_count(c)).transpose()?;
+        let with_values = match args.get(2) {
+            Some(opt) if opt.eq_ignore_ascii_case(b"withvalues") => true,
+            Some(_) => return Err(CommandError::InvalidCommand),
+            None => false,
+        };
+        Ok(HRandFieldCommand {
+            key: args[0].clone(),
+            count,
+            with_values,
+            cache,
+        })
+    }
+}
+
+impl CommandApply for HSetCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.hset(&self.key, &self.pairs)?))
+    }
+}
+
+impl CommandApply for HSetNxCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let set = ks.hsetnx(&self.key, &self.field, &self.value)?;
+        Ok(RespDT::Integer(set as i64))
+    }
+}
+
+impl CommandApply for HGetCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(ks
+            .hget(&self.key, &self.field)?
+            .map_or(RespDT::Null, RespDT::Bulk))
+    }
+}
+
+impl CommandApply for HMGetCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Array(
+            ks.hmget(&self.key, &self.fields)?
+                .into_iter()
+                .map(|v| v.map_or(RespDT::Null, RespDT::Bulk))
+                .collect(),
+        ))
+    }
+}
+
+impl CommandApply for HGetAllCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Map(
+            ks.hgetall(&self.key)?
+                .into_iter()
+                .map(|(field, value)| (RespDT::Bulk(field), RespDT::Bulk(value)))
+                .collect(),
+        ))
+    }
+}
+
+impl CommandApply for HDelCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.hdel(&self.key, &self.fields)?))
+    }
+}
+
+impl CommandApply for HExistsCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.hexists(&self.key, &self.field)? as i64))
+    }
+}
+
+impl CommandApply for HLenCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.hlen(&self.key)?))
+    }
+}
+
+impl CommandApply for HKeysCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let fields = ks.hgetall(&self.key)?.into_iter().map(|(f, _)| f).collect();
+        Ok(bulk_array(fields))
+    }
+}
+
+impl CommandApply for HValsCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let values = ks.hgetall(&self.key)?.into_iter().map(|(_, v)| v).collect();
+        Ok(bulk_array(values))
+    }
+}
+
+impl CommandApply for HIncrByCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.hincrby(
+            &self.key,
+            &self.field,
+            self.delta,
+        )?))
+    }
+}
+
+impl CommandApply for HIncrByFloatCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Bulk(ks.hincrbyfloat(
+            &self.key,
+            &self.field,
+            self.delta,
+        )?))
+    }
+}
+
+impl CommandApply for HStrLenCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.hstrlen(&self.key, &self.field)?))
+    }
+}
+
+impl CommandApply for HRandFieldCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        match self.count {
+            Some(count) => Ok(flatten_pairs(
+                ks.hrandfield(&self.key, count)?,
+                self.with_values,
+            )),
+            None => Ok(ks
+                .hrandfield(&self.key, 1)?
+                .pop()
+                .map_or(RespDT::Null, |(field, _)| RespDT::Bulk(field))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<HRandFieldCommand, CommandError> {
+        let args = args
+            .iter()
+            .map(|a| Bytes::copy_from_slice(a.as_bytes()))
+            .collect();
+        HRandFieldCommand::parse("hrandfield", args, Arc::new(Db::new()))
+    }
+
+    #[test]
+    fn test_randfield_count_range() {
+        assert!(matches!(
+            parse(&["h", "-9223372036854775808"]),
+            Err(CommandError::OutOfRange)
+        ));
+        assert!(matches!(
+            parse(&["h", "-4611686018427387904", "WITHVALUES"]),
+            Err(CommandError::OutOfRange)
+        ));
+        assert!(parse(&["h", "-4611686018427387903"]).is_ok());
+        assert!(parse(&["h", "4611686018427387903"]).is_ok());
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::cache::{Db, DbError, Keyspace},
+};
+
+use super::command::{check_arity, CommandApply, CommandError, OK_RESP};
+
+#[derive(Debug)]
+pub struct PfAddCommand {
+    pub key: Bytes,
+    pub elements: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct PfCountCommand {
+    pub keys: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct PfMergeCommand {
+    pub destination: Bytes,
+    pub sources: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+impl PfAddCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(PfAddCommand {
+            key,
+            elements: args,
+            cache,
+        })
+    }
+}
+
+impl PfCountCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        Ok(PfCountCommand { keys: args, cache })
+    }
+}
+
+impl PfMergeCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        let destination = args.remove(0);
+        Ok(PfMergeCommand {
+            destination,
+            sources: args,
+            cache,
+        })
+    }
+}
+
+impl CommandApply for PfAddCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.pfadd(&self.key, &self.elements)? as i64))
+    }
+}
+
+impl CommandApply for PfCountCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.pfcount(&self.keys)? as i64))
+    }
+}
+
+impl CommandApply for PfMergeCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        ks.pfmerge(&self.destination, &self.sources)?;
+        Ok(RespDT::SimpleString(OK_RESP.to_string()))
+    }
+}
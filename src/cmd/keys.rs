@@ -0,0 +1,275 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::cache::{Db, DbError, Keyspace},
+};
+
+use super::command::{
+    bulk_array, check_arity, lower, parse_int, CommandApply, CommandError, OK_RESP,
+};
+
+/// DEL and UNLINK. There is no lazy freeing, so both delete in place.
+#[derive(Debug)]
+pub struct DelCommand {
+    pub keys: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+/// EXISTS and TOUCH, which without access times to update is the same thing.
+#[derive(Debug)]
+pub struct ExistsCommand {
+    pub keys: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct TypeCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct KeysCommand {
+    pub pattern: Bytes,
+    pub cache: Arc<Db>,
+}
+
+/// RENAME and RENAMENX.
+#[derive(Debug)]
+pub struct RenameCommand {
+    pub source: Bytes,
+    pub destination: Bytes,
+    pub nx: bool,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct CopyCommand {
+    pub source: Bytes,
+    pub destination: Bytes,
+    pub replace: bool,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct RandomKeyCommand {
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct DbSizeCommand {
+    pub cache: Arc<Db>,
+}
+
+/// FLUSHDB and FLUSHALL, the same thing with a single database. ASYNC is
+/// accepted but, as with UNLINK, everything is freed in place.
+#[derive(Debug)]
+pub struct FlushCommand {
+    pub cache: Arc<Db>,
+}
+
+impl DelCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        Ok(DelCommand { keys: args, cache })
+    }
+}
+
+impl ExistsCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        Ok(ExistsCommand { keys: args, cache })
+    }
+}
+
+impl TypeCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(TypeCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl KeysCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(KeysCommand {
+            pattern: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl RenameCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        nx: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(RenameCommand {
+            source: args[0].clone(),
+            destination: args[1].clone(),
+            nx,
+            cache,
+        })
+    }
+}
+
+impl CopyCommand {
+    /// Parses `COPY source destination [DB index] [REPLACE]`. There is a
+    /// single database, so only index 0 is accepted.
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let mut replace = false;
+        let mut idx = 2;
+        while idx < args.len() {
+            match lower(&args[idx]).as_str() {
+                "replace" => replace = true,
+                "db" if idx + 1 < args.len() => {
+                    idx += 1;
+                    if parse_int(&args[idx])? != 0 {
+                        return Err(CommandError::DbIndexOutOfRange);
+                    }
+                }
+                _ => return Err(CommandError::InvalidCommand),
+            }
+            idx += 1;
+        }
+        if args[0] == args[1] {
+            return Err(CommandError::SameObject);
+        }
+        Ok(CopyCommand {
+            source: args[0].clone(),
+            destination: args[1].clone(),
+            replace,
+            cache,
+        })
+    }
+}
+
+impl RandomKeyCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 0, 0)?;
+        Ok(RandomKeyCommand { cache })
+    }
+}
+
+impl DbSizeCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 0, 0)?;
+        Ok(DbSizeCommand { cache })
+    }
+}
+
+impl FlushCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 0, 1)?;
+        match args.first().map(|mode| lower(mode)).as_deref() {
+            None | Some("sync") | Some("async") => Ok(FlushCommand { cache }),
+            Some(_) => Err(CommandError::InvalidCommand),
+        }
+    }
+}
+
+impl CommandApply for DelCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.del(&self.keys) as i64))
+    }
+}
+
+impl CommandApply for ExistsCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.exists(&self.keys) as i64))
+    }
+}
+
+impl CommandApply for TypeCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::SimpleString(ks.type_name(&self.key).to_string()))
+    }
+}
+
+impl CommandApply for KeysCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(bulk_array(ks.keys(&self.pattern)))
+    }
+}
+
+impl CommandApply for RenameCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let renamed = ks.rename(&self.source, &self.destination, self.nx)?;
+        Ok(match self.nx {
+            true => RespDT::Integer(renamed as i64),
+            false => RespDT::SimpleString(OK_RESP.to_string()),
+        })
+    }
+}
+
+impl CommandApply for CopyCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let copied = ks.copy(&self.source, &self.destination, self.replace);
+        Ok(RespDT::Integer(copied as i64))
+    }
+}
+
+impl CommandApply for RandomKeyCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(ks.random_key().map_or(RespDT::Null, RespDT::Bulk))
+    }
+}
+
+impl CommandApply for DbSizeCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.key_count() as i64))
+    }
+}
+
+impl CommandApply for FlushCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        ks.flush();
+        Ok(RespDT::SimpleString(OK_RESP.to_string()))
+    }
+}
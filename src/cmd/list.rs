@@ -0,0 +1,518 @@
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        list::ListEnd,
+    },
+};
+
+use super::command::{
+    bulk_array, check_arity, lower, parse_float, parse_int, respond_blocking, CommandApply,
+    CommandError, OK_RESP,
+};
+
+/// Parses a blocking timeout given in (possibly fractional) seconds.
+pub(crate) fn parse_timeout(arg: &[u8]) -> Result<Duration, CommandError> {
+    let secs = parse_float(arg).map_err(|_| CommandError::TimeoutNotFloat)?;
+    if secs < 0.0 {
+        return Err(CommandError::NegativeTimeout);
+    }
+    Duration::try_from_secs_f64(secs).map_err(|_| CommandError::TimeoutNotFloat)
+}
+
+fn parse_list_end(arg: &[u8]) -> Result<ListEnd, CommandError> {
+    match lower(arg).as_str() {
+        "left" => Ok(ListEnd::Left),
+        "right" => Ok(ListEnd::Right),
+        _ => Err(CommandError::InvalidCommand),
+    }
+}
+
+#[derive(Debug)]
+pub struct PushCommand {
+    pub key: Bytes,
+    pub end: ListEnd,
+    pub elements: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct PopCommand {
+    pub key: Bytes,
+    pub end: ListEnd,
+    pub count: Option<usize>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct LRangeCommand {
+    pub key: Bytes,
+    pub start: i64,
+    pub stop: i64,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct LLenCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct LIndexCommand {
+    pub key: Bytes,
+    pub index: i64,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct LSetCommand {
+    pub key: Bytes,
+    pub index: i64,
+    pub element: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct LRemCommand {
+    pub key: Bytes,
+    pub count: i64,
+    pub element: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct LTrimCommand {
+    pub key: Bytes,
+    pub start: i64,
+    pub stop: i64,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct LInsertCommand {
+    pub key: Bytes,
+    pub before: bool,
+    pub pivot: Bytes,
+    pub element: Bytes,
+    pub cache: Arc<Db>,
+}
+
+/// BLPOP and BRPOP.
+#[derive(Debug)]
+pub struct BlockingPopCommand {
+    pub keys: Vec<Bytes>,
+    pub end: ListEnd,
+    pub timeout: Duration,
+    pub cache: Arc<Db>,
+}
+
+/// LMOVE, or BLMOVE when `timeout` is set.
+#[derive(Debug)]
+pub struct LMoveCommand {
+    pub source: Bytes,
+    pub destination: Bytes,
+    pub from: ListEnd,
+    pub to: ListEnd,
+    pub timeout: Option<Duration>,
+    pub cache: Arc<Db>,
+}
+
+/// LMPOP, or BLMPOP when `timeout` is set.
+#[derive(Debug)]
+pub struct LMPopCommand {
+    pub keys: Vec<Bytes>,
+    pub end: ListEnd,
+    pub count: usize,
+    pub timeout: Option<Duration>,
+    pub cache: Arc<Db>,
+}
+
+impl PushCommand {
+    pub fn parse(
+        cmd: &str,
+        mut args: Vec<Bytes>,
+        end: ListEnd,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(PushCommand {
+            key,
+            end,
+            elements: args,
+            cache,
+        })
+    }
+}
+
+impl PopCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        end: ListEnd,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 2)?;
+        let count = match args.get(1) {
+            Some(count) => {
+                let count = parse_int(count).map_err(|_| CommandError::NotPositive)?;
+                if count < 0 {
+                    return Err(CommandError::NotPositive);
+                }
+                Some(count as usize)
+            }
+            None => None,
+        };
+        Ok(PopCommand {
+            key: args[0].clone(),
+            end,
+            count,
+            cache,
+        })
+    }
+}
+
+impl LRangeCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(LRangeCommand {
+            key: args[0].clone(),
+            start: parse_int(&args[1])?,
+            stop: parse_int(&args[2])?,
+            cache,
+        })
+    }
+}
+
+impl LLenCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(LLenCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl LIndexCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(LIndexCommand {
+            key: args[0].clone(),
+            index: parse_int(&args[1])?,
+            cache,
+        })
+    }
+}
+
+impl LSetCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(LSetCommand {
+            key: args[0].clone(),
+            index: parse_int(&args[1])?,
+            element: args[2].clone(),
+            cache,
+        })
+    }
+}
+
+impl LRemCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(LRemCommand {
+            key: args[0].clone(),
+            count: parse_int(&args[1])?,
+            element: args[2].clone(),
+            cache,
+        })
+    }
+}
+
+impl LTrimCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(LTrimCommand {
+            key: args[0].clone(),
+            start: parse_int(&args[1])?,
+            stop: parse_int(&args[2])?,
+            cache,
+        })
+    }
+}
+
+impl LInsertCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 4, 4)?;
+        let before = match lower(&args[1]).as_str() {
+            "before" => true,
+            "after" => false,
+            _ => return Err(CommandError::InvalidCommand),
+        };
+        Ok(LInsertCommand {
+            key: args[0].clone(),
+            before,
+            pivot: args[2].clone(),
+            element: args[3].clone(),
+            cache,
+        })
+    }
+}
+
+impl BlockingPopCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        end: ListEnd,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let (timeout, keys) = args.split_last().expect("arity checked");
+        Ok(BlockingPopCommand {
+            keys: keys.to_vec(),
+            end,
+            timeout: parse_timeout(timeout)?,
+            cache,
+        })
+    }
+
+    pub async fn respond(&self) -> Result<RespDT, Box<dyn std::error::Error>> {
+        respond_blocking(self, &self.keys, Some(self.timeout), RespDT::NullArray).await
+    }
+}
+
+impl LMoveCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        blocking: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        let arity = if blocking { 5 } else { 4 };
+        check_arity(cmd, &args, arity, arity)?;
+        let timeout = match blocking {
+            true => Some(parse_timeout(&args[4])?),
+            false => None,
+        };
+        Ok(LMoveCommand {
+            source: args[0].clone(),
+            destination: args[1].clone(),
+            from: parse_list_end(&args[2])?,
+            to: parse_list_end(&args[3])?,
+            timeout,
+            cache,
+        })
+    }
+
+    pub async fn respond(&self) -> Result<RespDT, Box<dyn std::error::Error>> {
+        let keys = [self.source.clone()];
+        respond_blocking(self, &keys, self.timeout, RespDT::Null).await
+    }
+}
+
+impl LMPopCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        blocking: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        let (timeout, args) = match blocking {
+            true => {
+                check_arity(cmd, &args, 4, usize::MAX)?;
+                (Some(parse_timeout(&args[0])?), &args[1..])
+            }
+            false => {
+                check_arity(cmd, &args, 3, usize::MAX)?;
+                (None, &args[..])
+            }
+        };
+        let numkeys = parse_int(&args[0])?;
+        if numkeys <= 0 {
+            return Err(CommandError::NumKeysNotPositive);
+        }
+        let numkeys = numkeys as usize;
+        if numkeys > args.len() - 2 {
+            return Err(CommandError::NumKeysTooMany);
+        }
+        let end = parse_list_end(&args[numkeys + 1])?;
+        let count = match &args[numkeys + 2..] {
+            [] => 1,
+            [opt, count] if opt.eq_ignore_ascii_case(b"count") => match parse_int(count) {
+                Ok(count) if count > 0 => count as usize,
+                _ => return Err(CommandError::CountNotGreaterThanZero),
+            },
+            _ => return Err(CommandError::InvalidCommand),
+        };
+        Ok(LMPopCommand {
+            keys: args[1..=numkeys].to_vec(),
+            end,
+            count,
+            timeout,
+            cache,
+        })
+    }
+
+    pub async fn respond(&self) -> Result<RespDT, Box<dyn std::error::Error>> {
+        respond_blocking(self, &self.keys, self.timeout, RespDT::NullArray).await
+    }
+}
+
+impl CommandApply for PushCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.push(
+            &self.key,
+            self.end,
+            &self.elements,
+        )?))
+    }
+}
+
+impl CommandApply for PopCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let popped = ks.pop(&self.key, self.end, self.count.unwrap_or(1))?;
+        Ok(match (popped, self.count) {
+            (None, Some(_)) => RespDT::NullArray,
+            (None, None) => RespDT::Null,
+            (Some(items), Some(_)) => bulk_array(items),
+            (Some(mut items), None) => items.pop().map_or(RespDT::Null, RespDT::Bulk),
+        })
+    }
+}
+
+impl CommandApply for LRangeCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(bulk_array(ks.lrange(&self.key, self.start, self.stop)?))
+    }
+}
+
+impl CommandApply for LLenCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.llen(&self.key)?))
+    }
+}
+
+impl CommandApply for LIndexCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(ks
+            .lindex(&self.key, self.index)?
+            .map_or(RespDT::Null, RespDT::Bulk))
+    }
+}
+
+impl CommandApply for LSetCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        ks.lset(&self.key, self.index, self.element.clone())?;
+        Ok(RespDT::SimpleString(OK_RESP.to_string()))
+    }
+}
+
+impl CommandApply for LRemCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.lrem(
+            &self.key,
+            self.count,
+            &self.element,
+        )?))
+    }
+}
+
+impl CommandApply for LTrimCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        ks.ltrim(&self.key, self.start, self.stop)?;
+        Ok(RespDT::SimpleString(OK_RESP.to_string()))
+    }
+}
+
+impl CommandApply for LInsertCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.linsert(
+            &self.key,
+            self.before,
+            &self.pivot,
+            self.element.clone(),
+        )?))
+    }
+}
+
+impl CommandApply for BlockingPopCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(match ks.lmpop(&self.keys, self.end, 1)? {
+            Some((key, mut popped)) => {
+                let element = popped.pop().expect("popped lists are never empty");
+                bulk_array(vec![key, element])
+            }
+            None => RespDT::NullArray,
+        })
+    }
+}
+
+impl CommandApply for LMoveCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(ks
+            .lmove(&self.source, &self.destination, self.from, self.to)?
+            .map_or(RespDT::Null, RespDT::Bulk))
+    }
+}
+
+impl CommandApply for LMPopCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(match ks.lmpop(&self.keys, self.end, self.count)? {
+            Some((key, popped)) => RespDT::Array(vec![RespDT::Bulk(key), bulk_array(popped)]),
+            None => RespDT::NullArray,
+        })
+    }
+}
@@ -1,3 +1,19 @@
+pub mod bitmap;
 pub mod command;
+pub mod expire;
+pub mod geo;
+pub mod hash;
+pub mod hyperloglog;
+pub mod keys;
+pub mod list;
+pub mod pubsub;
+pub mod scan;
+pub mod server;
+pub mod set;
+pub mod stream;
+pub mod stream_group;
+pub mod string;
+pub mod transaction;
+pub mod zset;
 
-pub use command::Command;
\ No newline at end of file
+pub use command::Command;
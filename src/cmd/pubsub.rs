@@ -0,0 +1,309 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        pubsub::{Message, Route, Subscriber, SubscriptionKind},
+        slot::key_hash_slot,
+    },
+};
+
+use super::command::{bulk_array, check_arity, lower, CommandApply, CommandError};
+
+/// SUBSCRIBE, PSUBSCRIBE, SSUBSCRIBE and their UNSUBSCRIBE counterparts.
+/// They change the connection's own state, so the connection runs them
+/// itself.
+#[derive(Debug)]
+pub struct SubscribeCommand {
+    pub channels: Vec<Bytes>,
+    pub kind: SubscriptionKind,
+}
+
+/// PUBLISH and SPUBLISH.
+#[derive(Debug)]
+pub struct PublishCommand {
+    pub channel: Bytes,
+    pub message: Bytes,
+    pub sharded: bool,
+    pub cache: Arc<Db>,
+}
+
+/// The PUBSUB subcommands. CHANNELS and NUMSUB look at regular channels,
+/// SHARDCHANNELS and SHARDNUMSUB at shard channels.
+#[derive(Debug)]
+pub enum PubSubView {
+    Channels(SubscriptionKind, Option<Bytes>),
+    NumSub(SubscriptionKind, Vec<Bytes>),
+    NumPat,
+}
+
+/// PUBSUB, the introspection command.
+#[derive(Debug)]
+pub struct PubSubCommand {
+    pub view: PubSubView,
+    pub cache: Arc<Db>,
+}
+
+impl SubscribeCommand {
+    /// Parses SUBSCRIBE, PSUBSCRIBE or SSUBSCRIBE, which need at least one
+    /// channel.
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        kind: SubscriptionKind,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        Self::parse_unsubscribe(args, kind)
+    }
+
+    /// Parses one of the UNSUBSCRIBE forms, where no channel means all of
+    /// them. Shard channels named together must share a slot, as they
+    /// would have to in a cluster.
+    pub fn parse_unsubscribe(
+        args: Vec<Bytes>,
+        kind: SubscriptionKind,
+    ) -> Result<Self, CommandError> {
+        if kind == SubscriptionKind::Shard {
+            let mut slots = args.iter().map(|channel| key_hash_slot(channel));
+            if let Some(first) = slots.next() {
+                if slots.any(|slot| slot != first) {
+                    return Err(CommandError::CrossSlot);
+                }
+            }
+        }
+        Ok(SubscribeCommand {
+            channels: args,
+            kind,
+        })
+    }
+}
+
+impl PublishCommand {
+    pub fn parse(
+        cmd: &str,
+        mut args: Vec<Bytes>,
+        sharded: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        let message = args.pop().unwrap();
+        let channel = args.pop().unwrap();
+        Ok(PublishCommand {
+            channel,
+            message,
+            sharded,
+            cache,
+        })
+    }
+}
+
+impl PubSubCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        let sub = lower(&args[0]);
+        let sub_cmd = format!("{}|{}", cmd, sub);
+        let view = match sub.as_str() {
+            "channels" | "shardchannels" => {
+                check_arity(&sub_cmd, &args, 1, 2)?;
+                PubSubView::Channels(kind_of(&sub), args.get(1).cloned())
+            }
+            "numsub" | "shardnumsub" => PubSubView::NumSub(kind_of(&sub), args[1..].to_vec()),
+            "numpat" => {
+                check_arity(&sub_cmd, &args, 1, 1)?;
+                PubSubView::NumPat
+            }
+            _ => {
+                return Err(CommandError::UnknownSubcommand(
+                    cmd.to_ascii_uppercase(),
+                    String::from_utf8_lossy(&args[0]).into_owned(),
+                ))
+            }
+        };
+        Ok(PubSubCommand { view, cache })
+    }
+}
+
+fn kind_of(sub: &str) -> SubscriptionKind {
+    if sub.starts_with("shard") {
+        SubscriptionKind::Shard
+    } else {
+        SubscriptionKind::Channel
+    }
+}
+
+impl CommandApply for PublishCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, _ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let broker = &self.cache.pubsub;
+        let receivers = if self.sharded {
+            broker.spublish(&self.channel, &self.message)
+        } else {
+            broker.publish(&self.channel, &self.message)
+        };
+        Ok(RespDT::Integer(receivers as i64))
+    }
+}
+
+impl CommandApply for PubSubCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, _ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let broker = &self.cache.pubsub;
+        Ok(match &self.view {
+            PubSubView::Channels(kind, pattern) => {
+                bulk_array(broker.channels(*kind, pattern.as_deref()))
+            }
+            PubSubView::NumSub(kind, channels) => RespDT::Map(
+                channels
+                    .iter()
+                    .map(|channel| {
+                        (
+                            RespDT::Bulk(channel.clone()),
+                            RespDT::Integer(broker.numsub(*kind, channel) as i64),
+                        )
+                    })
+                    .collect(),
+            ),
+            PubSubView::NumPat => RespDT::Integer(broker.numpat() as i64),
+        })
+    }
+}
+
+/// A connection's subscriptions, in the order they were made, and the
+/// mailbox the broker delivers their messages to. While there are any the
+/// connection is in subscribed mode and only takes a few commands.
+#[derive(Debug)]
+pub struct Subscriptions {
+    subscriber: Subscriber,
+    mailbox: UnboundedReceiver<Message>,
+    channels: Vec<Bytes>,
+    patterns: Vec<Bytes>,
+    shard_channels: Vec<Bytes>,
+}
+
+impl Subscriptions {
+    pub fn new(db: &Db) -> Self {
+        let (subscriber, mailbox) = db.pubsub.subscriber();
+        Subscriptions {
+            subscriber,
+            mailbox,
+            channels: Vec::new(),
+            patterns: Vec::new(),
+            shard_channels: Vec::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !(self.channels.is_empty() && self.patterns.is_empty() && self.shard_channels.is_empty())
+    }
+
+    /// The subscription count confirmations report: shard channels are
+    /// counted apart from the others, as in Redis.
+    fn count(&self, kind: SubscriptionKind) -> usize {
+        match kind {
+            SubscriptionKind::Shard => self.shard_channels.len(),
+            _ => self.channels.len() + self.patterns.len(),
+        }
+    }
+
+    fn list(&mut self, kind: SubscriptionKind) -> &mut Vec<Bytes> {
+        match kind {
+            SubscriptionKind::Channel => &mut self.channels,
+            SubscriptionKind::Pattern => &mut self.patterns,
+            SubscriptionKind::Shard => &mut self.shard_channels,
+        }
+    }
+
+    /// Subscribes to each channel in turn, with one reply per channel.
+    pub fn subscribe(&mut self, db: &Db, cmd: &SubscribeCommand) -> Vec<RespDT> {
+        let name = match cmd.kind {
+            SubscriptionKind::Channel => "subscribe",
+            SubscriptionKind::Pattern => "psubscribe",
+            SubscriptionKind::Shard => "ssubscribe",
+        };
+        let mut replies = Vec::with_capacity(cmd.channels.len());
+        for channel in &cmd.channels {
+            if db.pubsub.subscribe(&self.subscriber, channel, cmd.kind) {
+                self.list(cmd.kind).push(channel.clone());
+            }
+            replies.push(self.confirmation(name, cmd.kind, Some(channel.clone())));
+        }
+        replies
+    }
+
+    /// Unsubscribes from each channel in turn, or from every channel of the
+    /// kind if none is named, with one reply per channel. With nothing to
+    /// unsubscribe from there is still one reply, naming no channel.
+    pub fn unsubscribe(&mut self, db: &Db, cmd: &SubscribeCommand) -> Vec<RespDT> {
+        let name = match cmd.kind {
+            SubscriptionKind::Channel => "unsubscribe",
+            SubscriptionKind::Pattern => "punsubscribe",
+            SubscriptionKind::Shard => "sunsubscribe",
+        };
+        let channels = if cmd.channels.is_empty() {
+            self.list(cmd.kind).clone()
+        } else {
+            cmd.channels.clone()
+        };
+        let mut replies = Vec::with_capacity(channels.len());
+        for channel in channels {
+            db.pubsub
+                .unsubscribe(self.subscriber.id, &channel, cmd.kind);
+            self.list(cmd.kind).retain(|c| *c != channel);
+            replies.push(self.confirmation(name, cmd.kind, Some(channel)));
+        }
+        if replies.is_empty() {
+            replies.push(self.confirmation(name, cmd.kind, None));
+        }
+        replies
+    }
+
+    fn confirmation(
+        &self,
+        name: &'static str,
+        kind: SubscriptionKind,
+        channel: Option<Bytes>,
+    ) -> RespDT {
+        RespDT::Push(vec![
+            RespDT::bulk(name),
+            channel.map_or(RespDT::Null, RespDT::Bulk),
+            RespDT::Integer(self.count(kind) as i64),
+        ])
+    }
+
+    /// Waits for the next message published to one of the subscriptions.
+    pub async fn next_message(&mut self) -> RespDT {
+        // The subscriber keeps a sender alive, so the mailbox never closes.
+        let message = self.mailbox.recv().await.expect("mailbox closed");
+        let mut reply = match message.route {
+            Route::Channel => vec![RespDT::bulk("message")],
+            Route::Pattern(pattern) => vec![RespDT::bulk("pmessage"), RespDT::Bulk(pattern)],
+            Route::Shard => vec![RespDT::bulk("smessage")],
+        };
+        reply.push(RespDT::Bulk(message.channel));
+        reply.push(RespDT::Bulk(message.payload));
+        RespDT::Push(reply)
+    }
+
+    /// Drops every subscription, as the connection closes.
+    pub fn clear(&mut self, db: &Db) {
+        for kind in [
+            SubscriptionKind::Channel,
+            SubscriptionKind::Pattern,
+            SubscriptionKind::Shard,
+        ] {
+            for channel in std::mem::take(self.list(kind)) {
+                db.pubsub.unsubscribe(self.subscriber.id, &channel, kind);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        numeric::format_float,
+    },
+};
+
+use super::command::{check_arity, lower, parse_int, CommandApply, CommandError};
+
+/// Which command a `ScanCommand` was parsed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanKind {
+    Keys,
+    Hash,
+    Set,
+    ZSet,
+}
+
+/// SCAN, HSCAN, SSCAN and ZSCAN. `key` is empty for SCAN.
+#[derive(Debug)]
+pub struct ScanCommand {
+    pub kind: ScanKind,
+    pub key: Bytes,
+    pub cursor: u64,
+    pub count: usize,
+    pub pattern: Option<Bytes>,
+    /// SCAN only: restrict to keys of this type.
+    pub type_name: Option<String>,
+    /// HSCAN only: return fields without their values.
+    pub no_values: bool,
+    pub cache: Arc<Db>,
+}
+
+impl ScanCommand {
+    /// Parses `SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]` and the
+    /// keyed variants, which take the key first, no TYPE, and for HSCAN an
+    /// optional NOVALUES.
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        kind: ScanKind,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        let keyed = kind != ScanKind::Keys;
+        let start = keyed as usize;
+        check_arity(cmd, &args, start + 1, usize::MAX)?;
+        let key = if keyed { args[0].clone() } else { Bytes::new() };
+        let cursor = std::str::from_utf8(&args[start])
+            .ok()
+            .and_then(|cursor| cursor.parse::<u64>().ok())
+            .ok_or(CommandError::InvalidCursor)?;
+        let mut scan = ScanCommand {
+            kind,
+            key,
+            cursor,
+            count: 10,
+            pattern: None,
+            type_name: None,
+            no_values: false,
+            cache,
+        };
+        let mut idx = start + 1;
+        while idx < args.len() {
+            let opt = lower(&args[idx]);
+            let value = args.get(idx + 1);
+            match (opt.as_str(), value) {
+                ("match", Some(pattern)) => scan.pattern = Some(pattern.clone()),
+                ("count", Some(count)) => match parse_int(count)? {
+                    count if count < 1 => return Err(CommandError::InvalidCommand),
+                    count => scan.count = count as usize,
+                },
+                ("type", Some(type_name)) if kind == ScanKind::Keys => {
+                    scan.type_name = Some(lower(type_name))
+                }
+                ("novalues", _) if kind == ScanKind::Hash => {
+                    scan.no_values = true;
+                    idx += 1;
+                    continue;
+                }
+                _ => return Err(CommandError::InvalidCommand),
+            }
+            idx += 2;
+        }
+        Ok(scan)
+    }
+}
+
+impl CommandApply for ScanCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let pattern = self.pattern.as_deref();
+        let (cursor, items) = match self.kind {
+            ScanKind::Keys => {
+                let page = ks.scan(self.cursor, self.count, pattern, self.type_name.as_deref());
+                (
+                    page.cursor,
+                    page.items.into_iter().map(RespDT::Bulk).collect(),
+                )
+            }
+            ScanKind::Hash => {
+                let page = ks.hscan(&self.key, self.cursor, self.count, pattern)?;
+                let mut items = Vec::new();
+                for (field, value) in page.items {
+                    items.push(RespDT::Bulk(field));
+                    if !self.no_values {
+                        items.push(RespDT::Bulk(value));
+                    }
+                }
+                (page.cursor, items)
+            }
+            ScanKind::Set => {
+                let page = ks.sscan(&self.key, self.cursor, self.count, pattern)?;
+                (
+                    page.cursor,
+                    page.items.into_iter().map(RespDT::Bulk).collect(),
+                )
+            }
+            ScanKind::ZSet => {
+                let page = ks.zscan(&self.key, self.cursor, self.count, pattern)?;
+                let items = page
+                    .items
+                    .into_iter()
+                    .flat_map(|(member, score)| {
+                        [RespDT::Bulk(member), RespDT::bulk(format_float(score))]
+                    })
+                    .collect();
+                (page.cursor, items)
+            }
+        };
+        Ok(RespDT::Array(vec![
+            RespDT::bulk(cursor.to_string()),
+            RespDT::Array(items),
+        ]))
+    }
+}
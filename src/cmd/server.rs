@@ -0,0 +1,261 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{
+    resp::{Protocol, RespDT},
+    store::{
+        cache::{Db, DbError, Keyspace},
+        glob::glob_match,
+        notify::NotifyFlags,
+    },
+};
+
+use super::command::{check_arity, lower, parse_int, CommandApply, CommandError, OK_RESP};
+
+/// The Redis version HELLO reports, for clients that check for features.
+const REDIS_VERSION: &str = "7.2.0";
+
+/// The parameters CONFIG knows about.
+const NOTIFY_KEYSPACE_EVENTS: &str = "notify-keyspace-events";
+const CONFIG_PARAMETERS: [&str; 1] = [NOTIFY_KEYSPACE_EVENTS];
+
+/// CONFIG GET and CONFIG SET. Only `notify-keyspace-events` can be read
+/// or changed so far.
+#[derive(Debug)]
+pub enum ConfigAction {
+    /// Parameters matching any of the glob-style patterns.
+    Get(Vec<Bytes>),
+    Set(NotifyFlags),
+}
+
+#[derive(Debug)]
+pub struct ConfigCommand {
+    pub action: ConfigAction,
+    pub cache: Arc<Db>,
+}
+
+/// HELLO, optionally switching protocols, authenticating and naming the
+/// connection along the way. The connection runs it itself.
+#[derive(Debug)]
+pub struct HelloCommand {
+    pub protocol: Option<Protocol>,
+    pub name: Option<Bytes>,
+}
+
+/// What a connection is: its id, the protocol it speaks and the name it
+/// gave itself.
+#[derive(Debug)]
+pub struct Client {
+    pub id: u64,
+    pub protocol: Protocol,
+    pub name: Option<Bytes>,
+}
+
+/// INFO. Only the `stats` section is tracked so far; asking for any other
+/// section yields an empty reply, as Redis does for unknown ones.
+#[derive(Debug)]
+pub struct InfoCommand {
+    pub sections: Vec<String>,
+    pub cache: Arc<Db>,
+}
+
+impl InfoCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 0, usize::MAX)?;
+        Ok(InfoCommand {
+            sections: args.iter().map(|s| lower(s)).collect(),
+            cache,
+        })
+    }
+
+    fn wants(&self, section: &str) -> bool {
+        self.sections.is_empty()
+            || self
+                .sections
+                .iter()
+                .any(|s| s == section || s == "all" || s == "default" || s == "everything")
+    }
+}
+
+impl HelloCommand {
+    /// Parses `HELLO [protover [AUTH username password] [SETNAME name]]`.
+    pub fn parse(args: Vec<Bytes>) -> Result<Self, CommandError> {
+        let Some(version) = args.first() else {
+            return Ok(HelloCommand {
+                protocol: None,
+                name: None,
+            });
+        };
+        let protocol = match parse_int(version) {
+            Ok(2) => Protocol::Resp2,
+            Ok(3) => Protocol::Resp3,
+            Ok(_) => return Err(CommandError::UnsupportedProtocol),
+            Err(_) => return Err(CommandError::InvalidProtocolVersion),
+        };
+        let mut name = None;
+        let mut idx = 1;
+        while idx < args.len() {
+            let opt = lower(&args[idx]);
+            match opt.as_str() {
+                "auth" if idx + 2 < args.len() => {
+                    // There is no user but `default`, and it has no
+                    // password, so any password will do.
+                    if args[idx + 1] != "default" {
+                        return Err(CommandError::WrongPass);
+                    }
+                    idx += 3;
+                }
+                "setname" if idx + 1 < args.len() => {
+                    let candidate = &args[idx + 1];
+                    if candidate.iter().any(|b| !(b'!'..=b'~').contains(b)) {
+                        return Err(CommandError::InvalidClientName);
+                    }
+                    name = Some(candidate.clone());
+                    idx += 2;
+                }
+                _ => {
+                    return Err(CommandError::HelloSyntax(
+                        String::from_utf8_lossy(&args[idx]).into_owned(),
+                    ))
+                }
+            }
+        }
+        Ok(HelloCommand {
+            protocol: Some(protocol),
+            name,
+        })
+    }
+}
+
+impl Client {
+    pub fn new(db: &Db) -> Self {
+        Client {
+            id: db.new_client_id(),
+            protocol: Protocol::default(),
+            name: None,
+        }
+    }
+
+    /// Applies HELLO and replies with what the server and the connection
+    /// are now.
+    pub fn hello(&mut self, cmd: &HelloCommand) -> RespDT {
+        if let Some(protocol) = cmd.protocol {
+            self.protocol = protocol;
+        }
+        if let Some(name) = &cmd.name {
+            self.name = Some(name.clone());
+        }
+        let proto = match self.protocol {
+            Protocol::Resp2 => 2,
+            Protocol::Resp3 => 3,
+        };
+        RespDT::Map(vec![
+            (RespDT::bulk("server"), RespDT::bulk("redis")),
+            (RespDT::bulk("version"), RespDT::bulk(REDIS_VERSION)),
+            (RespDT::bulk("proto"), RespDT::Integer(proto)),
+            (RespDT::bulk("id"), RespDT::Integer(self.id as i64)),
+            (RespDT::bulk("mode"), RespDT::bulk("standalone")),
+            (RespDT::bulk("role"), RespDT::bulk("master")),
+            (RespDT::bulk("modules"), RespDT::Array(vec![])),
+        ])
+    }
+}
+
+impl ConfigCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        let sub = lower(&args[0]);
+        let sub_cmd = format!("{}|{}", cmd, sub);
+        let action = match sub.as_str() {
+            "get" => {
+                check_arity(&sub_cmd, &args, 2, usize::MAX)?;
+                ConfigAction::Get(args[1..].iter().map(|p| lower(p).into()).collect())
+            }
+            "set" => {
+                let pairs = args[1..].chunks_exact(2);
+                if args.len() < 3 || !pairs.remainder().is_empty() {
+                    return Err(CommandError::InvalidArguments(sub_cmd));
+                }
+                // Later pairs win, as they would applied in turn.
+                let mut flags = None;
+                for pair in pairs {
+                    let name = lower(&pair[0]);
+                    if name != NOTIFY_KEYSPACE_EVENTS {
+                        return Err(CommandError::UnknownConfig(name));
+                    }
+                    let value = String::from_utf8_lossy(&pair[1]);
+                    flags = Some(
+                        NotifyFlags::parse(&value).ok_or(CommandError::InvalidConfig(
+                            name,
+                            "Invalid event class character. Use 'Ag$lshzxeKEtmdn'.",
+                        ))?,
+                    );
+                }
+                ConfigAction::Set(flags.expect("at least one pair"))
+            }
+            _ => {
+                return Err(CommandError::UnknownSubcommand(
+                    cmd.to_ascii_uppercase(),
+                    String::from_utf8_lossy(&args[0]).into_owned(),
+                ))
+            }
+        };
+        Ok(ConfigCommand { action, cache })
+    }
+}
+
+impl CommandApply for ConfigCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        match &self.action {
+            ConfigAction::Get(patterns) => Ok(RespDT::Map(
+                CONFIG_PARAMETERS
+                    .iter()
+                    .filter(|name| patterns.iter().any(|p| glob_match(p, name.as_bytes())))
+                    .map(|name| {
+                        (
+                            RespDT::bulk(*name),
+                            RespDT::bulk(ks.notifier.flags.to_string()),
+                        )
+                    })
+                    .collect(),
+            )),
+            ConfigAction::Set(flags) => {
+                ks.notifier.flags = *flags;
+                Ok(RespDT::SimpleString(OK_RESP.to_string()))
+            }
+        }
+    }
+}
+
+impl CommandApply for InfoCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let mut info = String::new();
+        if self.wants("stats") {
+            let stats = &ks.expire_stats;
+            info.push_str("# Stats\r\n");
+            info.push_str(&format!("expired_keys:{}\r\n", stats.expired_keys));
+            info.push_str(&format!(
+                "expired_stale_perc:{:.2}\r\n",
+                stats.stale_perc * 100.0
+            ));
+            info.push_str(&format!(
+                "expired_time_cap_reached_count:{}\r\n",
+                stats.time_cap_reached_count
+            ));
+            info.push_str(&format!(
+                "expire_cycle_cpu_milliseconds:{}\r\n",
+                stats.cycle_time.as_millis()
+            ));
+        }
+        Ok(RespDT::VerbatimString("txt".to_string(), info.into()))
+    }
+}
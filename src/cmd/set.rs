@@ -0,0 +1,448 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        set::SetOp,
+    },
+};
+
+use super::command::{
+    bulk_array, bulk_set, check_arity, lower, parse_int, parse_random_count, CommandApply,
+    CommandError,
+};
+
+#[derive(Debug)]
+pub struct SAddCommand {
+    pub key: Bytes,
+    pub members: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SRemCommand {
+    pub key: Bytes,
+    pub members: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SMembersCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SIsMemberCommand {
+    pub key: Bytes,
+    pub member: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SMIsMemberCommand {
+    pub key: Bytes,
+    pub members: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SCardCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SPopCommand {
+    pub key: Bytes,
+    pub count: Option<usize>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SRandMemberCommand {
+    pub key: Bytes,
+    pub count: Option<i64>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SMoveCommand {
+    pub source: Bytes,
+    pub destination: Bytes,
+    pub member: Bytes,
+    pub cache: Arc<Db>,
+}
+
+/// SINTER, SUNION and SDIFF.
+#[derive(Debug)]
+pub struct SetAlgebraCommand {
+    pub op: SetOp,
+    pub keys: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+/// SINTERSTORE, SUNIONSTORE and SDIFFSTORE.
+#[derive(Debug)]
+pub struct SetAlgebraStoreCommand {
+    pub op: SetOp,
+    pub destination: Bytes,
+    pub keys: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SInterCardCommand {
+    pub keys: Vec<Bytes>,
+    pub limit: usize,
+    pub cache: Arc<Db>,
+}
+
+impl SAddCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(SAddCommand {
+            key,
+            members: args,
+            cache,
+        })
+    }
+}
+
+impl SRemCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(SRemCommand {
+            key,
+            members: args,
+            cache,
+        })
+    }
+}
+
+impl SMembersCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(SMembersCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl SIsMemberCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(SIsMemberCommand {
+            key: args[0].clone(),
+            member: args[1].clone(),
+            cache,
+        })
+    }
+}
+
+impl SMIsMemberCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(SMIsMemberCommand {
+            key,
+            members: args,
+            cache,
+        })
+    }
+}
+
+impl SCardCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(SCardCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl SPopCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 2)?;
+        let count = match args.get(1) {
+            Some(count) => {
+                let count = parse_int(count).map_err(|_| CommandError::NotPositive)?;
+                if count < 0 {
+                    return Err(CommandError::NotPositive);
+                }
+                Some(count as usize)
+            }
+            None => None,
+        };
+        Ok(SPopCommand {
+            key: args[0].clone(),
+            count,
+            cache,
+        })
+    }
+}
+
+impl SRandMemberCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 2)?;
+        Ok(SRandMemberCommand {
+            key: args[0].clone(),
+            count: args.get(1).map(|c| parse_random_count(c)).transpose()?,
+            cache,
+        })
+    }
+}
+
+impl SMoveCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(SMoveCommand {
+            source: args[0].clone(),
+            destination: args[1].clone(),
+            member: args[2].clone(),
+            cache,
+        })
+    }
+}
+
+impl SetAlgebraCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        op: SetOp,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        Ok(SetAlgebraCommand {
+            op,
+            keys: args,
+            cache,
+        })
+    }
+}
+
+impl SetAlgebraStoreCommand {
+    pub fn parse(
+        cmd: &str,
+        mut args: Vec<Bytes>,
+        op: SetOp,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let destination = args.remove(0);
+        Ok(SetAlgebraStoreCommand {
+            op,
+            destination,
+            keys: args,
+            cache,
+        })
+    }
+}
+
+impl SInterCardCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let numkeys = parse_int(&args[0])?;
+        if numkeys <= 0 {
+            return Err(CommandError::NumKeysNotPositive);
+        }
+        let numkeys = numkeys as usize;
+        if numkeys > args.len() - 1 {
+            return Err(CommandError::NumKeysTooMany);
+        }
+        let keys = args[1..=numkeys].to_vec();
+        let mut limit = 0;
+        let mut rest = args[numkeys + 1..].iter();
+        while let Some(opt) = rest.next() {
+            match (lower(opt).as_str(), rest.next()) {
+                ("limit", Some(value)) => {
+                    let value = parse_int(value)?;
+                    if value < 0 {
+                        return Err(CommandError::NegativeLimit);
+                    }
+                    limit = value as usize;
+                }
+                _ => return Err(CommandError::InvalidCommand),
+            }
+        }
+        Ok(SInterCardCommand { keys, limit, cache })
+    }
+}
+
+impl CommandApply for SAddCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.sadd(&self.key, &self.members)?))
+    }
+}
+
+impl CommandApply for SRemCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.srem(&self.key, &self.members)?))
+    }
+}
+
+impl CommandApply for SMembersCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(bulk_set(ks.smembers(&self.key)?))
+    }
+}
+
+impl CommandApply for SIsMemberCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(
+            ks.sismember(&self.key, &self.member)? as i64
+        ))
+    }
+}
+
+impl CommandApply for SMIsMemberCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Array(
+            ks.smismember(&self.key, &self.members)?
+                .into_iter()
+                .map(|found| RespDT::Integer(found as i64))
+                .collect(),
+        ))
+    }
+}
+
+impl CommandApply for SCardCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.scard(&self.key)?))
+    }
+}
+
+impl CommandApply for SPopCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let popped = ks.spop(&self.key, self.count.unwrap_or(1))?;
+        match self.count {
+            Some(_) => Ok(bulk_set(popped)),
+            None => Ok(popped.into_iter().next().map_or(RespDT::Null, RespDT::Bulk)),
+        }
+    }
+}
+
+impl CommandApply for SRandMemberCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        match self.count {
+            Some(count) => Ok(bulk_array(ks.srandmember(&self.key, count)?)),
+            None => Ok(ks
+                .srandmember(&self.key, 1)?
+                .into_iter()
+                .next()
+                .map_or(RespDT::Null, RespDT::Bulk)),
+        }
+    }
+}
+
+impl CommandApply for SMoveCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let moved = ks.smove(&self.source, &self.destination, &self.member)?;
+        Ok(RespDT::Integer(moved as i64))
+    }
+}
+
+impl CommandApply for SetAlgebraCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let result = ks.set_algebra(self.op, &self.keys)?;
+        Ok(bulk_set(result.into_iter().collect()))
+    }
+}
+
+impl CommandApply for SetAlgebraStoreCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.set_algebra_store(
+            self.op,
+            &self.destination,
+            &self.keys,
+        )?))
+    }
+}
+
+impl CommandApply for SInterCardCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.sintercard(&self.keys, self.limit)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<SRandMemberCommand, CommandError> {
+        let args = args
+            .iter()
+            .map(|a| Bytes::copy_from_slice(a.as_bytes()))
+            .collect();
+        SRandMemberCommand::parse("srandmember", args, Arc::new(Db::new()))
+    }
+
+    #[test]
+    fn test_randmember_count_range() {
+        assert!(matches!(
+            parse(&["s", "-9223372036854775808"]),
+            Err(CommandError::OutOfRange)
+        ));
+        assert!(matches!(
+            parse(&["s", "9223372036854775807"]),
+            Err(CommandError::OutOfRange)
+        ));
+        assert!(parse(&["s", "-4611686018427387903"]).is_ok());
+        assert!(parse(&["s"]).is_ok());
+    }
+}
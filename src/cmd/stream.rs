@@ -0,0 +1,496 @@
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        stream::{StreamFields, StreamId, TrimSpec, TrimStrategy, XAddId},
+    },
+};
+
+use super::command::{
+    blocking_reply, check_arity, lower, parse_int, CommandApply, CommandError, CommandRespond,
+};
+
+pub(crate) fn parse_stream_id(arg: &[u8], default_seq: u64) -> Result<StreamId, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| StreamId::parse(arg, default_seq))
+        .ok_or(CommandError::InvalidStreamId)
+}
+
+/// Parses an XRANGE boundary. `-` and `+` are the extremes, a bare
+/// millisecond value covers every sequence in it, and a leading `(` makes
+/// the bound exclusive.
+pub(crate) fn parse_range_bound(arg: &[u8], is_start: bool) -> Result<StreamId, CommandError> {
+    match arg {
+        b"-" => return Ok(StreamId::MIN),
+        b"+" => return Ok(StreamId::MAX),
+        _ => {}
+    }
+    let default_seq = if is_start { 0 } else { u64::MAX };
+    match arg.strip_prefix(b"(") {
+        Some(rest) => {
+            let id = parse_stream_id(rest, default_seq)?;
+            let adjusted = if is_start { id.next() } else { id.prev() };
+            adjusted.ok_or(CommandError::InvalidStreamId)
+        }
+        None => parse_stream_id(arg, default_seq),
+    }
+}
+
+/// Parses `MAXLEN|MINID [=|~] threshold [LIMIT count]` starting at
+/// `args[*idx]`, advancing `idx` past it.
+pub(crate) fn parse_trim(args: &[Bytes], idx: &mut usize) -> Result<TrimSpec, CommandError> {
+    let kind = lower(&args[*idx]);
+    *idx += 1;
+    let mut approx = false;
+    match args.get(*idx).map(|arg| &arg[..]) {
+        Some(b"~") => {
+            approx = true;
+            *idx += 1;
+        }
+        Some(b"=") => *idx += 1,
+        _ => {}
+    }
+    let threshold = args.get(*idx).ok_or(CommandError::InvalidCommand)?;
+    *idx += 1;
+    let strategy = if kind == "maxlen" {
+        let max = parse_int(threshold)?;
+        if max < 0 {
+            return Err(CommandError::NegativeMaxLen);
+        }
+        TrimStrategy::MaxLen(max as u64)
+    } else {
+        TrimStrategy::MinId(parse_stream_id(threshold, 0)?)
+    };
+    let mut limit = None;
+    if args
+        .get(*idx)
+        .is_some_and(|opt| opt.eq_ignore_ascii_case(b"limit"))
+    {
+        let count = args.get(*idx + 1).ok_or(CommandError::InvalidCommand)?;
+        let count = parse_int(count)?;
+        if count < 0 {
+            return Err(CommandError::NotPositive);
+        }
+        if !approx {
+            return Err(CommandError::LimitWithoutApprox);
+        }
+        limit = Some(count as u64);
+        *idx += 2;
+    }
+    Ok(TrimSpec {
+        strategy,
+        approx,
+        limit,
+    })
+}
+
+/// Parses the millisecond timeout of a BLOCK option.
+pub(crate) fn parse_block_timeout(arg: &[u8]) -> Result<Duration, CommandError> {
+    let millis = parse_int(arg)?;
+    if millis < 0 {
+        return Err(CommandError::NegativeTimeout);
+    }
+    Ok(Duration::from_millis(millis as u64))
+}
+
+pub(crate) fn entry_resp(id: StreamId, fields: StreamFields) -> RespDT {
+    let mut flat = Vec::with_capacity(fields.len() * 2);
+    for (field, value) in fields {
+        flat.push(RespDT::Bulk(field));
+        flat.push(RespDT::Bulk(value));
+    }
+    RespDT::Array(vec![RespDT::bulk(id.to_string()), RespDT::Array(flat)])
+}
+
+pub(crate) fn entries_resp(entries: Vec<(StreamId, StreamFields)>) -> RespDT {
+    RespDT::Array(
+        entries
+            .into_iter()
+            .map(|(id, fields)| entry_resp(id, fields))
+            .collect(),
+    )
+}
+
+/// Splits the arguments following STREAMS into keys and their IDs.
+pub(crate) fn split_streams<'a>(
+    cmd: &str,
+    args: &'a [Bytes],
+) -> Result<(&'a [Bytes], &'a [Bytes]), CommandError> {
+    let half = args.len() / 2;
+    if half == 0 || half * 2 != args.len() {
+        return Err(CommandError::UnbalancedStreams(cmd.to_string()));
+    }
+    Ok(args.split_at(half))
+}
+
+#[derive(Debug)]
+pub struct XAddCommand {
+    pub key: Bytes,
+    pub id: XAddId,
+    pub fields: StreamFields,
+    pub no_mkstream: bool,
+    pub trim: Option<TrimSpec>,
+    pub cache: Arc<Db>,
+}
+
+/// XRANGE and XREVRANGE.
+#[derive(Debug)]
+pub struct XRangeCommand {
+    pub key: Bytes,
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: Option<usize>,
+    pub rev: bool,
+    pub cache: Arc<Db>,
+}
+
+/// Where XREAD starts reading a stream. `$` is resolved to the last ID once,
+/// when the command first runs, so a blocked reader sees every later entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XReadFrom {
+    Last,
+    After(StreamId),
+}
+
+#[derive(Debug)]
+pub struct XReadCommand {
+    pub count: Option<usize>,
+    pub block: Option<Duration>,
+    pub streams: Vec<(Bytes, XReadFrom)>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct XLenCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct XDelCommand {
+    pub key: Bytes,
+    pub ids: Vec<StreamId>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct XTrimCommand {
+    pub key: Bytes,
+    pub spec: TrimSpec,
+    pub cache: Arc<Db>,
+}
+
+impl XAddCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 4, usize::MAX)?;
+        let mut idx = 1;
+        let mut no_mkstream = false;
+        let mut trim = None;
+        while idx < args.len() {
+            match lower(&args[idx]).as_str() {
+                "nomkstream" => {
+                    no_mkstream = true;
+                    idx += 1;
+                }
+                "maxlen" | "minid" => trim = Some(parse_trim(&args, &mut idx)?),
+                _ => break,
+            }
+        }
+        let id_arg = args.get(idx).ok_or(CommandError::InvalidCommand)?;
+        let id = if id_arg == "*" {
+            XAddId::Auto
+        } else if let Some(ms) = id_arg.strip_suffix(b"-*") {
+            let ms = std::str::from_utf8(ms).ok().and_then(|ms| ms.parse().ok());
+            XAddId::AutoSeq(ms.ok_or(CommandError::InvalidStreamId)?)
+        } else {
+            XAddId::Explicit(parse_stream_id(id_arg, 0)?)
+        };
+        let pairs = args[idx + 1..].chunks_exact(2);
+        if pairs.len() == 0 || !pairs.remainder().is_empty() {
+            return Err(CommandError::InvalidArguments(cmd.to_string()));
+        }
+        let fields = pairs
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        Ok(XAddCommand {
+            key: args[0].clone(),
+            id,
+            fields,
+            no_mkstream,
+            trim,
+            cache,
+        })
+    }
+}
+
+impl XRangeCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        rev: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 5)?;
+        // XREVRANGE takes `end start`.
+        let (start, end) = if rev {
+            (&args[2], &args[1])
+        } else {
+            (&args[1], &args[2])
+        };
+        let count = match args.get(3) {
+            Some(opt) if opt.eq_ignore_ascii_case(b"count") => {
+                let count = args.get(4).ok_or(CommandError::InvalidCommand)?;
+                Some(parse_int(count)?.max(0) as usize)
+            }
+            Some(_) => return Err(CommandError::InvalidCommand),
+            None => None,
+        };
+        Ok(XRangeCommand {
+            key: args[0].clone(),
+            start: parse_range_bound(start, true)?,
+            end: parse_range_bound(end, false)?,
+            count,
+            rev,
+            cache,
+        })
+    }
+}
+
+impl XReadCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, usize::MAX)?;
+        let mut count = None;
+        let mut block = None;
+        let mut idx = 0;
+        loop {
+            let opt = args.get(idx).ok_or(CommandError::InvalidCommand)?;
+            match lower(opt).as_str() {
+                "count" => {
+                    let arg = args.get(idx + 1).ok_or(CommandError::InvalidCommand)?;
+                    count = Some(parse_int(arg)?.max(0) as usize).filter(|c| *c > 0);
+                }
+                "block" => {
+                    let arg = args.get(idx + 1).ok_or(CommandError::InvalidCommand)?;
+                    block = Some(parse_block_timeout(arg)?);
+                }
+                "streams" => break,
+                _ => return Err(CommandError::InvalidCommand),
+            }
+            idx += 2;
+        }
+        let (keys, ids) = split_streams(cmd, &args[idx + 1..])?;
+        let streams = keys
+            .iter()
+            .zip(ids)
+            .map(|(key, id)| {
+                let from = match &id[..] {
+                    b"$" => XReadFrom::Last,
+                    id => XReadFrom::After(parse_stream_id(id, 0)?),
+                };
+                Ok((key.clone(), from))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(XReadCommand {
+            count,
+            block,
+            streams,
+            cache,
+        })
+    }
+
+    fn resolve(&self, ks: &mut Keyspace) -> Result<Vec<StreamId>, DbError> {
+        self.streams
+            .iter()
+            .map(|(key, from)| match from {
+                XReadFrom::Last => ks.xlast_id(key),
+                XReadFrom::After(id) => Ok(*id),
+            })
+            .collect()
+    }
+
+    /// Reads every stream after its resolved ID, or `None` if none of them
+    /// has anything new.
+    fn read(&self, ks: &mut Keyspace, after: &[StreamId]) -> Result<Option<RespDT>, DbError> {
+        let mut replies = Vec::new();
+        for ((key, _), after) in self.streams.iter().zip(after) {
+            let entries = ks.xread(key, *after, self.count)?;
+            if !entries.is_empty() {
+                replies.push(RespDT::Array(vec![
+                    RespDT::Bulk(key.clone()),
+                    entries_resp(entries),
+                ]));
+            }
+        }
+        Ok((!replies.is_empty()).then_some(RespDT::Array(replies)))
+    }
+
+    /// Serves the read, waiting for an XADD to one of the keys when BLOCK
+    /// was given and nothing is available yet.
+    pub async fn respond(&self) -> Result<RespDT, Box<dyn std::error::Error>> {
+        let Some(timeout) = self.block else {
+            return self.response().await;
+        };
+        let keys: Vec<Bytes> = self.streams.iter().map(|(key, _)| key.clone()).collect();
+        let mut after: Option<Vec<StreamId>> = None;
+        let result = self
+            .cache
+            .block_on(&keys, timeout, false, |ks| {
+                let after = match &after {
+                    Some(after) => after,
+                    None => after.insert(self.resolve(ks)?),
+                };
+                self.read(ks, after)
+            })
+            .await;
+        Ok(blocking_reply(result, RespDT::NullArray))
+    }
+}
+
+impl XLenCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(XLenCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl XDelCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let ids = args[1..]
+            .iter()
+            .map(|id| parse_stream_id(id, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(XDelCommand {
+            key: args[0].clone(),
+            ids,
+            cache,
+        })
+    }
+}
+
+impl XTrimCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, usize::MAX)?;
+        if !matches!(lower(&args[1]).as_str(), "maxlen" | "minid") {
+            return Err(CommandError::InvalidCommand);
+        }
+        let mut idx = 1;
+        let spec = parse_trim(&args, &mut idx)?;
+        if idx != args.len() {
+            return Err(CommandError::InvalidCommand);
+        }
+        Ok(XTrimCommand {
+            key: args[0].clone(),
+            spec,
+            cache,
+        })
+    }
+}
+
+impl CommandApply for XAddCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let id = ks.xadd(
+            &self.key,
+            self.id,
+            self.fields.clone(),
+            self.no_mkstream,
+            self.trim.as_ref(),
+        )?;
+        Ok(id.map_or(RespDT::Null, |id| RespDT::bulk(id.to_string())))
+    }
+}
+
+impl CommandApply for XRangeCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        // COUNT 0 asks for nothing; Redis answers with a null array.
+        if self.count == Some(0) {
+            return Ok(RespDT::NullArray);
+        }
+        let entries = ks.xrange(&self.key, self.start, self.end, self.count, self.rev)?;
+        Ok(entries_resp(entries))
+    }
+}
+
+impl CommandApply for XReadCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let after = self.resolve(ks)?;
+        Ok(self.read(ks, &after)?.unwrap_or(RespDT::NullArray))
+    }
+}
+
+impl CommandApply for XLenCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.xlen(&self.key)?))
+    }
+}
+
+impl CommandApply for XDelCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.xdel(&self.key, &self.ids)?))
+    }
+}
+
+impl CommandApply for XTrimCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.xtrim(&self.key, &self.spec)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(cmd: &str, args: &[&str]) -> XRangeCommand {
+        let args = args
+            .iter()
+            .map(|a| Bytes::copy_from_slice(a.as_bytes()))
+            .collect();
+        XRangeCommand::parse(cmd, args, cmd == "xrevrange", Arc::new(Db::new())).unwrap()
+    }
+
+    #[test]
+    fn test_range_count_zero_is_null() {
+        let mut ks = Keyspace::default();
+        let fields = vec![(Bytes::from("f"), Bytes::from("v"))];
+        ks.xadd(b"s", XAddId::Auto, fields, false, None).unwrap();
+
+        let range = parse("xrange", &["s", "-", "+", "COUNT", "0"]);
+        assert!(range.apply(&mut ks).unwrap() == RespDT::NullArray);
+        let revrange = parse("xrevrange", &["s", "+", "-", "COUNT", "0"]);
+        assert!(revrange.apply(&mut ks).unwrap() == RespDT::NullArray);
+        let range = parse("xrange", &["s", "-", "+", "COUNT", "1"]);
+        assert!(range.apply(&mut ks).unwrap() != RespDT::NullArray);
+    }
+}
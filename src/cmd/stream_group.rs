@@ -0,0 +1,838 @@
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        stream::{now_ms, Stream, StreamFields, StreamId},
+        stream_group::{ClaimOptions, ConsumerGroup, GroupRead, GroupStart},
+    },
+};
+
+use super::{
+    command::{
+        check_arity, lower, parse_int, respond_blocking, CommandApply, CommandError, OK_RESP,
+    },
+    stream::{
+        entries_resp, entry_resp, parse_block_timeout, parse_range_bound, parse_stream_id,
+        split_streams,
+    },
+};
+
+/// XINFO and friends reply with flat `name, value, ...` arrays.
+fn info_map(pairs: Vec<(&str, RespDT)>) -> RespDT {
+    let mut flat = Vec::with_capacity(pairs.len() * 2);
+    for (name, value) in pairs {
+        flat.push(RespDT::bulk(name.to_string()));
+        flat.push(value);
+    }
+    RespDT::Array(flat)
+}
+
+fn id_resp(id: StreamId) -> RespDT {
+    RespDT::bulk(id.to_string())
+}
+
+fn ids_resp(ids: impl IntoIterator<Item = StreamId>) -> RespDT {
+    RespDT::Array(ids.into_iter().map(id_resp).collect())
+}
+
+fn optional_int(value: Option<u64>) -> RespDT {
+    value.map_or(RespDT::Null, |v| RespDT::Integer(v as i64))
+}
+
+/// Parses a millisecond argument, clamping negative values to zero.
+fn parse_millis(arg: &[u8]) -> Result<u64, CommandError> {
+    Ok(parse_int(arg)?.max(0) as u64)
+}
+
+fn parse_group_start(arg: &[u8]) -> Result<GroupStart, CommandError> {
+    if arg == b"$" {
+        Ok(GroupStart::Last)
+    } else {
+        parse_stream_id(arg, 0).map(GroupStart::Id)
+    }
+}
+
+/// Parses an ENTRIESREAD value, where -1 means unknown.
+fn parse_entries_read(arg: &[u8]) -> Result<Option<u64>, CommandError> {
+    match parse_int(arg)? {
+        -1 => Ok(None),
+        n if n < 0 => Err(CommandError::NotPositive),
+        n => Ok(Some(n as u64)),
+    }
+}
+
+#[derive(Debug)]
+pub enum XGroupOp {
+    Create {
+        group: Bytes,
+        start: GroupStart,
+        mkstream: bool,
+        entries_read: Option<u64>,
+    },
+    SetId {
+        group: Bytes,
+        start: GroupStart,
+        entries_read: Option<u64>,
+    },
+    Destroy {
+        group: Bytes,
+    },
+    CreateConsumer {
+        group: Bytes,
+        consumer: Bytes,
+    },
+    DelConsumer {
+        group: Bytes,
+        consumer: Bytes,
+    },
+}
+
+#[derive(Debug)]
+pub struct XGroupCommand {
+    pub key: Bytes,
+    pub op: XGroupOp,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct XReadGroupCommand {
+    pub group: Bytes,
+    pub consumer: Bytes,
+    pub count: Option<usize>,
+    pub block: Option<Duration>,
+    pub no_ack: bool,
+    pub streams: Vec<(Bytes, GroupRead)>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct XAckCommand {
+    pub key: Bytes,
+    pub group: Bytes,
+    pub ids: Vec<StreamId>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct PendingRange {
+    pub min_idle: u64,
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: usize,
+    pub consumer: Option<Bytes>,
+}
+
+/// XPENDING in its summary form, or the extended form when `range` is set.
+#[derive(Debug)]
+pub struct XPendingCommand {
+    pub key: Bytes,
+    pub group: Bytes,
+    pub range: Option<PendingRange>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct XClaimCommand {
+    pub key: Bytes,
+    pub group: Bytes,
+    pub consumer: Bytes,
+    pub min_idle: u64,
+    pub ids: Vec<StreamId>,
+    pub opts: ClaimOptions,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct XAutoClaimCommand {
+    pub key: Bytes,
+    pub group: Bytes,
+    pub consumer: Bytes,
+    pub min_idle: u64,
+    pub start: StreamId,
+    pub count: usize,
+    pub just_id: bool,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub enum XInfoView {
+    /// `XINFO STREAM`, with the entry limit for the FULL form.
+    Stream {
+        full: Option<usize>,
+    },
+    Groups,
+    Consumers(Bytes),
+}
+
+#[derive(Debug)]
+pub struct XInfoCommand {
+    pub key: Bytes,
+    pub view: XInfoView,
+    pub cache: Arc<Db>,
+}
+
+impl XGroupCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        let sub = lower(&args[0]);
+        let sub_cmd = format!("{}|{}", cmd, sub);
+        let op = match sub.as_str() {
+            "create" => {
+                check_arity(&sub_cmd, &args, 4, 7)?;
+                let mut mkstream = false;
+                let mut entries_read = None;
+                let mut idx = 4;
+                while idx < args.len() {
+                    match lower(&args[idx]).as_str() {
+                        "mkstream" => mkstream = true,
+                        "entriesread" => {
+                            idx += 1;
+                            let arg = args.get(idx).ok_or(CommandError::InvalidCommand)?;
+                            entries_read = parse_entries_read(arg)?;
+                        }
+                        _ => return Err(CommandError::InvalidCommand),
+                    }
+                    idx += 1;
+                }
+                XGroupOp::Create {
+                    group: args[2].clone(),
+                    start: parse_group_start(&args[3])?,
+                    mkstream,
+                    entries_read,
+                }
+            }
+            "setid" => {
+                check_arity(&sub_cmd, &args, 4, 6)?;
+                let entries_read = match args.get(4) {
+                    Some(opt) if opt.eq_ignore_ascii_case(b"entriesread") => {
+                        let arg = args.get(5).ok_or(CommandError::InvalidCommand)?;
+                        parse_entries_read(arg)?
+                    }
+                    Some(_) => return Err(CommandError::InvalidCommand),
+                    None => None,
+                };
+                XGroupOp::SetId {
+                    group: args[2].clone(),
+                    start: parse_group_start(&args[3])?,
+                    entries_read,
+                }
+            }
+            "destroy" => {
+                check_arity(&sub_cmd, &args, 3, 3)?;
+                XGroupOp::Destroy {
+                    group: args[2].clone(),
+                }
+            }
+            "createconsumer" | "delconsumer" => {
+                check_arity(&sub_cmd, &args, 4, 4)?;
+                let (group, consumer) = (args[2].clone(), args[3].clone());
+                if sub == "createconsumer" {
+                    XGroupOp::CreateConsumer { group, consumer }
+                } else {
+                    XGroupOp::DelConsumer { group, consumer }
+                }
+            }
+            _ => {
+                return Err(CommandError::UnknownSubcommand(
+                    cmd.to_ascii_uppercase(),
+                    String::from_utf8_lossy(&args[0]).into_owned(),
+                ))
+            }
+        };
+        Ok(XGroupCommand {
+            key: args[1].clone(),
+            op,
+            cache,
+        })
+    }
+}
+
+impl XReadGroupCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 6, usize::MAX)?;
+        if !args[0].eq_ignore_ascii_case(b"group") {
+            return Err(CommandError::InvalidCommand);
+        }
+        let mut count = None;
+        let mut block = None;
+        let mut no_ack = false;
+        let mut idx = 3;
+        loop {
+            let opt = args.get(idx).ok_or(CommandError::InvalidCommand)?;
+            match lower(opt).as_str() {
+                "count" => {
+                    let arg = args.get(idx + 1).ok_or(CommandError::InvalidCommand)?;
+                    count = Some(parse_int(arg)?.max(0) as usize);
+                    idx += 2;
+                }
+                "block" => {
+                    let arg = args.get(idx + 1).ok_or(CommandError::InvalidCommand)?;
+                    block = Some(parse_block_timeout(arg)?);
+                    idx += 2;
+                }
+                "noack" => {
+                    no_ack = true;
+                    idx += 1;
+                }
+                "streams" => break,
+                _ => return Err(CommandError::InvalidCommand),
+            }
+        }
+        let (keys, ids) = split_streams(cmd, &args[idx + 1..])?;
+        let streams = keys
+            .iter()
+            .zip(ids)
+            .map(|(key, id)| {
+                let read = match &id[..] {
+                    b">" => GroupRead::New,
+                    b"$" => return Err(CommandError::DollarInReadGroup),
+                    id => GroupRead::History(parse_stream_id(id, 0)?),
+                };
+                Ok((key.clone(), read))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(XReadGroupCommand {
+            group: args[1].clone(),
+            consumer: args[2].clone(),
+            count,
+            block,
+            no_ack,
+            streams,
+            cache,
+        })
+    }
+}
+
+impl XReadGroupCommand {
+    /// Like XREAD, blocks only while every stream is read with `>` and none
+    /// has new entries; history reads always reply at once.
+    pub async fn respond(&self) -> Result<RespDT, Box<dyn std::error::Error>> {
+        let keys: Vec<Bytes> = self.streams.iter().map(|(key, _)| key.clone()).collect();
+        respond_blocking(self, &keys, self.block, RespDT::NullArray).await
+    }
+}
+
+impl XAckCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, usize::MAX)?;
+        let ids = args[2..]
+            .iter()
+            .map(|id| parse_stream_id(id, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(XAckCommand {
+            key: args[0].clone(),
+            group: args[1].clone(),
+            ids,
+            cache,
+        })
+    }
+}
+
+impl XPendingCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 8)?;
+        let range = if args.len() == 2 {
+            None
+        } else {
+            let mut idx = 2;
+            let mut min_idle = 0;
+            if args[idx].eq_ignore_ascii_case(b"idle") {
+                let arg = args.get(idx + 1).ok_or(CommandError::InvalidCommand)?;
+                min_idle = parse_millis(arg)?;
+                idx += 2;
+            }
+            if args.len() < idx + 3 || args.len() > idx + 4 {
+                return Err(CommandError::InvalidCommand);
+            }
+            Some(PendingRange {
+                min_idle,
+                start: parse_range_bound(&args[idx], true)?,
+                end: parse_range_bound(&args[idx + 1], false)?,
+                count: parse_int(&args[idx + 2])?.max(0) as usize,
+                consumer: args.get(idx + 3).cloned(),
+            })
+        };
+        Ok(XPendingCommand {
+            key: args[0].clone(),
+            group: args[1].clone(),
+            range,
+            cache,
+        })
+    }
+}
+
+impl XClaimCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 5, usize::MAX)?;
+        let min_idle = parse_millis(&args[3])?;
+        let mut idx = 4;
+        let mut ids = Vec::new();
+        while let Some(id) = args.get(idx).and_then(|arg| parse_stream_id(arg, 0).ok()) {
+            ids.push(id);
+            idx += 1;
+        }
+        if ids.is_empty() {
+            return Err(CommandError::InvalidStreamId);
+        }
+        let mut opts = ClaimOptions::default();
+        while idx < args.len() {
+            let value = args.get(idx + 1);
+            match lower(&args[idx]).as_str() {
+                "force" => opts.force = true,
+                "justid" => opts.just_id = true,
+                opt => {
+                    let value = value.ok_or(CommandError::InvalidCommand)?;
+                    match opt {
+                        "idle" => opts.idle = Some(parse_millis(value)?),
+                        "time" => opts.time = Some(parse_millis(value)?),
+                        "retrycount" => opts.retry_count = Some(parse_millis(value)?),
+                        "lastid" => opts.last_id = Some(parse_stream_id(value, 0)?),
+                        _ => return Err(CommandError::InvalidCommand),
+                    }
+                    idx += 1;
+                }
+            }
+            idx += 1;
+        }
+        Ok(XClaimCommand {
+            key: args[0].clone(),
+            group: args[1].clone(),
+            consumer: args[2].clone(),
+            min_idle,
+            ids,
+            opts,
+            cache,
+        })
+    }
+}
+
+impl XAutoClaimCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 5, 8)?;
+        let mut count = 100;
+        let mut just_id = false;
+        let mut idx = 5;
+        while idx < args.len() {
+            match lower(&args[idx]).as_str() {
+                "count" => {
+                    let arg = args.get(idx + 1).ok_or(CommandError::InvalidCommand)?;
+                    count = match parse_int(arg)? {
+                        n if n < 1 => return Err(CommandError::CountNotPositive),
+                        n => n as usize,
+                    };
+                    idx += 1;
+                }
+                "justid" => just_id = true,
+                _ => return Err(CommandError::InvalidCommand),
+            }
+            idx += 1;
+        }
+        Ok(XAutoClaimCommand {
+            key: args[0].clone(),
+            group: args[1].clone(),
+            consumer: args[2].clone(),
+            min_idle: parse_millis(&args[3])?,
+            start: parse_range_bound(&args[4], true)?,
+            count,
+            just_id,
+            cache,
+        })
+    }
+}
+
+impl XInfoCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        let sub = lower(&args[0]);
+        let sub_cmd = format!("{}|{}", cmd, sub);
+        let view = match sub.as_str() {
+            "stream" => {
+                check_arity(&sub_cmd, &args, 2, 5)?;
+                let full = match &args[2..] {
+                    [] => None,
+                    [full] if full.eq_ignore_ascii_case(b"full") => Some(10),
+                    [full, opt, count]
+                        if full.eq_ignore_ascii_case(b"full")
+                            && opt.eq_ignore_ascii_case(b"count") =>
+                    {
+                        Some(parse_int(count)?.max(0) as usize)
+                    }
+                    _ => return Err(CommandError::InvalidCommand),
+                };
+                XInfoView::Stream { full }
+            }
+            "groups" => {
+                check_arity(&sub_cmd, &args, 2, 2)?;
+                XInfoView::Groups
+            }
+            "consumers" => {
+                check_arity(&sub_cmd, &args, 3, 3)?;
+                XInfoView::Consumers(args[2].clone())
+            }
+            _ => {
+                return Err(CommandError::UnknownSubcommand(
+                    cmd.to_ascii_uppercase(),
+                    String::from_utf8_lossy(&args[0]).into_owned(),
+                ))
+            }
+        };
+        Ok(XInfoCommand {
+            key: args[1].clone(),
+            view,
+            cache,
+        })
+    }
+}
+
+impl CommandApply for XGroupCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let key = &self.key;
+        Ok(match &self.op {
+            XGroupOp::Create {
+                group,
+                start,
+                mkstream,
+                entries_read,
+            } => {
+                ks.xgroup_create(key, group, *start, *mkstream, *entries_read)?;
+                RespDT::SimpleString(OK_RESP.to_string())
+            }
+            XGroupOp::SetId {
+                group,
+                start,
+                entries_read,
+            } => {
+                ks.xgroup_setid(key, group, *start, *entries_read)?;
+                RespDT::SimpleString(OK_RESP.to_string())
+            }
+            XGroupOp::Destroy { group } => RespDT::Integer(ks.xgroup_destroy(key, group)? as i64),
+            XGroupOp::CreateConsumer { group, consumer } => {
+                RespDT::Integer(ks.xgroup_create_consumer(key, group, consumer)? as i64)
+            }
+            XGroupOp::DelConsumer { group, consumer } => {
+                RespDT::Integer(ks.xgroup_del_consumer(key, group, consumer)?)
+            }
+        })
+    }
+}
+
+impl CommandApply for XReadGroupCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    /// Streams read with `>` that have nothing new are left out of the
+    /// reply; a reply with no streams at all is a null array.
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let mut replies = Vec::new();
+        for (key, read) in &self.streams {
+            let entries = ks.xreadgroup(
+                key,
+                &self.group,
+                &self.consumer,
+                *read,
+                self.count,
+                self.no_ack,
+            )?;
+            if entries.is_empty() && *read == GroupRead::New {
+                continue;
+            }
+            let entries = entries
+                .into_iter()
+                .map(|(id, fields)| match fields {
+                    Some(fields) => entry_resp(id, fields),
+                    None => RespDT::Array(vec![id_resp(id), RespDT::NullArray]),
+                })
+                .collect();
+            replies.push(RespDT::Array(vec![
+                RespDT::Bulk(key.clone()),
+                RespDT::Array(entries),
+            ]));
+        }
+        if replies.is_empty() {
+            return Ok(RespDT::NullArray);
+        }
+        Ok(RespDT::Array(replies))
+    }
+}
+
+impl CommandApply for XAckCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.xack(
+            &self.key,
+            &self.group,
+            &self.ids,
+        )?))
+    }
+}
+
+impl CommandApply for XPendingCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let Some(range) = &self.range else {
+            let summary = ks.xpending_summary(&self.key, &self.group)?;
+            let Some((min, max)) = summary.bounds else {
+                return Ok(RespDT::Array(vec![
+                    RespDT::Integer(0),
+                    RespDT::Null,
+                    RespDT::Null,
+                    RespDT::NullArray,
+                ]));
+            };
+            let consumers = summary
+                .consumers
+                .into_iter()
+                .map(|(name, count)| {
+                    RespDT::Array(vec![RespDT::Bulk(name), RespDT::bulk(count.to_string())])
+                })
+                .collect();
+            return Ok(RespDT::Array(vec![
+                RespDT::Integer(summary.count as i64),
+                id_resp(min),
+                id_resp(max),
+                RespDT::Array(consumers),
+            ]));
+        };
+        let pending = ks.xpending_range(
+            &self.key,
+            &self.group,
+            range.min_idle,
+            range.start,
+            range.end,
+            range.count,
+            range.consumer.as_deref(),
+        )?;
+        Ok(RespDT::Array(
+            pending
+                .into_iter()
+                .map(|p| {
+                    RespDT::Array(vec![
+                        id_resp(p.id),
+                        RespDT::Bulk(p.consumer),
+                        RespDT::Integer(p.idle as i64),
+                        RespDT::Integer(p.delivery_count as i64),
+                    ])
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl CommandApply for XClaimCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let claimed = ks.xclaim(
+            &self.key,
+            &self.group,
+            &self.consumer,
+            self.min_idle,
+            &self.ids,
+            &self.opts,
+        )?;
+        if self.opts.just_id {
+            return Ok(ids_resp(claimed.into_iter().map(|(id, _)| id)));
+        }
+        Ok(entries_resp(claimed))
+    }
+}
+
+impl CommandApply for XAutoClaimCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let result = ks.xautoclaim(
+            &self.key,
+            &self.group,
+            &self.consumer,
+            self.min_idle,
+            self.start,
+            self.count,
+            self.just_id,
+        )?;
+        let claimed = if self.just_id {
+            ids_resp(result.claimed.into_iter().map(|(id, _)| id))
+        } else {
+            entries_resp(result.claimed)
+        };
+        Ok(RespDT::Array(vec![
+            id_resp(result.next),
+            claimed,
+            ids_resp(result.deleted),
+        ]))
+    }
+}
+
+fn stream_info(stream: &Stream) -> Vec<(&'static str, RespDT)> {
+    vec![
+        ("length", RespDT::Integer(stream.len() as i64)),
+        ("last-generated-id", id_resp(stream.last_id)),
+        ("max-deleted-entry-id", id_resp(stream.max_deleted_id)),
+        (
+            "entries-added",
+            RespDT::Integer(stream.entries_added as i64),
+        ),
+        (
+            "recorded-first-entry-id",
+            id_resp(stream.first_id().unwrap_or_default()),
+        ),
+    ]
+}
+
+fn first_and_last(stream: &Stream) -> [Option<RespDT>; 2] {
+    let resp = |(id, fields): (&StreamId, &StreamFields)| entry_resp(*id, fields.clone());
+    [
+        stream.entries.iter().next().map(resp),
+        stream.entries.iter().next_back().map(resp),
+    ]
+}
+
+fn full_group_info(stream: &Stream, name: &[u8], cg: &ConsumerGroup, limit: usize) -> RespDT {
+    let pel = cg
+        .pel
+        .iter()
+        .take(limit)
+        .map(|(id, p)| {
+            RespDT::Array(vec![
+                id_resp(*id),
+                RespDT::Bulk(p.consumer.clone()),
+                RespDT::Integer(p.delivery_time as i64),
+                RespDT::Integer(p.delivery_count as i64),
+            ])
+        })
+        .collect();
+    let consumers = cg
+        .consumers
+        .iter()
+        .map(|(consumer_name, consumer)| {
+            let pending = consumer
+                .pending
+                .iter()
+                .take(limit)
+                .map(|id| {
+                    let p = &cg.pel[id];
+                    RespDT::Array(vec![
+                        id_resp(*id),
+                        RespDT::Integer(p.delivery_time as i64),
+                        RespDT::Integer(p.delivery_count as i64),
+                    ])
+                })
+                .collect();
+            info_map(vec![
+                ("name", RespDT::Bulk(consumer_name.clone())),
+                ("seen-time", RespDT::Integer(consumer.seen_time as i64)),
+                (
+                    "active-time",
+                    RespDT::Integer(consumer.active_time.map_or(-1, |t| t as i64)),
+                ),
+                ("pel-count", RespDT::Integer(consumer.pending.len() as i64)),
+                ("pending", RespDT::Array(pending)),
+            ])
+        })
+        .collect();
+    info_map(vec![
+        ("name", RespDT::Bulk(Bytes::copy_from_slice(name))),
+        ("last-delivered-id", id_resp(cg.last_id)),
+        ("entries-read", optional_int(cg.entries_read)),
+        ("lag", optional_int(stream.group_lag(cg))),
+        ("pel-count", RespDT::Integer(cg.pel.len() as i64)),
+        ("pending", RespDT::Array(pel)),
+        ("consumers", RespDT::Array(consumers)),
+    ])
+}
+
+impl CommandApply for XInfoCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let stream = ks.get_stream(&self.key)?.ok_or(DbError::NoSuchKey)?;
+        let now = now_ms();
+        Ok(match &self.view {
+            XInfoView::Stream { full: None } => {
+                let mut info = stream_info(stream);
+                let [first, last] = first_and_last(stream);
+                info.push(("groups", RespDT::Integer(stream.groups.len() as i64)));
+                info.push(("first-entry", first.unwrap_or(RespDT::Null)));
+                info.push(("last-entry", last.unwrap_or(RespDT::Null)));
+                info_map(info)
+            }
+            XInfoView::Stream { full: Some(count) } => {
+                let limit = if *count == 0 { usize::MAX } else { *count };
+                let mut info = stream_info(stream);
+                let entries = stream.range(StreamId::MIN, StreamId::MAX, Some(limit), false);
+                info.push(("entries", entries_resp(entries)));
+                let groups = stream
+                    .groups
+                    .iter()
+                    .map(|(name, cg)| full_group_info(stream, name, cg, limit))
+                    .collect();
+                info.push(("groups", RespDT::Array(groups)));
+                info_map(info)
+            }
+            XInfoView::Groups => RespDT::Array(
+                stream
+                    .groups
+                    .iter()
+                    .map(|(name, cg)| {
+                        info_map(vec![
+                            ("name", RespDT::Bulk(name.clone())),
+                            ("consumers", RespDT::Integer(cg.consumers.len() as i64)),
+                            ("pending", RespDT::Integer(cg.pel.len() as i64)),
+                            ("last-delivered-id", id_resp(cg.last_id)),
+                            ("entries-read", optional_int(cg.entries_read)),
+                            ("lag", optional_int(stream.group_lag(cg))),
+                        ])
+                    })
+                    .collect(),
+            ),
+            XInfoView::Consumers(group) => {
+                let cg = stream.groups.get(group).ok_or_else(|| {
+                    DbError::NoGroupForKey(
+                        String::from_utf8_lossy(&self.key).into_owned(),
+                        String::from_utf8_lossy(group).into_owned(),
+                    )
+                })?;
+                RespDT::Array(
+                    cg.consumers
+                        .iter()
+                        .map(|(name, consumer)| {
+                            let inactive = consumer
+                                .active_time
+                                .map_or(-1, |t| now.saturating_sub(t) as i64);
+                            info_map(vec![
+                                ("name", RespDT::Bulk(name.clone())),
+                                ("pending", RespDT::Integer(consumer.pending.len() as i64)),
+                                (
+                                    "idle",
+                                    RespDT::Integer(now.saturating_sub(consumer.seen_time) as i64),
+                                ),
+                                ("inactive", RespDT::Integer(inactive)),
+                            ])
+                        })
+                        .collect(),
+                )
+            }
+        })
+    }
+}
@@ -0,0 +1,513 @@
+use std::{ops::RangeInclusive, sync::Arc};
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        string::{SetCondition, SetExpiry, SetOptions},
+    },
+};
+
+use super::command::{
+    check_arity, lower, parse_float, parse_int, parse_set_expiry, CommandApply, CommandError,
+    SetCommand, OK_RESP,
+};
+
+/// INCR, DECR, INCRBY and DECRBY, all as an increment by `delta`.
+#[derive(Debug)]
+pub struct IncrCommand {
+    pub key: Bytes,
+    pub delta: i64,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct IncrByFloatCommand {
+    pub key: Bytes,
+    pub delta: f64,
+    pub cache: Arc<Db>,
+}
+
+impl IncrCommand {
+    /// INCRBY and DECRBY (`by`) take the delta from the arguments, INCR and
+    /// DECR step by one.
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        by: bool,
+        decrement: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1 + by as usize, 1 + by as usize)?;
+        let delta = if by { parse_int(&args[1])? } else { 1 };
+        let delta = match decrement {
+            true => delta.checked_neg().ok_or(CommandError::DecrementOverflow)?,
+            false => delta,
+        };
+        Ok(IncrCommand {
+            key: args[0].clone(),
+            delta,
+            cache,
+        })
+    }
+}
+
+impl IncrByFloatCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(IncrByFloatCommand {
+            key: args[0].clone(),
+            delta: parse_float(&args[1])?,
+            cache,
+        })
+    }
+}
+
+impl CommandApply for IncrCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.incr_by(&self.key, self.delta)?))
+    }
+}
+
+impl CommandApply for IncrByFloatCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Bulk(ks.incr_by_float(&self.key, self.delta)?))
+    }
+}
+
+#[derive(Debug)]
+pub struct AppendCommand {
+    pub key: Bytes,
+    pub value: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct StrLenCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+/// GETRANGE, and its old name SUBSTR.
+#[derive(Debug)]
+pub struct GetRangeCommand {
+    pub key: Bytes,
+    pub start: i64,
+    pub end: i64,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SetRangeCommand {
+    pub key: Bytes,
+    pub offset: usize,
+    pub value: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct GetDelCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct GetExCommand {
+    pub key: Bytes,
+    pub expiry: SetExpiry,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct SetNxCommand {
+    pub key: Bytes,
+    pub value: Bytes,
+    pub cache: Arc<Db>,
+}
+
+/// MSET and MSETNX.
+#[derive(Debug)]
+pub struct MSetCommand {
+    pub pairs: Vec<(Bytes, Bytes)>,
+    pub nx: bool,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct MGetCommand {
+    pub keys: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LcsReply {
+    Sequence,
+    Len,
+    Idx {
+        min_match_len: usize,
+        with_match_len: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct LcsCommand {
+    pub key1: Bytes,
+    pub key2: Bytes,
+    pub reply: LcsReply,
+    pub cache: Arc<Db>,
+}
+
+impl SetCommand {
+    /// GETSET is SET with GET.
+    pub fn parse_getset(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(SetCommand {
+            key: args[0].clone(),
+            value: args[1].clone(),
+            options: SetOptions {
+                get: true,
+                ..Default::default()
+            },
+            cache,
+        })
+    }
+
+    /// SETEX and PSETEX, `unit` being the SET option they stand for.
+    pub fn parse_setex(
+        cmd: &str,
+        args: Vec<Bytes>,
+        unit: &str,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(SetCommand {
+            key: args[0].clone(),
+            value: args[2].clone(),
+            options: SetOptions {
+                expiry: parse_set_expiry(cmd, unit, &args[1])?,
+                ..Default::default()
+            },
+            cache,
+        })
+    }
+}
+
+impl AppendCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(AppendCommand {
+            key: args[0].clone(),
+            value: args[1].clone(),
+            cache,
+        })
+    }
+}
+
+impl StrLenCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(StrLenCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl GetRangeCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(GetRangeCommand {
+            key: args[0].clone(),
+            start: parse_int(&args[1])?,
+            end: parse_int(&args[2])?,
+            cache,
+        })
+    }
+}
+
+impl SetRangeCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        let offset = parse_int(&args[1])?;
+        if offset < 0 {
+            return Err(CommandError::OffsetOutOfRange);
+        }
+        Ok(SetRangeCommand {
+            key: args[0].clone(),
+            offset: offset as usize,
+            value: args[2].clone(),
+            cache,
+        })
+    }
+}
+
+impl GetDelCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(GetDelCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl GetExCommand {
+    /// Parses `GETEX key [EX s | PX ms | EXAT s | PXAT ms | PERSIST]`.
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 3)?;
+        let expiry = match args.get(1).map(|opt| lower(opt)) {
+            None => SetExpiry::Keep,
+            Some(opt) if opt == "persist" && args.len() == 2 => SetExpiry::Clear,
+            Some(opt)
+                if matches!(opt.as_str(), "ex" | "px" | "exat" | "pxat") && args.len() == 3 =>
+            {
+                parse_set_expiry(cmd, &opt, &args[2])?
+            }
+            Some(_) => return Err(CommandError::InvalidCommand),
+        };
+        Ok(GetExCommand {
+            key: args[0].clone(),
+            expiry,
+            cache,
+        })
+    }
+}
+
+impl SetNxCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(SetNxCommand {
+            key: args[0].clone(),
+            value: args[1].clone(),
+            cache,
+        })
+    }
+}
+
+impl MSetCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        nx: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let pairs = args.chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return Err(CommandError::InvalidArguments(cmd.to_string()));
+        }
+        Ok(MSetCommand {
+            pairs: pairs
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect(),
+            nx,
+            cache,
+        })
+    }
+}
+
+impl MGetCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, usize::MAX)?;
+        Ok(MGetCommand { keys: args, cache })
+    }
+}
+
+impl LcsCommand {
+    /// Parses `LCS key1 key2 [LEN] [IDX] [MINMATCHLEN len] [WITHMATCHLEN]`.
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let (mut len, mut idx, mut with_match_len) = (false, false, false);
+        let mut min_match_len = 0;
+        let mut i = 2;
+        while i < args.len() {
+            match lower(&args[i]).as_str() {
+                "len" => len = true,
+                "idx" => idx = true,
+                "withmatchlen" => with_match_len = true,
+                "minmatchlen" if i + 1 < args.len() => {
+                    i += 1;
+                    min_match_len = parse_int(&args[i])?.max(0) as usize;
+                }
+                _ => return Err(CommandError::InvalidCommand),
+            }
+            i += 1;
+        }
+        let reply = match (len, idx) {
+            (true, true) => return Err(CommandError::LcsLenAndIdx),
+            (true, false) => LcsReply::Len,
+            (false, true) => LcsReply::Idx {
+                min_match_len,
+                with_match_len,
+            },
+            (false, false) => LcsReply::Sequence,
+        };
+        Ok(LcsCommand {
+            key1: args[0].clone(),
+            key2: args[1].clone(),
+            reply,
+            cache,
+        })
+    }
+}
+
+impl CommandApply for AppendCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.append(&self.key, &self.value)? as i64))
+    }
+}
+
+impl CommandApply for StrLenCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.strlen(&self.key)? as i64))
+    }
+}
+
+impl CommandApply for GetRangeCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Bulk(ks.getrange(&self.key, self.start, self.end)?))
+    }
+}
+
+impl CommandApply for SetRangeCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let len = ks.setrange(&self.key, self.offset, &self.value)?;
+        Ok(RespDT::Integer(len as i64))
+    }
+}
+
+impl CommandApply for GetDelCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(ks.getdel(&self.key)?.map_or(RespDT::Null, RespDT::Bulk))
+    }
+}
+
+impl CommandApply for GetExCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(ks
+            .getex(&self.key, self.expiry)?
+            .map_or(RespDT::Null, RespDT::Bulk))
+    }
+}
+
+impl CommandApply for SetNxCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let options = SetOptions {
+            condition: SetCondition::IfAbsent,
+            ..Default::default()
+        };
+        let outcome = ks.set(&self.key, self.value.clone(), &options)?;
+        Ok(RespDT::Integer(outcome.written as i64))
+    }
+}
+
+impl CommandApply for MSetCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let written = ks.mset(&self.pairs, self.nx);
+        Ok(match self.nx {
+            true => RespDT::Integer(written as i64),
+            false => RespDT::SimpleString(OK_RESP.to_string()),
+        })
+    }
+}
+
+impl CommandApply for MGetCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let values = ks.mget(&self.keys);
+        Ok(RespDT::Array(
+            values
+                .into_iter()
+                .map(|value| value.map_or(RespDT::Null, RespDT::Bulk))
+                .collect(),
+        ))
+    }
+}
+
+impl CommandApply for LcsCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let lcs = ks.lcs(&self.key1, &self.key2)?;
+        let len = lcs.sequence.len() as i64;
+        let (min_match_len, with_match_len) = match self.reply {
+            LcsReply::Sequence => return Ok(RespDT::bulk(lcs.sequence)),
+            LcsReply::Len => return Ok(RespDT::Integer(len)),
+            LcsReply::Idx {
+                min_match_len,
+                with_match_len,
+            } => (min_match_len, with_match_len),
+        };
+        let range = |r: &RangeInclusive<usize>| {
+            RespDT::Array(vec![
+                RespDT::Integer(*r.start() as i64),
+                RespDT::Integer(*r.end() as i64),
+            ])
+        };
+        let matches = lcs
+            .matches
+            .iter()
+            .filter(|m| m.len() >= min_match_len)
+            .map(|m| {
+                let mut item = vec![range(&m.a), range(&m.b)];
+                if with_match_len {
+                    item.push(RespDT::Integer(m.len() as i64));
+                }
+                RespDT::Array(item)
+            })
+            .collect();
+        Ok(RespDT::Array(vec![
+            RespDT::bulk("matches".to_string()),
+            RespDT::Array(matches),
+            RespDT::bulk("len".to_string()),
+            RespDT::Integer(len),
+        ]))
+    }
+}
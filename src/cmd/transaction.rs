@@ -0,0 +1,184 @@
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{cache::Keyspace, Db},
+};
+
+use super::command::{Command, CommandError};
+
+const QUEUED_RESP: &str = "QUEUED";
+
+/// A connection's open MULTI: the commands queued so far, and whether one
+/// of them failed to parse, which dooms the whole transaction.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    queued: Vec<Command>,
+    aborted: bool,
+}
+
+impl Transaction {
+    /// Queues a command, or records that it was malformed so EXEC aborts.
+    /// Either way the result is the reply to send right away.
+    pub fn queue(&mut self, cmd: Result<Command, CommandError>) -> RespDT {
+        match cmd {
+            Ok(cmd) => {
+                self.queued.push(cmd);
+                RespDT::SimpleString(QUEUED_RESP.to_string())
+            }
+            Err(err) => {
+                self.aborted = true;
+                RespDT::SimpleError(err.to_string())
+            }
+        }
+    }
+
+    /// EXEC: runs every queued command back to back under one lock, so no
+    /// other client sees the key space halfway through. Errors raised while
+    /// running are replies like any other; they do not stop the rest. If a
+    /// watched key changed nothing runs, and the reply is a null array.
+    /// Either way the watches are dropped.
+    pub async fn exec(self, db: &Db, watches: &mut Watches) -> RespDT {
+        let mut ks = db.cache.lock().await;
+        // A watched key whose TTL ran out counts as changed even if nothing
+        // has evicted it yet.
+        for key in &watches.keys {
+            ks.expire_if_needed(key);
+        }
+        let dirty = watches.is_dirty(&ks);
+        watches.release(&mut ks);
+        if self.aborted {
+            return RespDT::SimpleError(CommandError::ExecAbort.to_string());
+        }
+        if dirty {
+            return RespDT::NullArray;
+        }
+        let replies = self
+            .queued
+            .iter()
+            .map(|cmd| cmd.apply_queued(&mut ks))
+            .collect();
+        ks.blocked.wake_ready();
+        RespDT::Array(replies)
+    }
+}
+
+/// The keys a connection WATCHes, under the id the key space knows it by.
+#[derive(Debug, Default)]
+pub struct Watches {
+    id: Option<u64>,
+    keys: Vec<Bytes>,
+}
+
+impl Watches {
+    pub async fn watch(&mut self, db: &Db, keys: &[Bytes]) {
+        let mut ks = db.cache.lock().await;
+        let id = *self.id.get_or_insert_with(|| ks.watched.new_client());
+        for key in keys {
+            if !self.keys.contains(key) {
+                ks.watch(id, key);
+                self.keys.push(key.clone());
+            }
+        }
+    }
+
+    /// UNWATCH, also run on DISCARD and when the connection closes.
+    pub async fn clear(&mut self, db: &Db) {
+        if !self.keys.is_empty() {
+            self.release(&mut *db.cache.lock().await);
+        }
+    }
+
+    fn is_dirty(&self, ks: &Keyspace) -> bool {
+        self.id.is_some_and(|id| ks.watched.is_dirty(id))
+    }
+
+    fn release(&mut self, ks: &mut Keyspace) {
+        if let Some(id) = self.id {
+            ks.watched.unwatch(id, &self.keys);
+        }
+        self.keys.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+    use crate::{
+        cmd::command::RespCache,
+        store::{
+            cache::{DbError, EntryValue, RespEntry},
+            string::StrValue,
+        },
+    };
+
+    fn command(db: &Arc<Db>, args: &[&str]) -> Result<Command, CommandError> {
+        let args = args.iter().map(|a| RespDT::bulk(a.to_string())).collect();
+        RespCache::new(db.clone(), RespDT::Array(args)).try_into()
+    }
+
+    #[tokio::test]
+    async fn test_exec_runs_queue_in_order() {
+        let db = Arc::new(Db::new());
+        let mut tx = Transaction::default();
+        let queued = RespDT::SimpleString(QUEUED_RESP.to_string());
+        assert!(tx.queue(command(&db, &["SET", "k", "v"])) == queued);
+        // Fails only once it runs, against the string SET left behind.
+        assert!(tx.queue(command(&db, &["LPUSH", "k", "x"])) == queued);
+        assert!(tx.queue(command(&db, &["GET", "k"])) == queued);
+        let reply = tx.exec(&db, &mut Watches::default()).await;
+        assert!(
+            reply
+                == RespDT::Array(vec![
+                    RespDT::SimpleString("OK".to_string()),
+                    RespDT::SimpleError(DbError::WrongType.to_string()),
+                    RespDT::bulk("v"),
+                ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_command_aborts_exec() {
+        let db = Arc::new(Db::new());
+        let mut tx = Transaction::default();
+        tx.queue(command(&db, &["SET", "k", "v"]));
+        let reply = tx.queue(command(&db, &["SET", "k"]));
+        assert!(
+            reply == RespDT::SimpleError(CommandError::InvalidArguments("set".into()).to_string())
+        );
+        let reply = tx.exec(&db, &mut Watches::default()).await;
+        assert!(reply == RespDT::SimpleError(CommandError::ExecAbort.to_string()));
+        assert!(db.cache.lock().await.get(b"k").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_empty_exec() {
+        let db = Arc::new(Db::new());
+        let reply = Transaction::default()
+            .exec(&db, &mut Watches::default())
+            .await;
+        assert!(reply == RespDT::Array(vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_watched_key_expiring_aborts_exec() {
+        let db = Arc::new(Db::new());
+        let soon = SystemTime::now() + Duration::from_millis(10);
+        db.cache.lock().await.insert(
+            Bytes::from("e"),
+            RespEntry::new(EntryValue::Str(StrValue::from("1")), Some(soon)),
+        );
+        let mut watches = Watches::default();
+        watches.watch(&db, &[Bytes::from("e")]).await;
+        // Nothing reads the key, so only EXEC can notice the TTL ran out.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let mut tx = Transaction::default();
+        tx.queue(command(&db, &["PING"]));
+        assert!(tx.exec(&db, &mut watches).await == RespDT::NullArray);
+    }
+}
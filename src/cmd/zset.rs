@@ -0,0 +1,708 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::{
+    resp::RespDT,
+    store::{
+        cache::{Db, DbError, Keyspace},
+        numeric::{self, format_float},
+        zset::{
+            Aggregate, LexBound, LexRange, RangeLimit, ScoreRange, SortedSet, ZAddFlags,
+            ZAddOutcome,
+        },
+    },
+};
+
+use super::command::{check_arity, lower, parse_float, parse_int, CommandApply, CommandError};
+
+pub(crate) fn score_resp(score: f64) -> RespDT {
+    RespDT::Double(score)
+}
+
+/// Members, each followed by its score if `with_scores`. Scores stay bulk
+/// strings even in RESP3, where Redis would pair them up instead.
+fn scored_array(items: Vec<(Bytes, f64)>, with_scores: bool) -> RespDT {
+    let mut out = Vec::with_capacity(items.len() * 2);
+    for (member, score) in items {
+        out.push(RespDT::Bulk(member));
+        if with_scores {
+            out.push(RespDT::bulk(format_float(score)));
+        }
+    }
+    RespDT::Array(out)
+}
+
+fn parse_score_bound(arg: &[u8]) -> Result<(f64, bool), CommandError> {
+    let (value, exclusive) = match arg.strip_prefix(b"(") {
+        Some(rest) => (rest, true),
+        None => (arg, false),
+    };
+    numeric::parse_float(value)
+        .map(|score| (score, exclusive))
+        .ok_or(CommandError::MinMaxNotFloat)
+}
+
+pub(crate) fn parse_score_range(min: &[u8], max: &[u8]) -> Result<ScoreRange, CommandError> {
+    let (min, min_exclusive) = parse_score_bound(min)?;
+    let (max, max_exclusive) = parse_score_bound(max)?;
+    Ok(ScoreRange {
+        min,
+        max,
+        min_exclusive,
+        max_exclusive,
+    })
+}
+
+fn parse_lex_bound(arg: &[u8]) -> Result<LexBound, CommandError> {
+    match arg {
+        b"-" => Ok(LexBound::NegInf),
+        b"+" => Ok(LexBound::PosInf),
+        _ => {
+            if let Some(rest) = arg.strip_prefix(b"[") {
+                Ok(LexBound::Inclusive(Bytes::copy_from_slice(rest)))
+            } else if let Some(rest) = arg.strip_prefix(b"(") {
+                Ok(LexBound::Exclusive(Bytes::copy_from_slice(rest)))
+            } else {
+                Err(CommandError::MinMaxNotLex)
+            }
+        }
+    }
+}
+
+fn parse_lex_range(min: &[u8], max: &[u8]) -> Result<LexRange, CommandError> {
+    Ok(LexRange {
+        min: parse_lex_bound(min)?,
+        max: parse_lex_bound(max)?,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RangeSpec {
+    Rank(i64, i64),
+    Score(ScoreRange),
+    Lex(LexRange),
+}
+
+impl RangeSpec {
+    fn select(&self, zset: &SortedSet, rev: bool, limit: Option<RangeLimit>) -> Vec<(Bytes, f64)> {
+        match self {
+            RangeSpec::Rank(start, stop) => zset.range_by_rank(*start, *stop, rev),
+            RangeSpec::Score(range) => zset.range_by_score(range, rev, limit),
+            RangeSpec::Lex(range) => zset.range_by_lex(range, rev, limit),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ZAddCommand {
+    pub key: Bytes,
+    pub flags: ZAddFlags,
+    pub elements: Vec<(f64, Bytes)>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct ZIncrByCommand {
+    pub key: Bytes,
+    pub delta: f64,
+    pub member: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct ZRemCommand {
+    pub key: Bytes,
+    pub members: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct ZCardCommand {
+    pub key: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct ZScoreCommand {
+    pub key: Bytes,
+    pub member: Bytes,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct ZMScoreCommand {
+    pub key: Bytes,
+    pub members: Vec<Bytes>,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct ZRankCommand {
+    pub key: Bytes,
+    pub member: Bytes,
+    pub rev: bool,
+    pub with_score: bool,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct ZCountCommand {
+    pub key: Bytes,
+    pub range: ScoreRange,
+    pub cache: Arc<Db>,
+}
+
+#[derive(Debug)]
+pub struct ZLexCountCommand {
+    pub key: Bytes,
+    pub range: LexRange,
+    pub cache: Arc<Db>,
+}
+
+/// ZRANGE and its legacy ZREVRANGE / ZRANGEBYSCORE / ZRANGEBYLEX forms.
+#[derive(Debug)]
+pub struct ZRangeCommand {
+    pub key: Bytes,
+    pub spec: RangeSpec,
+    pub rev: bool,
+    pub limit: Option<RangeLimit>,
+    pub with_scores: bool,
+    pub cache: Arc<Db>,
+}
+
+/// ZPOPMIN and ZPOPMAX.
+#[derive(Debug)]
+pub struct ZPopCommand {
+    pub key: Bytes,
+    pub count: Option<usize>,
+    pub max: bool,
+    pub cache: Arc<Db>,
+}
+
+/// ZREMRANGEBYRANK, ZREMRANGEBYSCORE and ZREMRANGEBYLEX.
+#[derive(Debug)]
+pub struct ZRemRangeCommand {
+    pub key: Bytes,
+    pub spec: RangeSpec,
+    pub cache: Arc<Db>,
+}
+
+/// ZUNIONSTORE and ZINTERSTORE.
+#[derive(Debug)]
+pub struct ZStoreCommand {
+    pub destination: Bytes,
+    pub keys: Vec<Bytes>,
+    pub weights: Vec<f64>,
+    pub aggregate: Aggregate,
+    pub union: bool,
+    pub cache: Arc<Db>,
+}
+
+impl ZAddCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, usize::MAX)?;
+        let mut flags = ZAddFlags::default();
+        let mut idx = 1;
+        while idx < args.len() {
+            match lower(&args[idx]).as_str() {
+                "nx" => flags.nx = true,
+                "xx" => flags.xx = true,
+                "gt" => flags.gt = true,
+                "lt" => flags.lt = true,
+                "ch" => flags.ch = true,
+                "incr" => flags.incr = true,
+                _ => break,
+            }
+            idx += 1;
+        }
+        let pairs = args[idx..].chunks_exact(2);
+        if !pairs.remainder().is_empty() || idx == args.len() {
+            return Err(CommandError::InvalidCommand);
+        }
+        if flags.nx && flags.xx {
+            return Err(CommandError::ZAddXxNx);
+        }
+        if [flags.nx, flags.gt, flags.lt]
+            .iter()
+            .filter(|f| **f)
+            .count()
+            > 1
+        {
+            return Err(CommandError::ZAddGtLtNx);
+        }
+        if flags.incr && pairs.len() > 1 {
+            return Err(CommandError::ZAddIncrPair);
+        }
+        let elements = pairs
+            .map(|pair| Ok((parse_float(&pair[0])?, pair[1].clone())))
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        Ok(ZAddCommand {
+            key: args[0].clone(),
+            flags,
+            elements,
+            cache,
+        })
+    }
+}
+
+impl ZIncrByCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(ZIncrByCommand {
+            key: args[0].clone(),
+            delta: parse_float(&args[1])?,
+            member: args[2].clone(),
+            cache,
+        })
+    }
+}
+
+impl ZRemCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(ZRemCommand {
+            key,
+            members: args,
+            cache,
+        })
+    }
+}
+
+impl ZCardCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 1)?;
+        Ok(ZCardCommand {
+            key: args[0].clone(),
+            cache,
+        })
+    }
+}
+
+impl ZScoreCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 2)?;
+        Ok(ZScoreCommand {
+            key: args[0].clone(),
+            member: args[1].clone(),
+            cache,
+        })
+    }
+}
+
+impl ZMScoreCommand {
+    pub fn parse(cmd: &str, mut args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, usize::MAX)?;
+        let key = args.remove(0);
+        Ok(ZMScoreCommand {
+            key,
+            members: args,
+            cache,
+        })
+    }
+}
+
+impl ZRankCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        rev: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 2, 3)?;
+        let with_score = match args.get(2) {
+            Some(opt) if opt.eq_ignore_ascii_case(b"withscore") => true,
+            Some(_) => return Err(CommandError::InvalidCommand),
+            None => false,
+        };
+        Ok(ZRankCommand {
+            key: args[0].clone(),
+            member: args[1].clone(),
+            rev,
+            with_score,
+            cache,
+        })
+    }
+}
+
+impl ZCountCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(ZCountCommand {
+            key: args[0].clone(),
+            range: parse_score_range(&args[1], &args[2])?,
+            cache,
+        })
+    }
+}
+
+impl ZLexCountCommand {
+    pub fn parse(cmd: &str, args: Vec<Bytes>, cache: Arc<Db>) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        Ok(ZLexCountCommand {
+            key: args[0].clone(),
+            range: parse_lex_range(&args[1], &args[2])?,
+            cache,
+        })
+    }
+}
+
+impl ZRangeCommand {
+    /// Parses the unified ZRANGE grammar. The legacy commands are expressed
+    /// through `implied`, the flags their name stands for (e.g. ZREVRANGE is
+    /// ZRANGE with REV).
+    pub fn parse(
+        cmd: &str,
+        mut args: Vec<Bytes>,
+        implied: &[&str],
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, usize::MAX)?;
+        args.extend(
+            implied
+                .iter()
+                .map(|flag| Bytes::copy_from_slice(flag.as_bytes())),
+        );
+        let (mut by_score, mut by_lex, mut rev, mut with_scores) = (false, false, false, false);
+        let mut limit = None;
+        let mut rest = args[3..].iter();
+        while let Some(opt) = rest.next() {
+            match lower(opt).as_str() {
+                "byscore" => by_score = true,
+                "bylex" => by_lex = true,
+                "rev" => rev = true,
+                "withscores" => with_scores = true,
+                "limit" => match (rest.next(), rest.next()) {
+                    (Some(offset), Some(count)) => {
+                        limit = Some(RangeLimit {
+                            offset: parse_int(offset)?,
+                            count: parse_int(count)?,
+                        })
+                    }
+                    _ => return Err(CommandError::InvalidCommand),
+                },
+                _ => return Err(CommandError::InvalidCommand),
+            }
+        }
+        if by_score && by_lex {
+            return Err(CommandError::InvalidCommand);
+        }
+        if limit.is_some() && !by_score && !by_lex {
+            return Err(CommandError::LimitWithoutBy);
+        }
+        if with_scores && by_lex {
+            return Err(CommandError::WithScoresByLex);
+        }
+        // With REV the score and lex forms take `max min`.
+        let (lo, hi) = if rev && (by_score || by_lex) {
+            (&args[2], &args[1])
+        } else {
+            (&args[1], &args[2])
+        };
+        let spec = if by_score {
+            RangeSpec::Score(parse_score_range(lo, hi)?)
+        } else if by_lex {
+            RangeSpec::Lex(parse_lex_range(lo, hi)?)
+        } else {
+            RangeSpec::Rank(parse_int(lo)?, parse_int(hi)?)
+        };
+        Ok(ZRangeCommand {
+            key: args[0].clone(),
+            spec,
+            rev,
+            limit,
+            with_scores,
+            cache,
+        })
+    }
+}
+
+impl ZPopCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        max: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 1, 2)?;
+        let count = match args.get(1) {
+            Some(count) => {
+                let count = parse_int(count).map_err(|_| CommandError::NotPositive)?;
+                if count < 0 {
+                    return Err(CommandError::NotPositive);
+                }
+                Some(count as usize)
+            }
+            None => None,
+        };
+        Ok(ZPopCommand {
+            key: args[0].clone(),
+            count,
+            max,
+            cache,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeKind {
+    Rank,
+    Score,
+    Lex,
+}
+
+impl ZRemRangeCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        kind: RangeKind,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, 3)?;
+        let spec = match kind {
+            RangeKind::Rank => RangeSpec::Rank(parse_int(&args[1])?, parse_int(&args[2])?),
+            RangeKind::Score => RangeSpec::Score(parse_score_range(&args[1], &args[2])?),
+            RangeKind::Lex => RangeSpec::Lex(parse_lex_range(&args[1], &args[2])?),
+        };
+        Ok(ZRemRangeCommand {
+            key: args[0].clone(),
+            spec,
+            cache,
+        })
+    }
+}
+
+impl ZStoreCommand {
+    pub fn parse(
+        cmd: &str,
+        args: Vec<Bytes>,
+        union: bool,
+        cache: Arc<Db>,
+    ) -> Result<Self, CommandError> {
+        check_arity(cmd, &args, 3, usize::MAX)?;
+        let numkeys = parse_int(&args[1])?;
+        if numkeys <= 0 {
+            return Err(CommandError::NoInputKeys(cmd.to_string()));
+        }
+        let numkeys = numkeys as usize;
+        if numkeys > args.len() - 2 {
+            return Err(CommandError::InvalidCommand);
+        }
+        let keys = args[2..2 + numkeys].to_vec();
+        let mut weights = vec![1.0; numkeys];
+        let mut aggregate = Aggregate::Sum;
+        let mut idx = 2 + numkeys;
+        while idx < args.len() {
+            match lower(&args[idx]).as_str() {
+                "weights" if idx + numkeys < args.len() => {
+                    for (i, weight) in weights.iter_mut().enumerate() {
+                        *weight = numeric::parse_float(&args[idx + 1 + i])
+                            .ok_or(CommandError::WeightNotFloat)?;
+                    }
+                    idx += numkeys + 1;
+                }
+                "aggregate" if idx + 1 < args.len() => {
+                    aggregate = match lower(&args[idx + 1]).as_str() {
+                        "sum" => Aggregate::Sum,
+                        "min" => Aggregate::Min,
+                        "max" => Aggregate::Max,
+                        _ => return Err(CommandError::InvalidCommand),
+                    };
+                    idx += 2;
+                }
+                _ => return Err(CommandError::InvalidCommand),
+            }
+        }
+        Ok(ZStoreCommand {
+            destination: args[0].clone(),
+            keys,
+            weights,
+            aggregate,
+            union,
+            cache,
+        })
+    }
+}
+
+impl CommandApply for ZAddCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let outcomes = ks.zadd(&self.key, self.flags, &self.elements)?;
+        if self.flags.incr {
+            return Ok(match outcomes.first() {
+                Some(ZAddOutcome::Added(score))
+                | Some(ZAddOutcome::Updated(score))
+                | Some(ZAddOutcome::Unchanged(score)) => score_resp(*score),
+                _ => RespDT::Null,
+            });
+        }
+        let changed = outcomes
+            .iter()
+            .filter(|outcome| match outcome {
+                ZAddOutcome::Added(_) => true,
+                ZAddOutcome::Updated(_) => self.flags.ch,
+                _ => false,
+            })
+            .count();
+        Ok(RespDT::Integer(changed as i64))
+    }
+}
+
+impl CommandApply for ZIncrByCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(score_resp(ks.zincrby(
+            &self.key,
+            &self.member,
+            self.delta,
+        )?))
+    }
+}
+
+impl CommandApply for ZRemCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.zrem(&self.key, &self.members)?))
+    }
+}
+
+impl CommandApply for ZCardCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.zcard(&self.key)?))
+    }
+}
+
+impl CommandApply for ZScoreCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(ks
+            .zscore(&self.key, &self.member)?
+            .map_or(RespDT::Null, score_resp))
+    }
+}
+
+impl CommandApply for ZMScoreCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Array(
+            ks.zmscore(&self.key, &self.members)?
+                .into_iter()
+                .map(|score| score.map_or(RespDT::Null, score_resp))
+                .collect(),
+        ))
+    }
+}
+
+impl CommandApply for ZRankCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(match ks.zrank(&self.key, &self.member, self.rev)? {
+            Some((rank, score)) if self.with_score => {
+                RespDT::Array(vec![RespDT::Integer(rank as i64), score_resp(score)])
+            }
+            Some((rank, _)) => RespDT::Integer(rank as i64),
+            None if self.with_score => RespDT::NullArray,
+            None => RespDT::Null,
+        })
+    }
+}
+
+impl CommandApply for ZCountCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.zcount(&self.key, &self.range)?))
+    }
+}
+
+impl CommandApply for ZLexCountCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.zlexcount(&self.key, &self.range)?))
+    }
+}
+
+impl CommandApply for ZRangeCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let items = match ks.get_zset(&self.key)? {
+            Some(zset) => self.spec.select(zset, self.rev, self.limit),
+            None => vec![],
+        };
+        Ok(scored_array(items, self.with_scores))
+    }
+}
+
+impl CommandApply for ZPopCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let popped = ks.zpop(&self.key, self.count.unwrap_or(1), self.max)?;
+        Ok(scored_array(popped, true))
+    }
+}
+
+impl CommandApply for ZRemRangeCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        let event = match self.spec {
+            RangeSpec::Rank(..) => "zremrangebyrank",
+            RangeSpec::Score(_) => "zremrangebyscore",
+            RangeSpec::Lex(_) => "zremrangebylex",
+        };
+        let removed = ks.zremrange(&self.key, event, |zset| self.spec.select(zset, false, None))?;
+        Ok(RespDT::Integer(removed))
+    }
+}
+
+impl CommandApply for ZStoreCommand {
+    fn db(&self) -> &Db {
+        &self.cache
+    }
+
+    fn apply(&self, ks: &mut Keyspace) -> Result<RespDT, DbError> {
+        Ok(RespDT::Integer(ks.zstore_combine(
+            &self.destination,
+            &self.keys,
+            &self.weights,
+            self.aggregate,
+            self.union,
+        )?))
+    }
+}
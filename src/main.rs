@@ -11,26 +11,129 @@ mod store;
 use cli::CliArgs;
 use cmd::Command;
 use resp::RespHandler;
-use store::Db;
+use store::{notify::NotifyFlags, Db};
 
-use crate::cmd::command::RespCache;
+use crate::cmd::command::{CommandError, RespCache, OK_RESP};
+use crate::cmd::pubsub::Subscriptions;
+use crate::cmd::server::Client;
+use crate::cmd::transaction::{Transaction, Watches};
+use crate::resp::{Protocol, RespDT};
 use clap::Parser;
 
 async fn handle_conn(cache: Arc<Db>, stream: TcpStream) -> Result<(), Box<dyn std::error::Error>> {
+    let mut watches = Watches::default();
+    let mut subscriptions = Subscriptions::new(&cache);
+    // The error is not `Send`, so it cannot be held across the unwatch.
+    let result = serve_conn(&cache, stream, &mut watches, &mut subscriptions)
+        .await
+        .map_err(|e| e.to_string());
+    watches.clear(&cache).await;
+    subscriptions.clear(&cache);
+    Ok(result?)
+}
+
+async fn serve_conn(
+    cache: &Arc<Db>,
+    stream: TcpStream,
+    watches: &mut Watches,
+    subscriptions: &mut Subscriptions,
+) -> Result<(), Box<dyn std::error::Error>> {
     let mut handler = RespHandler::new(BufReader::new(stream));
+    let mut client = Client::new(cache);
+    let mut transaction: Option<Transaction> = None;
     loop {
+        // Messages are pushed whenever the client is not mid-command.
+        let message = tokio::select! {
+            ready = handler.readable() => {
+                ready?;
+                None
+            }
+            message = subscriptions.next_message() => Some(message),
+        };
+        if let Some(message) = message {
+            handler.write(message.encode(client.protocol)).await?;
+            continue;
+        }
         let resp = handler.decode().await?;
         match resp {
+            // Redis ignores empty command arrays.
+            Some(RespDT::Array(items)) if items.is_empty() => continue,
             Some(res) => {
+                let name = res
+                    .extract_array()
+                    .map(|(name, _)| name)
+                    .unwrap_or_default();
                 let rc = RespCache::new(cache.clone(), res);
-                let cmd: Command = match rc.try_into() {
-                    Ok(cmd) => cmd,
-                    Err(e) => {
-                        eprintln!("Error: {:?}", e);
-                        continue;
+                let parsed: Result<Command, _> = rc.try_into();
+                if let Err(e) = &parsed {
+                    eprintln!("Error: {:?}", e);
+                }
+                // RESP3 clients take messages as pushes, so they can go on
+                // running commands while subscribed.
+                let restricted = subscriptions.is_active() && client.protocol == Protocol::Resp2;
+                let parsed = match parsed {
+                    Ok(Command::Subscribe(_) | Command::Unsubscribe(_) | Command::Ping(_)) => {
+                        parsed
                     }
+                    Ok(_) if restricted => Err(CommandError::SubscribedMode(name)),
+                    parsed => parsed,
+                };
+                let response = match (transaction.as_mut(), parsed) {
+                    (Some(_), Ok(Command::Multi)) => {
+                        RespDT::SimpleError(CommandError::NestedMulti.to_string())
+                            .encode(client.protocol)
+                    }
+                    (Some(_), Ok(Command::Watch(_))) => {
+                        RespDT::SimpleError(CommandError::WatchInsideMulti.to_string())
+                            .encode(client.protocol)
+                    }
+                    (Some(_), Ok(Command::Discard)) => {
+                        transaction = None;
+                        watches.clear(cache).await;
+                        RespDT::SimpleString(OK_RESP.to_string()).encode(client.protocol)
+                    }
+                    (Some(_), Ok(Command::Exec)) => {
+                        let tx = transaction.take().expect("inside a transaction");
+                        tx.exec(cache, watches).await.encode(client.protocol)
+                    }
+                    (
+                        Some(tx),
+                        Ok(Command::Subscribe(_) | Command::Unsubscribe(_) | Command::Hello(_)),
+                    ) => tx
+                        .queue(Err(CommandError::NotAllowedInMulti))
+                        .encode(client.protocol),
+                    (Some(tx), parsed) => tx.queue(parsed).encode(client.protocol),
+                    (None, Ok(Command::Subscribe(cmd))) => subscriptions
+                        .subscribe(cache, &cmd)
+                        .iter()
+                        .flat_map(|reply| reply.encode(client.protocol))
+                        .collect(),
+                    (None, Ok(Command::Unsubscribe(cmd))) => subscriptions
+                        .unsubscribe(cache, &cmd)
+                        .iter()
+                        .flat_map(|reply| reply.encode(client.protocol))
+                        .collect(),
+                    (None, Ok(Command::Hello(cmd))) => {
+                        let reply = client.hello(&cmd);
+                        reply.encode(client.protocol)
+                    }
+                    // Subscribed RESP2 clients get PONG as a message-shaped
+                    // array.
+                    (None, Ok(Command::Ping(_))) if restricted => {
+                        RespDT::Array(vec![RespDT::bulk("pong"), RespDT::bulk("")])
+                            .encode(client.protocol)
+                    }
+                    (None, Ok(cmd)) => {
+                        match &cmd {
+                            Command::Multi => transaction = Some(Transaction::default()),
+                            Command::Watch(keys) => watches.watch(cache, keys).await,
+                            Command::Unwatch => watches.clear(cache).await,
+                            _ => {}
+                        }
+                        cmd.execute().await?.encode(client.protocol)
+                    }
+                    (None, Err(e)) => RespDT::SimpleError(e.to_string()).encode(client.protocol),
                 };
-                let response = cmd.execute().await?;
                 handler.write(response).await?;
             }
             None => return Ok(()),
@@ -41,15 +144,22 @@ async fn handle_conn(cache: Arc<Db>, stream: TcpStream) -> Result<(), Box<dyn st
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = CliArgs::parse();
+    let mut db = Db::new();
+    db.cache.get_mut().notifier.flags = NotifyFlags::parse(&args.notify_keyspace_events)
+        .ok_or("invalid --notify-keyspace-events")?;
     let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), args.port));
     let listener = TcpListener::bind(addr).await?;
     println!("Listening on {}:{}", addr.ip(), addr.port());
-    let cache = Arc::new(Db::new());
+    let cache = Arc::new(db);
+    let expiring = Arc::clone(&cache);
+    tokio::spawn(async move { expiring.active_expire().await });
     loop {
         let cache_clone = Arc::clone(&cache);
         let (stream, _) = listener.accept().await?;
         tokio::spawn(async move {
-            handle_conn(cache_clone, stream).await.unwrap();
+            if let Err(e) = handle_conn(cache_clone, stream).await {
+                eprintln!("Connection error: {}", e);
+            }
         });
     }
 }
@@ -1,4 +1,3 @@
-
 pub mod resp_parser;
 
-pub use resp_parser::{RespDT, RespHandler};
+pub use resp_parser::{Protocol, RespDT, RespHandler};
@@ -4,17 +4,20 @@ use std::{
 };
 
 use async_recursion::async_recursion;
+use bytes::Bytes;
 use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
 
+use crate::store::numeric::format_float;
+
 /// up to 512 MB in length
 const RESP_MAX_SIZE: i64 = 512 * 1024 * 1024;
 const CRLF_BYTES: &[u8] = b"\r\n";
 const NULL_BYTES: &[u8] = b"$-1\r\n";
 const NULL_ARRAY_BYTES: &[u8] = b"*-1\r\n";
+const NULL_RESP3_BYTES: &[u8] = b"_\r\n";
 
 pub struct RespHandler<R> {
     pub rw_tools: R,
-    pub buf_bulk: bool,
 }
 
 impl<R> RespHandler<R>
@@ -22,18 +25,7 @@ where
     R: AsyncBufRead + Unpin + Send + AsyncWriteExt,
 {
     pub fn new(reader: R) -> Self {
-        RespHandler {
-            rw_tools: reader,
-            buf_bulk: false,
-        }
-    }
-
-    #[allow(dead_code)]
-    pub fn with_buf_bulk(reader: R) -> Self {
-        RespHandler {
-            rw_tools: reader,
-            buf_bulk: true,
-        }
+        RespHandler { rw_tools: reader }
     }
 
     pub async fn write(&mut self, buff: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
@@ -42,6 +34,14 @@ where
         Ok(())
     }
 
+    /// Waits until there is input to decode, or the peer has hung up.
+    /// Unlike `decode` this can be raced against other events without
+    /// losing anything, as the input stays buffered.
+    pub async fn readable(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.rw_tools.fill_buf().await?;
+        Ok(())
+    }
+
     #[async_recursion]
     pub async fn decode(&mut self) -> Result<Option<RespDT>, Box<dyn std::error::Error>> {
         let mut res: Vec<u8> = Vec::new();
@@ -67,50 +67,56 @@ where
             b'+' => parse_string(bytes).map(|s| Some(RespDT::SimpleString(s))),
             b'-' => parse_string(bytes).map(|s| Some(RespDT::SimpleError(s))),
             b':' => parse_integer(bytes).map(|i| Some(RespDT::Integer(i))),
-            b'$' => {
-                let data_length = parse_integer(bytes)?;
-                if data_length == -1 {
-                    return Ok(Some(RespDT::Null));
-                }
-                if !(0..RESP_MAX_SIZE).contains(&data_length) {
-                    return Err(Error::new(
-                        ErrorKind::InvalidInput,
-                        format!("invalid bulk string length: {}", data_length),
-                    )
-                    .into());
-                }
-                let mut buf = vec![0; (data_length + 2) as usize];
-                self.rw_tools.read_exact(&mut buf).await?;
-                if !is_crlf(buf[buf.len() - 2], buf[buf.len() - 1]) {
-                    return Err(Error::new(
-                        ErrorKind::InvalidInput,
-                        format!("invalid CRLF: {:?}", buf),
-                    )
-                    .into());
-                }
-                buf.truncate(data_length as usize);
-                if self.buf_bulk {
-                    return Ok(Some(RespDT::BufBulk(buf)));
+            b'_' if bytes.is_empty() => Ok(Some(RespDT::Null)),
+            b'#' => match bytes {
+                b"t" => Ok(Some(RespDT::Boolean(true))),
+                b"f" => Ok(Some(RespDT::Boolean(false))),
+                _ => Err(invalid(format!("invalid boolean: {:?}", bytes))),
+            },
+            b',' => parse_double(bytes).map(|f| Some(RespDT::Double(f))),
+            b'(' => parse_big_number(bytes).map(|n| Some(RespDT::BigNumber(n))),
+            b'$' => match self.read_bulk(bytes).await? {
+                Some(data) => Ok(Some(RespDT::Bulk(data))),
+                None => Ok(Some(RespDT::Null)),
+            },
+            b'!' => match self.read_bulk(bytes).await? {
+                Some(data) => parse_string(&data).map(|s| Some(RespDT::BulkError(s))),
+                None => Err(invalid("null bulk error")),
+            },
+            b'=' => {
+                let data = self
+                    .read_bulk(bytes)
+                    .await?
+                    .ok_or_else(|| invalid("null verbatim string"))?;
+                if data.len() < 4 || data[3] != b':' {
+                    return Err(invalid(format!("invalid verbatim string: {:?}", data)));
                 }
-                return parse_string(&buf).map(|s| Some(RespDT::Bulk(s)));
+                let format = parse_string(&data[..3])?;
+                Ok(Some(RespDT::VerbatimString(format, data.slice(4..))))
             }
             b'*' => {
-                let data_length = parse_integer(bytes)?;
-                if data_length == -1 {
-                    return Ok(Some(RespDT::NullArray));
+                let len = parse_length(bytes, "array")?;
+                match len {
+                    Some(len) => Ok(Some(RespDT::Array(self.decode_items(len).await?))),
+                    None => Ok(Some(RespDT::NullArray)),
                 }
-                if !(0..RESP_MAX_SIZE).contains(&data_length) {
-                    return Err(Error::new(
-                        ErrorKind::InvalidInput,
-                        format!("invalid array length: {}", data_length),
-                    )
-                    .into());
-                }
-                let mut arr = Vec::with_capacity(data_length as usize);
-                for _ in 0..data_length {
-                    arr.push(self.decode().await?.unwrap());
+            }
+            b'~' => {
+                let len = parse_length(bytes, "set")?.ok_or_else(|| invalid("null set"))?;
+                Ok(Some(RespDT::Set(self.decode_items(len).await?)))
+            }
+            b'>' => {
+                let len = parse_length(bytes, "push")?.ok_or_else(|| invalid("null push"))?;
+                Ok(Some(RespDT::Push(self.decode_items(len).await?)))
+            }
+            b'%' => {
+                let len = parse_length(bytes, "map")?.ok_or_else(|| invalid("null map"))?;
+                let mut items = self.decode_items(len * 2).await?.into_iter();
+                let mut pairs = Vec::with_capacity(len);
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    pairs.push((key, value));
                 }
-                Ok(Some(RespDT::Array(arr)))
+                Ok(Some(RespDT::Map(pairs)))
             }
             _ => Err(Error::new(
                 ErrorKind::InvalidInput,
@@ -119,6 +125,39 @@ where
             .into()),
         }
     }
+
+    /// Reads the payload of a `$`, `!` or `=` frame whose length line was
+    /// `len`. `None` is the RESP2 null bulk string.
+    async fn read_bulk(&mut self, len: &[u8]) -> Result<Option<Bytes>, Box<dyn std::error::Error>> {
+        let Some(data_length) = parse_length(len, "bulk string")? else {
+            return Ok(None);
+        };
+        let mut buf = vec![0; data_length + 2];
+        self.rw_tools.read_exact(&mut buf).await?;
+        if !is_crlf(buf[buf.len() - 2], buf[buf.len() - 1]) {
+            return Err(
+                Error::new(ErrorKind::InvalidInput, format!("invalid CRLF: {:?}", buf)).into(),
+            );
+        }
+        buf.truncate(data_length);
+        Ok(Some(Bytes::from(buf)))
+    }
+
+    async fn decode_items(
+        &mut self,
+        len: usize,
+    ) -> Result<Vec<RespDT>, Box<dyn std::error::Error>> {
+        // The length is untrusted, so only a bounded amount is reserved up front.
+        let mut items = Vec::with_capacity(len.min(1024));
+        for _ in 0..len {
+            let item = self
+                .decode()
+                .await?
+                .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "unexpected EOF"))?;
+            items.push(item);
+        }
+        Ok(items)
+    }
 }
 
 #[inline]
@@ -138,47 +177,111 @@ fn parse_integer(bytes: &[u8]) -> Result<i64, Box<dyn std::error::Error>> {
         .map_err(|err| Error::new(ErrorKind::InvalidData, err).into())
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+fn invalid(msg: impl Into<String>) -> Box<dyn std::error::Error> {
+    Error::new(ErrorKind::InvalidInput, msg.into()).into()
+}
+
+/// The length of a bulk or aggregate frame, `None` for `-1`.
+fn parse_length(bytes: &[u8], what: &str) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    match parse_integer(bytes)? {
+        -1 => Ok(None),
+        len if (0..RESP_MAX_SIZE).contains(&len) => Ok(Some(len as usize)),
+        len => Err(invalid(format!("invalid {} length: {}", what, len))),
+    }
+}
+
+/// Parses a RESP3 double, which may also be `inf`, `-inf` or `nan`.
+fn parse_double(bytes: &[u8]) -> Result<f64, Box<dyn std::error::Error>> {
+    std::str::from_utf8(bytes)?
+        .parse::<f64>()
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err).into())
+}
+
+fn parse_big_number(bytes: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let digits = bytes.strip_prefix(b"-").unwrap_or(bytes);
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return Err(invalid(format!("invalid big number: {:?}", bytes)));
+    }
+    parse_string(bytes)
+}
+
+/// Formats a double as Redis does on the wire: the shortest representation
+/// that round-trips, and `inf`, `-inf` or `nan` for the special values.
+fn format_double(f: f64) -> String {
+    if f.is_nan() {
+        "nan".to_string()
+    } else {
+        format_float(f)
+    }
+}
+
+/// The protocol a connection speaks: RESP2 until it says otherwise with
+/// HELLO.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+/// A RESP value. Replies are built with the RESP3 types where they fit and
+/// encoded for the connection's protocol: RESP2 clients get each RESP3 type
+/// in the RESP2 shape Redis gives them.
+#[derive(Debug, Clone, PartialEq)]
 pub enum RespDT {
     SimpleString(String),
     SimpleError(String),
     Integer(i64),
-    Bulk(String),
-    BufBulk(Vec<u8>),
+    /// Binary safe: keys and values are passed through as raw bytes.
+    Bulk(Bytes),
     Array(Vec<RespDT>),
     NullArray,
     Null,
-    // Booleans(char),
-    // Doubles(char),
-    // BigNums(char),
-    // BulkError(char),
-    // VerbatimString(char),
-    // Maps(char),
-    // Sets(char),
-    // Pushes(char),
+    /// RESP2: the integer 1 or 0.
+    Boolean(bool),
+    /// RESP2: a bulk string.
+    Double(f64),
+    /// Decimal digits, with an optional leading minus. RESP2: a bulk string.
+    BigNumber(String),
+    /// An error that may span lines. RESP2: a simple error.
+    BulkError(String),
+    /// Text along with its three letter format, such as `txt`. RESP2: a bulk
+    /// string of the text.
+    VerbatimString(String, Bytes),
+    /// RESP2: an array of the keys and values, alternating.
+    Map(Vec<(RespDT, RespDT)>),
+    /// RESP2: an array.
+    Set(Vec<RespDT>),
+    /// Out of band data, such as Pub/Sub messages. RESP2: an array.
+    Push(Vec<RespDT>),
 }
 
 impl RespDT {
-    pub fn extract_array(&self) -> Result<(String, Vec<&RespDT>), Box<dyn std::error::Error>> {
+    /// A bulk string from anything that converts into bytes cheaply, such as
+    /// a `String`, a `Vec<u8>` or a static string.
+    pub fn bulk(data: impl Into<Bytes>) -> Self {
+        RespDT::Bulk(data.into())
+    }
+
+    /// Splits a command frame into its lowercased name and its arguments.
+    /// `None` unless this is a non-empty array led by a bulk string.
+    pub fn extract_array(&self) -> Option<(String, Vec<&RespDT>)> {
         match self {
             RespDT::Array(a) => {
-                let cmd = a
-                    .first()
-                    .unwrap()
-                    .clone()
-                    .extract_bulk_str()
-                    .unwrap()
-                    .to_ascii_lowercase();
+                let RespDT::Bulk(cmd) = a.first()? else {
+                    return None;
+                };
+                let cmd = String::from_utf8_lossy(cmd).to_ascii_lowercase();
                 let args = a.iter().skip(1).collect::<Vec<_>>();
-                Ok((cmd, args))
+                Some((cmd, args))
             }
-            _ => Err(Error::new(ErrorKind::Unsupported, "Unexpected command format").into()),
+            _ => None,
         }
     }
 
-    pub fn extract_bulk_str(&self) -> Result<String, Box<dyn std::error::Error>> {
+    pub fn extract_bulk_str(&self) -> Result<Bytes, Box<dyn std::error::Error>> {
         match self {
-            RespDT::Bulk(s) => Ok(s.to_string()),
+            RespDT::Bulk(s) => Ok(s.clone()),
             _ => Err(Error::new(
                 ErrorKind::Unsupported,
                 "Expected command to be a bulk string",
@@ -193,9 +296,9 @@ impl RespDT {
     //     String::from_utf8(res).map_err(|err| Error::new(ErrorKind::InvalidData, err).into())
     // }
 
-    pub fn encode_raw(&self) -> Vec<u8> {
+    pub fn encode(&self, protocol: Protocol) -> Vec<u8> {
         let mut res: Vec<u8> = Vec::new();
-        self.buf_encode(&mut res);
+        self.buf_encode(&mut res, protocol);
         res
     }
 
@@ -209,8 +312,10 @@ impl RespDT {
     //     res
     // }
 
-    fn buf_encode(&self, buf: &mut Vec<u8>) {
+    fn buf_encode(&self, buf: &mut Vec<u8>, protocol: Protocol) {
+        let resp3 = protocol == Protocol::Resp3;
         match self {
+            RespDT::Null | RespDT::NullArray if resp3 => buf.extend_from_slice(NULL_RESP3_BYTES),
             RespDT::Null => buf.extend_from_slice(NULL_BYTES),
             RespDT::NullArray => buf.extend_from_slice(NULL_ARRAY_BYTES),
             RespDT::SimpleString(s) => {
@@ -218,9 +323,14 @@ impl RespDT {
                 buf.extend_from_slice(s.as_bytes());
                 buf.extend_from_slice(CRLF_BYTES);
             }
+            // Error text can quote the client, so any line break in it is
+            // blanked out to keep the error on one line, as Redis does.
             RespDT::SimpleError(s) => {
                 buf.extend_from_slice(b"-");
-                buf.extend_from_slice(s.as_bytes());
+                buf.extend(s.bytes().map(|b| match b {
+                    b'\r' | b'\n' => b' ',
+                    b => b,
+                }));
                 buf.extend_from_slice(CRLF_BYTES);
             }
             RespDT::Integer(i) => {
@@ -228,32 +338,77 @@ impl RespDT {
                 buf.extend_from_slice(i.to_string().as_bytes());
                 buf.extend_from_slice(CRLF_BYTES);
             }
-            RespDT::Bulk(s) => {
-                buf.extend_from_slice(b"$");
-                buf.extend_from_slice(s.len().to_string().as_bytes());
-                buf.extend_from_slice(CRLF_BYTES);
-                buf.extend_from_slice(s.as_bytes());
-                buf.extend_from_slice(CRLF_BYTES);
+            RespDT::Bulk(data) => encode_blob(buf, b'$', data),
+            RespDT::Array(arr) => encode_aggregate(buf, b'*', arr, protocol),
+            RespDT::Boolean(b) if resp3 => {
+                buf.extend_from_slice(if *b { b"#t\r\n" } else { b"#f\r\n" })
             }
-            RespDT::BufBulk(data) => {
-                buf.extend_from_slice(b"$");
-                buf.extend_from_slice(data.len().to_string().as_bytes());
-                buf.extend_from_slice(CRLF_BYTES);
-                buf.extend_from_slice(data);
+            RespDT::Boolean(b) => RespDT::Integer(*b as i64).buf_encode(buf, protocol),
+            RespDT::Double(f) if resp3 => {
+                buf.extend_from_slice(b",");
+                buf.extend_from_slice(format_double(*f).as_bytes());
                 buf.extend_from_slice(CRLF_BYTES);
             }
-            RespDT::Array(arr) => {
-                buf.extend_from_slice(b"*");
-                buf.extend_from_slice(arr.len().to_string().as_bytes());
+            RespDT::Double(f) => encode_blob(buf, b'$', format_double(*f).as_bytes()),
+            RespDT::BigNumber(n) if resp3 => {
+                buf.extend_from_slice(b"(");
+                buf.extend_from_slice(n.as_bytes());
                 buf.extend_from_slice(CRLF_BYTES);
-                for _ in arr {
-                    self.buf_encode(buf);
+            }
+            RespDT::BigNumber(n) => encode_blob(buf, b'$', n.as_bytes()),
+            RespDT::BulkError(s) if resp3 => encode_blob(buf, b'!', s.as_bytes()),
+            RespDT::BulkError(s) => RespDT::SimpleError(s.clone()).buf_encode(buf, protocol),
+            RespDT::VerbatimString(format, data) if resp3 => {
+                let mut text = Vec::with_capacity(format.len() + 1 + data.len());
+                text.extend_from_slice(format.as_bytes());
+                text.push(b':');
+                text.extend_from_slice(data);
+                encode_blob(buf, b'=', &text);
+            }
+            RespDT::VerbatimString(_, data) => encode_blob(buf, b'$', data),
+            RespDT::Map(pairs) if resp3 => {
+                encode_header(buf, b'%', pairs.len());
+                for (key, value) in pairs {
+                    key.buf_encode(buf, protocol);
+                    value.buf_encode(buf, protocol);
                 }
             }
+            RespDT::Map(pairs) => {
+                encode_header(buf, b'*', pairs.len() * 2);
+                for (key, value) in pairs {
+                    key.buf_encode(buf, protocol);
+                    value.buf_encode(buf, protocol);
+                }
+            }
+            RespDT::Set(items) => {
+                encode_aggregate(buf, if resp3 { b'~' } else { b'*' }, items, protocol)
+            }
+            RespDT::Push(items) => {
+                encode_aggregate(buf, if resp3 { b'>' } else { b'*' }, items, protocol)
+            }
         }
     }
 }
 
+fn encode_header(buf: &mut Vec<u8>, prefix: u8, len: usize) {
+    buf.push(prefix);
+    buf.extend_from_slice(len.to_string().as_bytes());
+    buf.extend_from_slice(CRLF_BYTES);
+}
+
+fn encode_blob(buf: &mut Vec<u8>, prefix: u8, data: &[u8]) {
+    encode_header(buf, prefix, data.len());
+    buf.extend_from_slice(data);
+    buf.extend_from_slice(CRLF_BYTES);
+}
+
+fn encode_aggregate(buf: &mut Vec<u8>, prefix: u8, items: &[RespDT], protocol: Protocol) {
+    encode_header(buf, prefix, items.len());
+    for item in items {
+        item.buf_encode(buf, protocol);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
@@ -334,19 +489,94 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_parse_buf_bulk_string() {
-        let input = b"$5\r\nHello\r\n";
-        let mut parser = RespHandler::with_buf_bulk(BufReader::new(Cursor::new(Vec::from(input))));
-        let r = parser.decode().await;
-        assert!(r.is_ok());
+    async fn test_parse_bulk_string_binary() {
+        let input = b"$4\r\n\x1f\x8b\r\n\r\n";
+        let mut parser = RespHandler::new(BufReader::new(Cursor::new(Vec::from(input))));
+        let r = parser.decode().await.unwrap();
+        assert_eq!(r, Some(RespDT::bulk(&b"\x1f\x8b\r\n"[..])));
     }
 
     #[tokio::test]
-    async fn test_parse_buf_bulk_string_empty() {
-        let input = b"$0\r\n\r\n";
-        let mut parser = RespHandler::with_buf_bulk(BufReader::new(Cursor::new(Vec::from(input))));
-        let r = parser.decode().await;
-        assert!(r.is_ok());
+    async fn test_encode_bulk_string_binary() {
+        let data = RespDT::bulk(vec![0xff, 0x00]);
+        assert_eq!(data.encode(Protocol::Resp2), b"$2\r\n\xff\x00\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_parse_resp3_scalars() {
+        let input =
+            b"_\r\n#t\r\n,-1.5\r\n,inf\r\n(-12345678901234567890\r\n!3\r\nERR\r\n=7\r\ntxt:hey\r\n";
+        let mut parser = RespHandler::new(BufReader::new(Cursor::new(Vec::from(&input[..]))));
+        let mut values = vec![];
+        while let Some(value) = parser.decode().await.unwrap() {
+            values.push(value);
+        }
+        assert_eq!(
+            values,
+            [
+                RespDT::Null,
+                RespDT::Boolean(true),
+                RespDT::Double(-1.5),
+                RespDT::Double(f64::INFINITY),
+                RespDT::BigNumber("-12345678901234567890".to_string()),
+                RespDT::BulkError("ERR".to_string()),
+                RespDT::VerbatimString("txt".to_string(), Bytes::from("hey")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_resp3_aggregates() {
+        let input = b"%2\r\n+a\r\n:1\r\n+b\r\n~1\r\n#f\r\n>2\r\n+message\r\n$2\r\nhi\r\n";
+        let mut parser = RespHandler::new(BufReader::new(Cursor::new(Vec::from(&input[..]))));
+        let map = parser.decode().await.unwrap().unwrap();
+        assert_eq!(
+            map,
+            RespDT::Map(vec![
+                (RespDT::SimpleString("a".into()), RespDT::Integer(1)),
+                (
+                    RespDT::SimpleString("b".into()),
+                    RespDT::Set(vec![RespDT::Boolean(false)])
+                ),
+            ])
+        );
+        let push = parser.decode().await.unwrap().unwrap();
+        assert_eq!(
+            push,
+            RespDT::Push(vec![
+                RespDT::SimpleString("message".into()),
+                RespDT::bulk("hi")
+            ])
+        );
+        // The encoder gives back what was decoded.
+        assert_eq!(
+            [map, push].map(|v| v.encode(Protocol::Resp3)).concat(),
+            input
+        );
+    }
+
+    #[tokio::test]
+    async fn test_encode_resp3_types_for_resp2() {
+        let map = RespDT::Map(vec![
+            (RespDT::bulk("score"), RespDT::Double(2.5)),
+            (RespDT::bulk("ok"), RespDT::Boolean(true)),
+            (RespDT::bulk("none"), RespDT::Null),
+        ]);
+        assert_eq!(
+            map.encode(Protocol::Resp2),
+            b"*6\r\n$5\r\nscore\r\n$3\r\n2.5\r\n$2\r\nok\r\n:1\r\n$4\r\nnone\r\n$-1\r\n"
+        );
+        assert_eq!(
+            map.encode(Protocol::Resp3),
+            b"%3\r\n$5\r\nscore\r\n,2.5\r\n$2\r\nok\r\n#t\r\n$4\r\nnone\r\n_\r\n"
+        );
+        let push = RespDT::Push(vec![RespDT::VerbatimString("txt".into(), Bytes::from("x"))]);
+        assert_eq!(push.encode(Protocol::Resp2), b"*1\r\n$1\r\nx\r\n");
+        assert_eq!(
+            RespDT::BulkError("ERR a\r\nb".into()).encode(Protocol::Resp2),
+            b"-ERR a  b\r\n"
+        );
+        assert_eq!(RespDT::NullArray.encode(Protocol::Resp3), b"_\r\n");
     }
 
     #[tokio::test]
@@ -404,4 +634,34 @@ mod tests {
         let r = parser.decode().await;
         assert!(r.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_non_command_frames() {
+        let input = b"*0\r\n+PING\r\n*1\r\n:1\r\n";
+        let mut parser = RespHandler::new(BufReader::new(Cursor::new(Vec::from(&input[..]))));
+        let empty = parser.decode().await.unwrap().unwrap();
+        assert!(empty == RespDT::Array(vec![]));
+        assert!(empty.extract_array().is_none());
+        let simple = parser.decode().await.unwrap().unwrap();
+        assert!(simple.extract_array().is_none());
+        let integer = parser.decode().await.unwrap().unwrap();
+        assert!(integer.extract_array().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_parse_huge_array_length() {
+        // Announcing more items than arrive is an EOF, not a huge allocation.
+        let input = b"*2147483647\r\n:1\r\n";
+        let mut parser = RespHandler::new(BufReader::new(Cursor::new(Vec::from(input))));
+        assert!(parser.decode().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encode_error_stays_on_one_line() {
+        let err = RespDT::SimpleError("ERR unknown command 'a\r\nb\n'".to_string());
+        assert_eq!(
+            err.encode(Protocol::Resp2),
+            b"-ERR unknown command 'a  b '\r\n".to_vec()
+        );
+    }
 }
@@ -0,0 +1,467 @@
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, EntryValue, Keyspace, RespEntry},
+    notify::EventClass,
+    string::MAX_STRING_LEN,
+};
+
+/// One past the highest bit offset a string can hold.
+pub const MAX_BITS: u64 = MAX_STRING_LEN as u64 * 8;
+
+/// Whether BITCOUNT and BITPOS ranges count bytes or bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitUnit {
+    #[default]
+    Byte,
+    Bit,
+}
+
+/// A `start end` range as BITCOUNT and BITPOS take it. Negative indexes
+/// count from the end; a missing `end` means the last byte or bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitRange {
+    pub start: i64,
+    pub end: Option<i64>,
+    pub unit: BitUnit,
+}
+
+impl BitRange {
+    /// The range as absolute, inclusive bit offsets into a string of `len`
+    /// bytes, or `None` if it selects nothing.
+    fn resolve(&self, len: usize) -> Option<(u64, u64)> {
+        let total = match self.unit {
+            BitUnit::Byte => len as i64,
+            BitUnit::Bit => len as i64 * 8,
+        };
+        let fix = |idx: i64| {
+            if idx < 0 {
+                total.saturating_add(idx).max(0)
+            } else {
+                idx
+            }
+        };
+        let (start, end) = (fix(self.start), fix(self.end.unwrap_or(-1)).min(total - 1));
+        if start > end {
+            return None;
+        }
+        let (start, end) = (start as u64, end as u64);
+        Some(match self.unit {
+            BitUnit::Byte => (start * 8, end * 8 + 7),
+            BitUnit::Bit => (start, end),
+        })
+    }
+}
+
+/// The whole string, as BITCOUNT and BITPOS default to.
+impl Default for BitRange {
+    fn default() -> Self {
+        BitRange {
+            start: 0,
+            end: None,
+            unit: BitUnit::Byte,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+/// A BITFIELD type such as `i8` or `u63`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitFieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+/// What BITFIELD does when SET or INCRBY leaves the range of the type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitOverflow {
+    #[default]
+    Wrap,
+    Sat,
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitFieldOp {
+    Get {
+        ty: BitFieldType,
+        offset: u64,
+    },
+    Set {
+        ty: BitFieldType,
+        offset: u64,
+        value: i64,
+        overflow: BitOverflow,
+    },
+    IncrBy {
+        ty: BitFieldType,
+        offset: u64,
+        incr: i64,
+        overflow: BitOverflow,
+    },
+}
+
+impl BitFieldOp {
+    /// The bit just past the field, for the operations that write.
+    fn write_end(&self) -> Option<u64> {
+        match self {
+            BitFieldOp::Get { .. } => None,
+            BitFieldOp::Set { ty, offset, .. } | BitFieldOp::IncrBy { ty, offset, .. } => {
+                Some(offset + ty.bits as u64)
+            }
+        }
+    }
+}
+
+impl BitFieldType {
+    fn bounds(&self) -> (i128, i128) {
+        if self.signed {
+            (-(1i128 << (self.bits - 1)), (1i128 << (self.bits - 1)) - 1)
+        } else {
+            (0, (1i128 << self.bits) - 1)
+        }
+    }
+
+    /// Interprets the raw field bits, sign extending signed types.
+    fn decode(&self, raw: u64) -> i64 {
+        if self.signed && self.bits < 64 && raw >> (self.bits - 1) != 0 {
+            (raw | (u64::MAX << self.bits)) as i64
+        } else {
+            raw as i64
+        }
+    }
+
+    /// Brings `value` into the range of the type according to `overflow`,
+    /// or returns `None` if FAIL refuses it.
+    fn fit(&self, value: i128, overflow: BitOverflow) -> Option<i64> {
+        let (min, max) = self.bounds();
+        if (min..=max).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            BitOverflow::Fail => None,
+            BitOverflow::Sat => Some(if value > max { max } else { min } as i64),
+            BitOverflow::Wrap => {
+                let modulus = 1i128 << self.bits;
+                let wrapped = value.rem_euclid(modulus);
+                Some(if wrapped > max {
+                    wrapped - modulus
+                } else {
+                    wrapped
+                } as i64)
+            }
+        }
+    }
+}
+
+fn get_bit(bytes: &[u8], offset: u64) -> bool {
+    bytes
+        .get((offset / 8) as usize)
+        .is_some_and(|byte| byte & (0x80 >> (offset % 8)) != 0)
+}
+
+fn set_bit(bytes: &mut [u8], offset: u64, on: bool) {
+    let mask = 0x80 >> (offset % 8);
+    let byte = &mut bytes[(offset / 8) as usize];
+    if on {
+        *byte |= mask;
+    } else {
+        *byte &= !mask;
+    }
+}
+
+/// Reads `bits` bits starting at `offset`, most significant first. Bits
+/// past the end of the string read as zero.
+fn read_field(bytes: &[u8], offset: u64, bits: u32) -> u64 {
+    (0..bits as u64).fold(0, |acc, i| acc << 1 | get_bit(bytes, offset + i) as u64)
+}
+
+fn write_field(bytes: &mut [u8], offset: u64, bits: u32, value: u64) {
+    for i in 0..bits as u64 {
+        set_bit(bytes, offset + i, value >> (bits as u64 - 1 - i) & 1 != 0);
+    }
+}
+
+/// Counts the set bits in the inclusive bit range `lo..=hi`.
+fn count_bits(bytes: &[u8], lo: u64, hi: u64) -> u64 {
+    let (first, last) = ((lo / 8) as usize, (hi / 8) as usize);
+    bytes[first..=last]
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| {
+            let mut byte = byte;
+            if i == 0 {
+                byte &= 0xff >> (lo % 8);
+            }
+            if first + i == last {
+                byte &= 0xff << (7 - hi % 8);
+            }
+            byte.count_ones() as u64
+        })
+        .sum()
+}
+
+/// Finds the first bit equal to `bit` in the inclusive range `lo..=hi`,
+/// skipping whole bytes that cannot contain it.
+fn find_bit(bytes: &[u8], bit: bool, lo: u64, hi: u64) -> Option<u64> {
+    let skip = if bit { 0x00 } else { 0xff };
+    let mut pos = lo;
+    while pos <= hi {
+        if pos & 7 == 0 && pos + 7 <= hi && bytes[(pos / 8) as usize] == skip {
+            pos += 8;
+            continue;
+        }
+        if get_bit(bytes, pos) == bit {
+            return Some(pos);
+        }
+        pos += 1;
+    }
+    None
+}
+
+impl Keyspace {
+    /// SETBIT: returns the previous value of the bit, growing the string
+    /// with zero bytes as needed.
+    pub fn setbit(&mut self, key: &[u8], offset: u64, on: bool) -> Result<bool, DbError> {
+        let mut bytes = self.str_bytes(key)?.map(Vec::from).unwrap_or_default();
+        let needed = (offset / 8) as usize + 1;
+        if bytes.len() < needed {
+            bytes.resize(needed, 0);
+        }
+        let previous = get_bit(&bytes, offset);
+        set_bit(&mut bytes, offset, on);
+        self.overwrite_str(key, Bytes::from(bytes).into());
+        self.notify(EventClass::String, "setbit", key);
+        Ok(previous)
+    }
+
+    pub fn getbit(&mut self, key: &[u8], offset: u64) -> Result<bool, DbError> {
+        Ok(self
+            .str_bytes(key)?
+            .is_some_and(|bytes| get_bit(&bytes, offset)))
+    }
+
+    pub fn bitcount(&mut self, key: &[u8], range: BitRange) -> Result<u64, DbError> {
+        let bytes = self.str_bytes(key)?.unwrap_or_default();
+        Ok(range
+            .resolve(bytes.len())
+            .map_or(0, |(lo, hi)| count_bits(&bytes, lo, hi)))
+    }
+
+    /// BITPOS. Without an explicit end, a string of all ones is treated as
+    /// followed by clear bits, so looking for 0 returns the bit past it.
+    pub fn bitpos(&mut self, key: &[u8], bit: bool, range: BitRange) -> Result<i64, DbError> {
+        let Some(bytes) = self.str_bytes(key)? else {
+            return Ok(if bit { -1 } else { 0 });
+        };
+        let Some((lo, hi)) = range.resolve(bytes.len()) else {
+            return Ok(-1);
+        };
+        Ok(match find_bit(&bytes, bit, lo, hi) {
+            Some(pos) => pos as i64,
+            None if !bit && range.end.is_none() => hi as i64 + 1,
+            None => -1,
+        })
+    }
+
+    /// BITOP: stores the result at `destination`, or deletes it if every
+    /// source is empty, and returns the length of the result. Shorter
+    /// sources are padded with zero bytes.
+    pub fn bitop(
+        &mut self,
+        op: BitOp,
+        destination: &[u8],
+        keys: &[Bytes],
+    ) -> Result<usize, DbError> {
+        let mut sources = Vec::with_capacity(keys.len());
+        for key in keys {
+            sources.push(self.str_bytes(key)?.unwrap_or_default());
+        }
+        let len = sources.iter().map(Bytes::len).max().unwrap_or(0);
+        let result: Vec<u8> = (0..len)
+            .map(|i| {
+                let mut column = sources.iter().map(|s| s.get(i).copied().unwrap_or(0));
+                let first = column.next().unwrap_or(0);
+                match op {
+                    BitOp::And => column.fold(first, |acc, byte| acc & byte),
+                    BitOp::Or => column.fold(first, |acc, byte| acc | byte),
+                    BitOp::Xor => column.fold(first, |acc, byte| acc ^ byte),
+                    BitOp::Not => !first,
+                }
+            })
+            .collect();
+        if result.is_empty() {
+            self.delete(destination);
+        } else {
+            self.insert(
+                Bytes::copy_from_slice(destination),
+                RespEntry::new(EntryValue::Str(Bytes::from(result).into()), None),
+            );
+            self.notify(EventClass::String, "set", destination);
+        }
+        Ok(len)
+    }
+
+    /// Runs BITFIELD operations in order, returning one reply per
+    /// operation; `None` is an INCRBY or SET refused by OVERFLOW FAIL.
+    /// Like Redis, a command that writes grows the string up front to fit
+    /// every field it writes, even ones that end up refused.
+    pub fn bitfield(
+        &mut self,
+        key: &[u8],
+        ops: &[BitFieldOp],
+    ) -> Result<Vec<Option<i64>>, DbError> {
+        let current = self.str_bytes(key)?;
+        let write_end = ops.iter().filter_map(BitFieldOp::write_end).max();
+        let mut bytes = current.map(Vec::from).unwrap_or_default();
+        if let Some(end) = write_end {
+            let needed = end.div_ceil(8) as usize;
+            if bytes.len() < needed {
+                bytes.resize(needed, 0);
+            }
+        }
+        let mut replies = Vec::with_capacity(ops.len());
+        for op in ops {
+            let reply = match *op {
+                BitFieldOp::Get { ty, offset } => {
+                    Some(ty.decode(read_field(&bytes, offset, ty.bits)))
+                }
+                BitFieldOp::Set {
+                    ty,
+                    offset,
+                    value,
+                    overflow,
+                } => {
+                    let old = ty.decode(read_field(&bytes, offset, ty.bits));
+                    // Unsigned types take the value as Redis does, bit
+                    // pattern and all, so -1 saturates to the maximum.
+                    let value = if ty.signed {
+                        value as i128
+                    } else {
+                        value as u64 as i128
+                    };
+                    ty.fit(value, overflow).map(|new| {
+                        write_field(&mut bytes, offset, ty.bits, new as u64);
+                        old
+                    })
+                }
+                BitFieldOp::IncrBy {
+                    ty,
+                    offset,
+                    incr,
+                    overflow,
+                } => {
+                    let old = ty.decode(read_field(&bytes, offset, ty.bits));
+                    ty.fit(old as i128 + incr as i128, overflow).inspect(|new| {
+                        write_field(&mut bytes, offset, ty.bits, *new as u64);
+                    })
+                }
+            };
+            replies.push(reply);
+        }
+        if write_end.is_some() {
+            self.overwrite_str(key, Bytes::from(bytes).into());
+            self.notify(EventClass::String, "setbit", key);
+        }
+        Ok(replies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: i64, end: i64, unit: BitUnit) -> BitRange {
+        BitRange {
+            start,
+            end: Some(end),
+            unit,
+        }
+    }
+
+    #[test]
+    fn test_setbit_getbit_bitcount() {
+        let mut ks = Keyspace::default();
+        assert_eq!(ks.setbit(b"b", 7, true), Ok(false));
+        assert_eq!(ks.setbit(b"b", 7, true), Ok(true));
+        ks.setbit(b"b", 17, true).unwrap();
+        assert_eq!(ks.strlen(b"b"), Ok(3));
+        assert_eq!(ks.getbit(b"b", 17), Ok(true));
+        assert_eq!(ks.getbit(b"b", 1000), Ok(false));
+        assert_eq!(ks.bitcount(b"b", BitRange::default()), Ok(2));
+        assert_eq!(ks.bitcount(b"b", range(1, -1, BitUnit::Byte)), Ok(1));
+        assert_eq!(ks.bitcount(b"b", range(5, 17, BitUnit::Bit)), Ok(2));
+        assert_eq!(ks.bitcount(b"b", range(8, 16, BitUnit::Bit)), Ok(0));
+    }
+
+    #[test]
+    fn test_bitpos() {
+        let mut ks = Keyspace::default();
+        ks.set(
+            b"k",
+            Bytes::from_static(b"\xff\xf0\x00"),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(ks.bitpos(b"k", false, BitRange::default()), Ok(12));
+        assert_eq!(ks.bitpos(b"k", true, range(2, -1, BitUnit::Byte)), Ok(-1));
+        assert_eq!(ks.bitpos(b"k", true, range(3, 20, BitUnit::Bit)), Ok(3));
+        ks.set(b"ones", Bytes::from_static(b"\xff"), &Default::default())
+            .unwrap();
+        assert_eq!(ks.bitpos(b"ones", false, BitRange::default()), Ok(8));
+        assert_eq!(
+            ks.bitpos(b"ones", false, range(0, -1, BitUnit::Byte)),
+            Ok(-1)
+        );
+        assert_eq!(ks.bitpos(b"missing", false, BitRange::default()), Ok(0));
+    }
+
+    #[test]
+    fn test_bitfield_overflow() {
+        let mut ks = Keyspace::default();
+        let u8_ty = BitFieldType {
+            signed: false,
+            bits: 8,
+        };
+        let i4_ty = BitFieldType {
+            signed: true,
+            bits: 4,
+        };
+        let incr = |ty, incr, overflow| BitFieldOp::IncrBy {
+            ty,
+            offset: 0,
+            incr,
+            overflow,
+        };
+        let ops = [
+            incr(u8_ty, 250, BitOverflow::Wrap),
+            incr(u8_ty, 10, BitOverflow::Wrap),
+            incr(u8_ty, 300, BitOverflow::Sat),
+            incr(u8_ty, 1, BitOverflow::Fail),
+        ];
+        assert_eq!(
+            ks.bitfield(b"f", &ops),
+            Ok(vec![Some(250), Some(4), Some(255), None])
+        );
+        let ops = [
+            incr(i4_ty, 7, BitOverflow::Wrap),
+            incr(i4_ty, 1, BitOverflow::Wrap),
+            incr(i4_ty, -100, BitOverflow::Sat),
+            BitFieldOp::Get {
+                ty: u8_ty,
+                offset: 0,
+            },
+        ];
+        assert_eq!(
+            ks.bitfield(b"g", &ops),
+            Ok(vec![Some(7), Some(-8), Some(-8), Some(0x80)])
+        );
+    }
+}
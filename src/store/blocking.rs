@@ -0,0 +1,139 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use tokio::sync::Notify;
+
+#[derive(Debug)]
+struct Waiter {
+    id: u64,
+    notify: Arc<Notify>,
+    /// Consumes what it is woken for, so only the longest-waiting one is
+    /// woken at a time.
+    exclusive: bool,
+}
+
+/// Connections parked on keys by a blocking command, in arrival order.
+///
+/// Writes mark keys as ready while the lock is held; the waiters are only
+/// notified once the write has finished, so they never observe a command
+/// half-applied. Readers that merely observe a key (XREAD) are all woken,
+/// while consuming ones (BLPOP and friends) are woken one at a time in FIFO
+/// order: each passes the turn on once it is done.
+#[derive(Debug, Default)]
+pub struct BlockedClients {
+    next_id: u64,
+    waiters: HashMap<Bytes, VecDeque<Waiter>>,
+    ready: Vec<Bytes>,
+}
+
+impl BlockedClients {
+    /// Parks a new waiter on every key in `keys`. The returned id is used to
+    /// unregister it once it is served or times out.
+    pub fn register(&mut self, keys: &[Bytes], exclusive: bool) -> (u64, Arc<Notify>) {
+        self.next_id += 1;
+        let id = self.next_id;
+        let notify = Arc::new(Notify::new());
+        for key in keys {
+            self.waiters
+                .entry(key.clone())
+                .or_default()
+                .push_back(Waiter {
+                    id,
+                    notify: notify.clone(),
+                    exclusive,
+                });
+        }
+        (id, notify)
+    }
+
+    pub fn unregister(&mut self, id: u64, keys: &[Bytes]) {
+        for key in keys {
+            if let Some(queue) = self.waiters.get_mut(key) {
+                queue.retain(|w| w.id != id);
+                if queue.is_empty() {
+                    self.waiters.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Records that `key` may now satisfy someone blocked on it.
+    pub fn signal(&mut self, key: &[u8]) {
+        if self.waiters.contains_key(key) && !self.ready.iter().any(|k| k[..] == *key) {
+            self.ready.push(Bytes::copy_from_slice(key));
+        }
+    }
+
+    /// Notifies the waiters of every key signalled since the last call.
+    /// A woken waiter re-checks its condition itself and keeps its place in
+    /// the queue if there turns out to be nothing for it.
+    pub fn wake_ready(&mut self) {
+        for key in self.ready.drain(..) {
+            let mut exclusive_woken = false;
+            for waiter in self.waiters.get(&key).into_iter().flatten() {
+                if waiter.exclusive {
+                    if exclusive_woken {
+                        continue;
+                    }
+                    exclusive_woken = true;
+                }
+                waiter.notify.notify_one();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_signal_wakes_waiter() {
+        let mut blocked = BlockedClients::default();
+        let keys = vec![Bytes::from("a"), Bytes::from("b")];
+        let (id, notify) = blocked.register(&keys, false);
+        blocked.signal(b"zzz");
+        blocked.signal(b"b");
+        blocked.wake_ready();
+        tokio::time::timeout(Duration::from_secs(1), notify.notified())
+            .await
+            .expect("waiter should have been notified");
+        blocked.unregister(id, &keys);
+        assert!(blocked.waiters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_exclusive_waiters_woken_in_order() {
+        let mut blocked = BlockedClients::default();
+        let keys = vec![Bytes::from("q")];
+        let (first, first_notify) = blocked.register(&keys, true);
+        let (_, second_notify) = blocked.register(&keys, true);
+        blocked.signal(b"q");
+        blocked.wake_ready();
+        let wait = Duration::from_millis(50);
+        assert!(tokio::time::timeout(wait, first_notify.notified())
+            .await
+            .is_ok());
+        assert!(tokio::time::timeout(wait, second_notify.notified())
+            .await
+            .is_err());
+        blocked.unregister(first, &keys);
+        blocked.signal(b"q");
+        blocked.wake_ready();
+        assert!(tokio::time::timeout(wait, second_notify.notified())
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_signal_ignores_keys_without_waiters() {
+        let mut blocked = BlockedClients::default();
+        blocked.signal(b"a");
+        assert!(blocked.ready.is_empty());
+    }
+}
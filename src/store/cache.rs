@@ -1,61 +1,483 @@
-use std::{collections::HashMap, time::SystemTime};
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
 
-use tokio::sync::Mutex;
+use bytes::Bytes;
+use thiserror::Error;
+use tokio::{sync::Mutex, time::Instant};
 
-pub type Cache = Mutex<HashMap<String, RespEntry>>;
+use super::{
+    blocking::BlockedClients,
+    expire::{ExpireStats, VolatileKeys},
+    hash::HashValue,
+    notify::{EventClass, Notifier},
+    pubsub::Broker,
+    scan::ScanIndex,
+    set::SetValue,
+    stream::Stream,
+    string::StrValue,
+    watch::WatchedKeys,
+    zset::SortedSet,
+};
 
-#[derive(Debug, Default)]
+pub type Cache = Mutex<Keyspace>;
+
+#[derive(Debug)]
 pub struct Db {
     pub cache: Cache,
+    /// Shared with the key space, which publishes keyspace events on it.
+    pub pubsub: Arc<Broker>,
+    /// The last connection id handed out.
+    client_ids: AtomicU64,
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum DbError {
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("ERR no such key")]
+    NoSuchKey,
+    #[error("ERR index out of range")]
+    IndexOutOfRange,
+    #[error("ERR hash value is not an integer")]
+    HashValueNotInteger,
+    #[error("ERR hash value is not a float")]
+    HashValueNotFloat,
+    #[error("ERR increment or decrement would overflow")]
+    Overflow,
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+    #[error("ERR string exceeds maximum allowed size (proto-max-bulk-len)")]
+    StringTooLong,
+    #[error("ERR The specified keys must contain string values")]
+    LcsNotString,
+    #[error("ERR increment would produce NaN or Infinity")]
+    NanOrInfinity,
+    #[error("ERR resulting score is not a number (NaN)")]
+    ScoreNaN,
+    #[error("ERR The ID specified in XADD is equal or smaller than the target stream top item")]
+    StreamIdTooSmall,
+    #[error("ERR The ID specified in XADD must be greater than 0-0")]
+    StreamIdZero,
+    #[error("BUSYGROUP Consumer Group name already exists")]
+    BusyGroup,
+    #[error("NOGROUP No such key '{0}' or consumer group '{1}'")]
+    NoGroup(String, String),
+    #[error("NOGROUP No such key '{0}' or consumer group '{1}' in XREADGROUP with GROUP option")]
+    NoGroupRead(String, String),
+    #[error("NOGROUP No such consumer group '{1}' for key name '{0}'")]
+    NoGroupForKey(String, String),
+    #[error("ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.")]
+    XGroupKeyMissing,
+    #[error("WRONGTYPE Key is not a valid HyperLogLog string value.")]
+    InvalidHll,
+    #[error("INVALIDOBJ Corrupted HLL object detected")]
+    CorruptHll,
+    #[error("ERR could not decode requested zset member")]
+    GeoMemberMissing,
+}
+
+#[derive(Debug, Clone)]
+pub enum EntryValue {
+    Str(StrValue),
+    List(VecDeque<Bytes>),
+    Hash(HashValue),
+    Set(SetValue),
+    ZSet(SortedSet),
+    Stream(Stream),
 }
 
 #[derive(Debug, Clone)]
 pub struct RespEntry {
-    pub value: String,
+    pub value: EntryValue,
     pub expiry: Option<SystemTime>,
 }
 
 impl RespEntry {
-    pub fn new(value: String, expiry: Option<SystemTime>) -> Self {
+    pub fn new(value: EntryValue, expiry: Option<SystemTime>) -> Self {
         RespEntry { value, expiry }
     }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        matches!(self.expiry, Some(expiry) if expiry <= now)
+    }
+}
+
+/// The key space guarded by the `Db` lock. Every command runs against it
+/// synchronously, so a caller holding the lock sees a consistent view.
+#[derive(Debug, Default)]
+pub struct Keyspace {
+    entries: HashMap<Bytes, RespEntry>,
+    /// The keys in SCAN order.
+    pub(super) scan_index: ScanIndex,
+    pub(super) volatile: VolatileKeys,
+    pub blocked: BlockedClients,
+    pub watched: WatchedKeys,
+    pub notifier: Notifier,
+    pub expire_stats: ExpireStats,
+}
+
+impl Keyspace {
+    /// Looks up a live entry, evicting it first if its TTL has passed.
+    pub fn get(&mut self, key: &[u8]) -> Option<&RespEntry> {
+        self.expire_if_needed(key);
+        self.entries.get(key)
+    }
+
+    /// Mutable access to a live entry. It is not a write by itself: callers
+    /// touch the key's watchers once they know they changed something, so
+    /// a removal that finds nothing leaves transactions alone.
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut RespEntry> {
+        self.expire_if_needed(key);
+        self.entries.get_mut(key)
+    }
+
+    pub fn insert(&mut self, key: Bytes, entry: RespEntry) {
+        self.expire_if_needed(&key);
+        if !self.entries.contains_key(&key) {
+            self.scan_index.insert(key.clone());
+            self.notify(EventClass::New, "new", &key);
+        }
+        if entry.expiry.is_some() {
+            self.volatile.add(&key);
+        }
+        self.watched.touch(&key);
+        self.entries.insert(key, entry);
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<RespEntry> {
+        self.expire_if_needed(key);
+        let removed = self.entries.remove(key);
+        if removed.is_some() {
+            self.scan_index.remove(key);
+            self.watched.touch(key);
+        }
+        removed
+    }
+
+    pub fn get_str(&mut self, key: &[u8]) -> Result<Option<&StrValue>, DbError> {
+        match self.get(key) {
+            Some(RespEntry {
+                value: EntryValue::Str(s),
+                ..
+            }) => Ok(Some(s)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_list(&mut self, key: &[u8]) -> Result<Option<&VecDeque<Bytes>>, DbError> {
+        match self.get(key) {
+            Some(RespEntry {
+                value: EntryValue::List(l),
+                ..
+            }) => Ok(Some(l)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_list_mut(&mut self, key: &[u8]) -> Result<Option<&mut VecDeque<Bytes>>, DbError> {
+        match self.get_mut(key) {
+            Some(RespEntry {
+                value: EntryValue::List(l),
+                ..
+            }) => Ok(Some(l)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the list at `key`, creating an empty one if the key is absent.
+    pub fn list_entry(&mut self, key: &[u8]) -> Result<&mut VecDeque<Bytes>, DbError> {
+        match self.entry_value(key, EntryValue::List(VecDeque::new()))? {
+            EntryValue::List(l) => Ok(l),
+            _ => Err(DbError::WrongType),
+        }
+    }
+
+    pub fn get_hash(&mut self, key: &[u8]) -> Result<Option<&HashValue>, DbError> {
+        match self.get(key) {
+            Some(RespEntry {
+                value: EntryValue::Hash(h),
+                ..
+            }) => Ok(Some(h)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_hash_mut(&mut self, key: &[u8]) -> Result<Option<&mut HashValue>, DbError> {
+        match self.get_mut(key) {
+            Some(RespEntry {
+                value: EntryValue::Hash(h),
+                ..
+            }) => Ok(Some(h)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the hash at `key`, creating an empty one if the key is absent.
+    pub fn hash_entry(&mut self, key: &[u8]) -> Result<&mut HashValue, DbError> {
+        match self.entry_value(key, EntryValue::Hash(HashValue::default()))? {
+            EntryValue::Hash(h) => Ok(h),
+            _ => Err(DbError::WrongType),
+        }
+    }
+
+    pub fn get_set(&mut self, key: &[u8]) -> Result<Option<&SetValue>, DbError> {
+        match self.get(key) {
+            Some(RespEntry {
+                value: EntryValue::Set(s),
+                ..
+            }) => Ok(Some(s)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_set_mut(&mut self, key: &[u8]) -> Result<Option<&mut SetValue>, DbError> {
+        match self.get_mut(key) {
+            Some(RespEntry {
+                value: EntryValue::Set(s),
+                ..
+            }) => Ok(Some(s)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the set at `key`, creating an empty one if the key is absent.
+    pub fn set_entry(&mut self, key: &[u8]) -> Result<&mut SetValue, DbError> {
+        match self.entry_value(key, EntryValue::Set(SetValue::default()))? {
+            EntryValue::Set(s) => Ok(s),
+            _ => Err(DbError::WrongType),
+        }
+    }
+
+    pub fn get_zset(&mut self, key: &[u8]) -> Result<Option<&SortedSet>, DbError> {
+        match self.get(key) {
+            Some(RespEntry {
+                value: EntryValue::ZSet(z),
+                ..
+            }) => Ok(Some(z)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_zset_mut(&mut self, key: &[u8]) -> Result<Option<&mut SortedSet>, DbError> {
+        match self.get_mut(key) {
+            Some(RespEntry {
+                value: EntryValue::ZSet(z),
+                ..
+            }) => Ok(Some(z)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the sorted set at `key`, creating an empty one if the key is
+    /// absent.
+    pub fn zset_entry(&mut self, key: &[u8]) -> Result<&mut SortedSet, DbError> {
+        match self.entry_value(key, EntryValue::ZSet(SortedSet::new()))? {
+            EntryValue::ZSet(z) => Ok(z),
+            _ => Err(DbError::WrongType),
+        }
+    }
+
+    pub fn get_stream(&mut self, key: &[u8]) -> Result<Option<&Stream>, DbError> {
+        match self.get(key) {
+            Some(RespEntry {
+                value: EntryValue::Stream(s),
+                ..
+            }) => Ok(Some(s)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_stream_mut(&mut self, key: &[u8]) -> Result<Option<&mut Stream>, DbError> {
+        match self.get_mut(key) {
+            Some(RespEntry {
+                value: EntryValue::Stream(s),
+                ..
+            }) => Ok(Some(s)),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the stream at `key`, creating an empty one if the key is
+    /// absent. Streams, unlike other aggregates, survive being emptied.
+    pub fn stream_entry(&mut self, key: &[u8]) -> Result<&mut Stream, DbError> {
+        match self.entry_value(key, EntryValue::Stream(Stream::default()))? {
+            EntryValue::Stream(s) => Ok(s),
+            _ => Err(DbError::WrongType),
+        }
+    }
+
+    /// Every key whose TTL has not passed, in no particular order.
+    pub fn live_keys(&self) -> impl Iterator<Item = &Bytes> {
+        let now = SystemTime::now();
+        self.entries
+            .iter()
+            .filter(move |(_, entry)| !entry.is_expired(now))
+            .map(|(key, _)| key)
+    }
+
+    /// Number of keys, counting expired ones not evicted yet as DBSIZE does.
+    pub fn key_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// FLUSHDB. Watchers of the keys that were there see them as deleted.
+    pub fn flush(&mut self) {
+        let entries = std::mem::take(&mut self.entries);
+        self.watched.touch_all(|key| entries.contains_key(key));
+        self.scan_index = ScanIndex::default();
+        self.volatile = VolatileKeys::default();
+    }
+
+    /// Drops the key if it holds an empty collection, as Redis never keeps
+    /// empty aggregate values around.
+    pub fn remove_if_empty(&mut self, key: &[u8]) {
+        let empty = match self.entries.get(key).map(|e| &e.value) {
+            Some(EntryValue::List(l)) => l.is_empty(),
+            Some(EntryValue::Hash(h)) => h.is_empty(),
+            Some(EntryValue::Set(s)) => s.is_empty(),
+            Some(EntryValue::ZSet(z)) => z.is_empty(),
+            _ => false,
+        };
+        if empty {
+            self.entries.remove(key);
+            self.scan_index.remove(key);
+            self.notify(EventClass::Generic, "del", key);
+        }
+    }
+
+    /// The value at `key` for one of the `*_entry` accessors, created from
+    /// `empty` if the key is missing. A key holding another type fails with
+    /// WRONGTYPE before anything happens, so it neither dooms the
+    /// transactions watching it nor raises a "new" event.
+    fn entry_value(&mut self, key: &[u8], empty: EntryValue) -> Result<&mut EntryValue, DbError> {
+        self.expire_if_needed(key);
+        match self.entries.get(key) {
+            Some(entry) if mem::discriminant(&entry.value) != mem::discriminant(&empty) => {
+                return Err(DbError::WrongType);
+            }
+            Some(_) => {}
+            None => {
+                let owned = Bytes::copy_from_slice(key);
+                self.entries
+                    .insert(owned.clone(), RespEntry::new(empty, None));
+                self.scan_index.insert(owned);
+                self.notify(EventClass::New, "new", key);
+            }
+        }
+        self.watched.touch(key);
+        Ok(&mut self
+            .entries
+            .get_mut(key)
+            .expect("present or just created")
+            .value)
+    }
+
+    /// Evicts the key if its TTL has passed, returning whether it did.
+    pub fn expire_if_needed(&mut self, key: &[u8]) -> bool {
+        let expired = self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(SystemTime::now()));
+        if expired {
+            self.entries.remove(key);
+            self.scan_index.remove(key);
+            self.volatile.remove(key);
+            self.watched.touch(key);
+            self.expire_stats.expired_keys += 1;
+            self.notify(EventClass::Expired, "expired", key);
+        }
+        expired
+    }
+}
+
+impl Default for Db {
+    fn default() -> Self {
+        Db::new()
+    }
 }
 
 impl Db {
     pub fn new() -> Self {
+        let pubsub = Arc::new(Broker::default());
+        let keyspace = Keyspace {
+            notifier: Notifier::new(pubsub.clone()),
+            ..Default::default()
+        };
         Db {
-            cache: Default::default(),
+            cache: Mutex::new(keyspace),
+            pubsub,
+            client_ids: AtomicU64::new(0),
         }
     }
 
-    pub async fn store(&self, key: String, val: String, expiry: Option<SystemTime>) {
-        self.cache
-            .lock()
-            .await
-            .insert(key, RespEntry::new(val, expiry));
+    /// A new connection's id, unique for the life of the server.
+    pub fn new_client_id(&self) -> u64 {
+        self.client_ids.fetch_add(1, Ordering::Relaxed) + 1
     }
 
-    pub async fn fetch(&self, key: String) -> Option<String> {
-        let entry = {
-            let cache = self.cache.lock().await;
-            cache.get(&key).cloned()
+    /// Runs `attempt` under the lock until it produces a reply, parking on
+    /// `keys` in between until one of them is signalled. Gives up with
+    /// `Ok(None)` once `timeout` elapses; a zero timeout waits forever.
+    /// `exclusive` waiters are served in arrival order, see `BlockedClients`.
+    pub async fn block_on<T>(
+        &self,
+        keys: &[Bytes],
+        timeout: Duration,
+        exclusive: bool,
+        mut attempt: impl FnMut(&mut Keyspace) -> Result<Option<T>, DbError>,
+    ) -> Result<Option<T>, DbError> {
+        let deadline = (!timeout.is_zero()).then(|| Instant::now() + timeout);
+        let (id, notify) = {
+            let mut ks = self.cache.lock().await;
+            if let Some(reply) = attempt(&mut ks)? {
+                ks.blocked.wake_ready();
+                return Ok(Some(reply));
+            }
+            ks.blocked.register(keys, exclusive)
         };
-
-        match entry {
-            Some(entry) => {
-                if let Some(expiry) = entry.expiry {
-                    if expiry < SystemTime::now() {
-                        self.invalidate(&key).await;
-                        return None;
-                    }
+        loop {
+            let woken = match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline, notify.notified())
+                    .await
+                    .is_ok(),
+                None => {
+                    notify.notified().await;
+                    true
                 }
-                Some(entry.value)
+            };
+            let mut ks = self.cache.lock().await;
+            let outcome = if woken { attempt(&mut ks) } else { Ok(None) };
+            if woken && matches!(outcome, Ok(None)) {
+                continue;
             }
-            None => None,
+            ks.blocked.unregister(id, keys);
+            // Whatever this waiter left behind, or a wake-up it raced with
+            // its timeout, may serve the next one in line.
+            for key in keys {
+                ks.blocked.signal(key);
+            }
+            ks.blocked.wake_ready();
+            return outcome;
         }
     }
-
-    async fn invalidate(&self, key: &String) {
-        self.cache.lock().await.remove(key);
-    }
 }
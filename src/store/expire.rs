@@ -0,0 +1,302 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+
+use super::{
+    cache::{Db, Keyspace},
+    notify::EventClass,
+    random,
+};
+
+/// How often the active expiry cycle runs.
+const CYCLE_PERIOD: Duration = Duration::from_millis(100);
+/// Share of each period, in percent, a cycle may hold the lock for.
+const CYCLE_TIME_PERC: u32 = 25;
+/// Keys sampled per round of a cycle.
+const KEYS_PER_ROUND: usize = 20;
+/// A cycle keeps sampling while more than this percentage of a round turned
+/// out to be expired, as there are likely many more.
+const ACCEPTABLE_STALE_PERC: usize = 10;
+
+/// Milliseconds since the Unix epoch; negative before it.
+pub fn unix_ms(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_millis() as i64,
+        Err(before) => -(before.duration().as_millis() as i64),
+    }
+}
+
+pub fn from_unix_ms(ms: i64) -> SystemTime {
+    if ms >= 0 {
+        UNIX_EPOCH + Duration::from_millis(ms as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_millis(ms.unsigned_abs())
+    }
+}
+
+/// The NX / XX / GT / LT flags of the EXPIRE family. A key without a TTL
+/// counts as expiring infinitely far in the future.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExpireCondition {
+    pub nx: bool,
+    pub xx: bool,
+    pub gt: bool,
+    pub lt: bool,
+}
+
+impl ExpireCondition {
+    fn allows(&self, current: Option<SystemTime>, new: SystemTime) -> bool {
+        match current {
+            None => !self.xx && !self.gt,
+            Some(current) => !self.nx && (!self.gt || new > current) && (!self.lt || new < current),
+        }
+    }
+}
+
+/// Keys that were given a TTL at some point, kept in a vector so a random
+/// one can be picked in constant time. It may still hold keys that have
+/// since been persisted, overwritten or deleted; sampling prunes those.
+#[derive(Debug, Default)]
+pub struct VolatileKeys {
+    keys: Vec<Bytes>,
+    slots: HashMap<Bytes, usize>,
+}
+
+impl VolatileKeys {
+    pub fn add(&mut self, key: &[u8]) {
+        if !self.slots.contains_key(key) {
+            self.slots
+                .insert(Bytes::copy_from_slice(key), self.keys.len());
+            self.keys.push(Bytes::copy_from_slice(key));
+        }
+    }
+
+    pub fn remove(&mut self, key: &[u8]) {
+        let Some(slot) = self.slots.remove(key) else {
+            return;
+        };
+        self.keys.swap_remove(slot);
+        if let Some(moved) = self.keys.get(slot) {
+            self.slots.insert(moved.clone(), slot);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn random(&self) -> Option<&Bytes> {
+        match self.keys.len() {
+            0 => None,
+            n => self.keys.get(random::below(n)),
+        }
+    }
+}
+
+/// Counters reported by INFO under `# Stats`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExpireStats {
+    /// Keys removed because their TTL passed, whether found by a read or by
+    /// the active cycle.
+    pub expired_keys: u64,
+    /// Running estimate of the share of volatile keys that are already
+    /// expired, from the samples of recent cycles.
+    pub stale_perc: f64,
+    /// Cycles cut short because they ran out of time.
+    pub time_cap_reached_count: u64,
+    pub cycle_time: Duration,
+}
+
+/// Remaining lifetime of a key, as TTL and friends report it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyTtl {
+    Missing,
+    Persistent,
+    ExpiresAt(SystemTime),
+}
+
+impl Keyspace {
+    /// Sets the key to expire at `deadline` if `condition` allows it. A
+    /// deadline already in the past deletes the key straight away.
+    pub fn expire_at(
+        &mut self,
+        key: &[u8],
+        deadline: SystemTime,
+        condition: ExpireCondition,
+    ) -> bool {
+        let Some(entry) = self.get_mut(key) else {
+            return false;
+        };
+        if !condition.allows(entry.expiry, deadline) {
+            return false;
+        }
+        if deadline <= SystemTime::now() {
+            self.delete(key);
+        } else {
+            entry.expiry = Some(deadline);
+            self.volatile.add(key);
+            self.watched.touch(key);
+            self.notify(EventClass::Generic, "expire", key);
+        }
+        true
+    }
+
+    pub fn ttl(&mut self, key: &[u8]) -> KeyTtl {
+        match self.get(key) {
+            None => KeyTtl::Missing,
+            Some(entry) => entry.expiry.map_or(KeyTtl::Persistent, KeyTtl::ExpiresAt),
+        }
+    }
+
+    /// Removes the key's TTL, returning whether it had one.
+    pub fn persist(&mut self, key: &[u8]) -> bool {
+        let persisted = self
+            .get_mut(key)
+            .and_then(|entry| entry.expiry.take())
+            .is_some();
+        if persisted {
+            self.watched.touch(key);
+            self.notify(EventClass::Generic, "persist", key);
+        }
+        persisted
+    }
+
+    /// One pass of active expiry, in the manner of Redis: sample random keys
+    /// with a TTL, evict the expired ones, and go for another round while a
+    /// large share of the sample was expired, until `budget` is spent.
+    /// Returns how many keys were evicted.
+    pub fn active_expire_cycle(&mut self, budget: Duration) -> usize {
+        let start = Instant::now();
+        let (mut sampled, mut expired) = (0, 0);
+        while self.volatile.len() > 0 {
+            let (mut round_sampled, mut round_expired) = (0, 0);
+            for _ in 0..KEYS_PER_ROUND.min(self.volatile.len()) {
+                let Some(key) = self.volatile.random().cloned() else {
+                    break;
+                };
+                round_sampled += 1;
+                if self.expire_if_needed(&key) {
+                    round_expired += 1;
+                } else if self.get(&key).and_then(|entry| entry.expiry).is_none() {
+                    self.volatile.remove(&key);
+                }
+            }
+            sampled += round_sampled;
+            expired += round_expired;
+            if start.elapsed() >= budget {
+                self.expire_stats.time_cap_reached_count += 1;
+                break;
+            }
+            if round_expired * 100 <= round_sampled * ACCEPTABLE_STALE_PERC {
+                break;
+            }
+        }
+        let stats = &mut self.expire_stats;
+        if sampled > 0 {
+            let stale = expired as f64 / sampled as f64;
+            stats.stale_perc = stale * 0.05 + stats.stale_perc * 0.95;
+        }
+        stats.cycle_time += start.elapsed();
+        expired
+    }
+}
+
+impl Db {
+    /// Evicts expired keys nobody reads any more. Runs forever, so it is
+    /// meant to be spawned next to the listener.
+    pub async fn active_expire(&self) {
+        let budget = CYCLE_PERIOD * CYCLE_TIME_PERC / 100;
+        let mut ticker = tokio::time::interval(CYCLE_PERIOD);
+        loop {
+            ticker.tick().await;
+            self.cache.lock().await.active_expire_cycle(budget);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::string::{SetExpiry, SetOptions};
+
+    fn in_secs(secs: u64) -> SystemTime {
+        SystemTime::now() + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut ks = Keyspace::default();
+        ks.set(b"k", "v".into(), &SetOptions::default()).unwrap();
+        let nx = ExpireCondition {
+            nx: true,
+            ..Default::default()
+        };
+        let gt = ExpireCondition {
+            gt: true,
+            ..Default::default()
+        };
+        let lt = ExpireCondition {
+            lt: true,
+            ..Default::default()
+        };
+        assert!(!ks.expire_at(b"k", in_secs(100), gt));
+        assert!(ks.expire_at(b"k", in_secs(100), lt));
+        assert!(!ks.expire_at(b"k", in_secs(50), nx));
+        assert!(!ks.expire_at(b"k", in_secs(50), gt));
+        assert!(ks.expire_at(b"k", in_secs(200), gt));
+        assert!(!ks.expire_at(b"missing", in_secs(10), ExpireCondition::default()));
+    }
+
+    #[test]
+    fn test_past_deadline_deletes() {
+        let mut ks = Keyspace::default();
+        ks.set(b"k", "v".into(), &SetOptions::default()).unwrap();
+        assert!(ks.expire_at(b"k", from_unix_ms(-5000), ExpireCondition::default()));
+        assert_eq!(ks.ttl(b"k"), KeyTtl::Missing);
+    }
+
+    #[test]
+    fn test_ttl_and_persist() {
+        let mut ks = Keyspace::default();
+        ks.set(b"k", "v".into(), &SetOptions::default()).unwrap();
+        assert_eq!(ks.ttl(b"k"), KeyTtl::Persistent);
+        assert!(!ks.persist(b"k"));
+        let deadline = in_secs(30);
+        ks.expire_at(b"k", deadline, ExpireCondition::default());
+        assert_eq!(ks.ttl(b"k"), KeyTtl::ExpiresAt(deadline));
+        assert!(ks.persist(b"k"));
+        assert_eq!(ks.ttl(b"k"), KeyTtl::Persistent);
+        assert_eq!(unix_ms(from_unix_ms(-1234)), -1234);
+    }
+
+    #[test]
+    fn test_active_expire_cycle() {
+        let mut ks = Keyspace::default();
+        let px = |ms| SetOptions {
+            expiry: SetExpiry::After(Duration::from_millis(ms)),
+            ..Default::default()
+        };
+        for i in 0..50 {
+            ks.set(format!("k{i}").as_bytes(), "v".into(), &px(1))
+                .unwrap();
+        }
+        ks.set(b"later", "v".into(), &px(100_000)).unwrap();
+        ks.set(b"persisted", "v".into(), &px(1)).unwrap();
+        ks.persist(b"persisted");
+        std::thread::sleep(Duration::from_millis(5));
+        let mut expired = 0;
+        for _ in 0..1000 {
+            if ks.volatile.len() == 1 {
+                break;
+            }
+            expired += ks.active_expire_cycle(Duration::from_secs(1));
+        }
+        assert_eq!(expired, 50);
+        assert_eq!(ks.expire_stats.expired_keys, 50);
+        assert!(ks.expire_stats.stale_perc > 0.0);
+        assert!(ks.get(b"later").is_some() && ks.get(b"persisted").is_some());
+    }
+}
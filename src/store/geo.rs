@@ -0,0 +1,287 @@
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, EntryValue, Keyspace, RespEntry},
+    geohash::{self, GeoShape},
+    notify::EventClass,
+    zset::{ScoreRange, SortedSet},
+};
+
+/// Where a GEOSEARCH is centred.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoOrigin {
+    Member(Bytes),
+    LonLat(f64, f64),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GeoSort {
+    #[default]
+    None,
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoQuery {
+    pub origin: GeoOrigin,
+    pub shape: GeoShape,
+    pub sort: GeoSort,
+    /// COUNT, and whether ANY lets the search stop at the first matches
+    /// found rather than the closest ones.
+    pub count: Option<(usize, bool)>,
+}
+
+/// A member found by a search, with its distance from the centre in meters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoMatch {
+    pub member: Bytes,
+    pub distance: f64,
+    pub hash: u64,
+    pub lon: f64,
+    pub lat: f64,
+}
+
+impl SortedSet {
+    fn geo_position(&self, member: &[u8]) -> Option<(f64, f64)> {
+        self.score(member)
+            .map(|score| geohash::decode(score as u64))
+    }
+
+    /// Scans the cells covering the query's shape, keeping the members
+    /// actually inside it. With COUNT ANY the scan stops once enough are
+    /// found.
+    fn geo_search(&self, center: (f64, f64), query: &GeoQuery) -> Vec<GeoMatch> {
+        let limit = match query.count {
+            Some((count, true)) => count,
+            _ => usize::MAX,
+        };
+        let mut matches = Vec::new();
+        'cells: for (min, max) in geohash::search_ranges(center, &query.shape) {
+            let range = ScoreRange {
+                min: min as f64,
+                max: max as f64,
+                min_exclusive: false,
+                max_exclusive: true,
+            };
+            for (member, score) in self.range_by_score(&range, false, None) {
+                let hash = score as u64;
+                let (lon, lat) = geohash::decode(hash);
+                if let Some(distance) = query.shape.contains(center, lon, lat) {
+                    matches.push(GeoMatch {
+                        member,
+                        distance,
+                        hash,
+                        lon,
+                        lat,
+                    });
+                    if matches.len() >= limit {
+                        break 'cells;
+                    }
+                }
+            }
+        }
+        // COUNT without ANY wants the closest members, so it implies ASC.
+        let sort = match (query.sort, query.count) {
+            (GeoSort::None, Some((_, false))) => GeoSort::Asc,
+            (sort, _) => sort,
+        };
+        match sort {
+            GeoSort::None => {}
+            GeoSort::Asc => matches.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+            GeoSort::Desc => matches.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+        }
+        if let Some((count, _)) = query.count {
+            matches.truncate(count);
+        }
+        matches
+    }
+}
+
+impl Keyspace {
+    /// GEOPOS: the `(lon, lat)` of each member, decoded from its score.
+    pub fn geopos(
+        &mut self,
+        key: &[u8],
+        members: &[Bytes],
+    ) -> Result<Vec<Option<(f64, f64)>>, DbError> {
+        let zset = self.get_zset(key)?;
+        Ok(members
+            .iter()
+            .map(|member| zset.and_then(|z| z.geo_position(member)))
+            .collect())
+    }
+
+    /// GEODIST in meters, or `None` if either member is missing.
+    pub fn geodist(&mut self, key: &[u8], a: &[u8], b: &[u8]) -> Result<Option<f64>, DbError> {
+        let Some(zset) = self.get_zset(key)? else {
+            return Ok(None);
+        };
+        Ok(zset
+            .geo_position(a)
+            .zip(zset.geo_position(b))
+            .map(|((lon1, lat1), (lon2, lat2))| geohash::distance(lon1, lat1, lon2, lat2)))
+    }
+
+    pub fn geohash(
+        &mut self,
+        key: &[u8],
+        members: &[Bytes],
+    ) -> Result<Vec<Option<String>>, DbError> {
+        let zset = self.get_zset(key)?;
+        Ok(members
+            .iter()
+            .map(|member| {
+                zset.and_then(|z| z.score(member))
+                    .map(|score| geohash::to_string(score as u64))
+            })
+            .collect())
+    }
+
+    /// GEOSEARCH. A missing key yields no matches, but a FROMMEMBER member
+    /// missing from an existing key is an error.
+    pub fn geosearch(&mut self, key: &[u8], query: &GeoQuery) -> Result<Vec<GeoMatch>, DbError> {
+        let Some(zset) = self.get_zset(key)? else {
+            return Ok(vec![]);
+        };
+        let center = match &query.origin {
+            GeoOrigin::LonLat(lon, lat) => (*lon, *lat),
+            GeoOrigin::Member(member) => {
+                zset.geo_position(member).ok_or(DbError::GeoMemberMissing)?
+            }
+        };
+        Ok(zset.geo_search(center, query))
+    }
+
+    /// GEOSEARCHSTORE: stores the matches at `destination`, scored by their
+    /// geohash or, with `store_dist`, by their distance in units of
+    /// `unit` meters. Returns how many were stored.
+    pub fn geosearchstore(
+        &mut self,
+        destination: &[u8],
+        source: &[u8],
+        query: &GeoQuery,
+        store_dist: Option<f64>,
+    ) -> Result<usize, DbError> {
+        let matches = self.geosearch(source, query)?;
+        let mut result = SortedSet::new();
+        for found in &matches {
+            let score = match store_dist {
+                Some(unit) => found.distance / unit,
+                None => found.hash as f64,
+            };
+            result.insert(&found.member, score);
+        }
+        if result.is_empty() {
+            self.delete(destination);
+        } else {
+            self.insert(
+                Bytes::copy_from_slice(destination),
+                RespEntry::new(EntryValue::ZSet(result), None),
+            );
+            self.notify(EventClass::ZSet, "geosearchstore", destination);
+        }
+        Ok(matches.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{geohash::GEO_STEP_MAX, zset::ZAddFlags};
+
+    fn sicily() -> Keyspace {
+        let mut ks = Keyspace::default();
+        let places = [
+            (13.361389, 38.115556, "Palermo"),
+            (15.087269, 37.502669, "Catania"),
+            (12.758489, 38.788135, "edge1"),
+            (17.241510, 38.788135, "edge2"),
+        ];
+        let elements: Vec<(f64, Bytes)> = places
+            .iter()
+            .map(|&(lon, lat, name)| {
+                (
+                    geohash::encode(lon, lat, GEO_STEP_MAX) as f64,
+                    Bytes::from(name),
+                )
+            })
+            .collect();
+        ks.zadd(b"Sicily", ZAddFlags::default(), &elements).unwrap();
+        ks
+    }
+
+    fn names(matches: &[GeoMatch]) -> Vec<&[u8]> {
+        matches.iter().map(|m| &m.member[..]).collect()
+    }
+
+    #[test]
+    fn test_geodist_and_geohash() {
+        let mut ks = sicily();
+        let dist = ks.geodist(b"Sicily", b"Palermo", b"Catania").unwrap();
+        assert_eq!(format!("{:.4}", dist.unwrap()), "166274.1516");
+        assert_eq!(ks.geodist(b"Sicily", b"Palermo", b"Rome"), Ok(None));
+        let members = [Bytes::from("Palermo"), Bytes::from("Rome")];
+        assert_eq!(
+            ks.geohash(b"Sicily", &members),
+            Ok(vec![Some("sqc8b49rny0".to_string()), None])
+        );
+    }
+
+    #[test]
+    fn test_geosearch_shapes() {
+        let mut ks = sicily();
+        let mut query = GeoQuery {
+            origin: GeoOrigin::LonLat(15.0, 37.0),
+            shape: GeoShape::Radius(200_000.0),
+            sort: GeoSort::Asc,
+            count: None,
+        };
+        let found = ks.geosearch(b"Sicily", &query).unwrap();
+        assert_eq!(names(&found), [&b"Catania"[..], b"Palermo"]);
+        assert_eq!(format!("{:.4}", found[0].distance / 1000.0), "56.4413");
+        query.shape = GeoShape::Box {
+            width: 400_000.0,
+            height: 400_000.0,
+        };
+        query.sort = GeoSort::Desc;
+        let found = ks.geosearch(b"Sicily", &query).unwrap();
+        assert_eq!(
+            names(&found),
+            [&b"edge1"[..], b"edge2", b"Palermo", b"Catania"]
+        );
+        query.count = Some((1, false));
+        query.sort = GeoSort::None;
+        let found = ks.geosearch(b"Sicily", &query).unwrap();
+        assert_eq!(names(&found), [&b"Catania"[..]]);
+    }
+
+    #[test]
+    fn test_geosearchstore() {
+        let mut ks = sicily();
+        let query = GeoQuery {
+            origin: GeoOrigin::Member(Bytes::from("Palermo")),
+            shape: GeoShape::Radius(100_000.0),
+            sort: GeoSort::None,
+            count: None,
+        };
+        assert_eq!(ks.geosearchstore(b"near", b"Sicily", &query, None), Ok(2));
+        assert_eq!(
+            ks.zscore(b"near", b"Palermo"),
+            ks.zscore(b"Sicily", b"Palermo")
+        );
+        assert_eq!(
+            ks.geosearchstore(b"near", b"Sicily", &query, Some(1000.0)),
+            Ok(2)
+        );
+        assert_eq!(ks.zscore(b"near", b"Palermo"), Ok(Some(0.0)));
+        let missing = GeoQuery {
+            origin: GeoOrigin::Member(Bytes::from("Rome")),
+            ..query
+        };
+        assert_eq!(
+            ks.geosearch(b"Sicily", &missing),
+            Err(DbError::GeoMemberMissing)
+        );
+    }
+}
@@ -0,0 +1,272 @@
+/// Geohashes as Redis stores them in a sorted set: 26 bits of latitude and
+/// 26 of longitude, interleaved into a 52 bit integer used as the score.
+pub const GEO_STEP_MAX: u32 = 26;
+pub const GEO_LAT_MIN: f64 = -85.051_128_78;
+pub const GEO_LAT_MAX: f64 = 85.051_128_78;
+pub const GEO_LONG_MIN: f64 = -180.0;
+pub const GEO_LONG_MAX: f64 = 180.0;
+const EARTH_RADIUS_IN_METERS: f64 = 6_372_797.560_856;
+const MERCATOR_MAX: f64 = 20_037_726.37;
+const GEOHASH_ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Spreads `lat` over the even bits and `lon` over the odd ones.
+fn interleave(lat: u32, lon: u32) -> u64 {
+    (0..32).fold(0, |bits, i| {
+        bits | ((lat as u64 >> i) & 1) << (2 * i) | ((lon as u64 >> i) & 1) << (2 * i + 1)
+    })
+}
+
+fn deinterleave(bits: u64) -> (u32, u32) {
+    (0..32).fold((0, 0), |(lat, lon), i| {
+        (
+            lat | (((bits >> (2 * i)) & 1) as u32) << i,
+            lon | (((bits >> (2 * i + 1)) & 1) as u32) << i,
+        )
+    })
+}
+
+fn encode_in(lon: f64, lat: f64, step: u32, lat_min: f64, lat_max: f64) -> u64 {
+    let scale = (1u64 << step) as f64;
+    let lat_offset = (lat - lat_min) / (lat_max - lat_min) * scale;
+    let lon_offset = (lon - GEO_LONG_MIN) / (GEO_LONG_MAX - GEO_LONG_MIN) * scale;
+    interleave(lat_offset as u32, lon_offset as u32)
+}
+
+/// Whether a coordinate pair can be indexed: any longitude, but only the
+/// latitudes the Web Mercator projection covers.
+pub fn valid(lon: f64, lat: f64) -> bool {
+    (GEO_LONG_MIN..=GEO_LONG_MAX).contains(&lon) && (GEO_LAT_MIN..=GEO_LAT_MAX).contains(&lat)
+}
+
+/// Encodes a coordinate pair at `step` bits of precision per axis.
+pub fn encode(lon: f64, lat: f64, step: u32) -> u64 {
+    encode_in(lon, lat, step, GEO_LAT_MIN, GEO_LAT_MAX)
+}
+
+/// The area a hash covers, as `(lon_min, lon_max, lat_min, lat_max)`.
+fn area(hash: u64, step: u32) -> (f64, f64, f64, f64) {
+    let (lat, lon) = deinterleave(hash);
+    let scale = (1u64 << step) as f64;
+    let lat_span = GEO_LAT_MAX - GEO_LAT_MIN;
+    let lon_span = GEO_LONG_MAX - GEO_LONG_MIN;
+    (
+        GEO_LONG_MIN + lon as f64 / scale * lon_span,
+        GEO_LONG_MIN + (lon as f64 + 1.0) / scale * lon_span,
+        GEO_LAT_MIN + lat as f64 / scale * lat_span,
+        GEO_LAT_MIN + (lat as f64 + 1.0) / scale * lat_span,
+    )
+}
+
+/// The centre of the area a full precision hash covers, as `(lon, lat)`.
+pub fn decode(hash: u64) -> (f64, f64) {
+    let (lon_min, lon_max, lat_min, lat_max) = area(hash, GEO_STEP_MAX);
+    (
+        ((lon_min + lon_max) / 2.0).clamp(GEO_LONG_MIN, GEO_LONG_MAX),
+        ((lat_min + lat_max) / 2.0).clamp(GEO_LAT_MIN, GEO_LAT_MAX),
+    )
+}
+
+/// The standard 11 character geohash, which spans latitudes -90..90 rather
+/// than the Mercator range the scores use.
+pub fn to_string(hash: u64) -> String {
+    let (lon, lat) = decode(hash);
+    let bits = encode_in(lon, lat, GEO_STEP_MAX, -90.0, 90.0);
+    (0..11)
+        .map(|i| {
+            // 52 bits make ten full characters; the last one is padding.
+            let index = if i == 10 {
+                0
+            } else {
+                (bits >> (47 - i * 5)) & 0x1f
+            };
+            GEOHASH_ALPHABET[index as usize] as char
+        })
+        .collect()
+}
+
+/// Great-circle distance in meters between two coordinate pairs.
+pub fn distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (lat1r, lat2r) = (lat1.to_radians(), lat2.to_radians());
+    let v = ((lon2.to_radians() - lon1.to_radians()) / 2.0).sin();
+    if v == 0.0 {
+        return EARTH_RADIUS_IN_METERS * (lat2r - lat1r).abs();
+    }
+    let u = ((lat2r - lat1r) / 2.0).sin();
+    let a = u * u + lat1r.cos() * lat2r.cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+/// The area a search covers: a circle, or a box centred on the origin.
+/// Sizes are in meters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeoShape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+impl GeoShape {
+    /// The distance from the centre to `(lon, lat)` if the point lies
+    /// inside the shape.
+    pub fn contains(&self, center: (f64, f64), lon: f64, lat: f64) -> Option<f64> {
+        let (x, y) = center;
+        match *self {
+            GeoShape::Radius(radius) => Some(distance(x, y, lon, lat)).filter(|&d| d <= radius),
+            GeoShape::Box { width, height } => {
+                if distance(lon, lat, lon, y) > height / 2.0
+                    || distance(lon, lat, x, lat) > width / 2.0
+                {
+                    return None;
+                }
+                Some(distance(x, y, lon, lat))
+            }
+        }
+    }
+
+    /// `(lon_min, lat_min, lon_max, lat_max)` around `center`.
+    fn bounding_box(&self, center: (f64, f64)) -> (f64, f64, f64, f64) {
+        let (lon, lat) = center;
+        let (half_width, half_height) = match *self {
+            GeoShape::Radius(radius) => (radius, radius),
+            GeoShape::Box { width, height } => (width / 2.0, height / 2.0),
+        };
+        let lat_delta = (half_height / EARTH_RADIUS_IN_METERS).to_degrees();
+        let lon_delta =
+            |lat: f64| (half_width / EARTH_RADIUS_IN_METERS / lat.to_radians().cos()).to_degrees();
+        // The box is widest on the side nearer the pole.
+        let lon_delta = if lat < 0.0 {
+            lon_delta(lat - lat_delta)
+        } else {
+            lon_delta(lat + lat_delta)
+        };
+        (
+            lon - lon_delta,
+            lat - lat_delta,
+            lon + lon_delta,
+            lat + lat_delta,
+        )
+    }
+
+    fn radius(&self) -> f64 {
+        match *self {
+            GeoShape::Radius(radius) => radius,
+            GeoShape::Box { width, height } => (width / 2.0).hypot(height / 2.0),
+        }
+    }
+}
+
+/// The coarsest precision whose cells still let the centre cell and its
+/// neighbours cover a circle of `radius` meters.
+fn estimate_step(radius: f64, lat: f64) -> u32 {
+    if radius == 0.0 {
+        return GEO_STEP_MAX;
+    }
+    let (mut range, mut step) = (radius, 1i32);
+    while range < MERCATOR_MAX {
+        range *= 2.0;
+        step += 1;
+    }
+    // Stay a little coarser so the range fits in most cases, and coarser
+    // still towards the poles, where cells get narrower.
+    step -= 2;
+    if !(-66.0..=66.0).contains(&lat) {
+        step -= 1;
+        if !(-80.0..=80.0).contains(&lat) {
+            step -= 1;
+        }
+    }
+    step.clamp(1, GEO_STEP_MAX as i32) as u32
+}
+
+fn neighbor(hash: u64, step: u32, d_lon: i64, d_lat: i64) -> u64 {
+    let (lat, lon) = deinterleave(hash);
+    let cells = 1i64 << step;
+    interleave(
+        (lat as i64 + d_lat).rem_euclid(cells) as u32,
+        (lon as i64 + d_lon).rem_euclid(cells) as u32,
+    )
+}
+
+/// The score ranges to scan for members inside `shape` around `center`:
+/// the cell holding the centre and those of its eight neighbours that the
+/// shape reaches, each as a half-open `min..max` range of scores.
+pub fn search_ranges(center: (f64, f64), shape: &GeoShape) -> Vec<(u64, u64)> {
+    let (lon, lat) = center;
+    let (min_lon, min_lat, max_lon, max_lat) = shape.bounding_box(center);
+    let mut step = estimate_step(shape.radius(), lat);
+    let mut hash = encode(lon, lat, step);
+    // If the neighbours do not reach the edges of the bounding box, the
+    // cells are too small: go one step coarser.
+    let (.., north_max) = area(neighbor(hash, step, 0, 1), step);
+    let (.., south_min, _) = area(neighbor(hash, step, 0, -1), step);
+    let (_, east_max, ..) = area(neighbor(hash, step, 1, 0), step);
+    let (west_min, ..) = area(neighbor(hash, step, -1, 0), step);
+    if step > 1
+        && (north_max < max_lat || south_min > min_lat || east_max < max_lon || west_min > min_lon)
+    {
+        step -= 1;
+        hash = encode(lon, lat, step);
+    }
+    let (lon_lo, lon_hi, lat_lo, lat_hi) = area(hash, step);
+    // Neighbours on a side the bounding box does not reach are skipped.
+    let skip_south = step >= 2 && lat_lo < min_lat;
+    let skip_north = step >= 2 && lat_hi > max_lat;
+    let skip_west = step >= 2 && lon_lo < min_lon;
+    let skip_east = step >= 2 && lon_hi > max_lon;
+    let mut cells = vec![hash];
+    for (d_lon, d_lat) in [
+        (0, 1),
+        (0, -1),
+        (1, 0),
+        (-1, 0),
+        (1, 1),
+        (-1, 1),
+        (1, -1),
+        (-1, -1),
+    ] {
+        let skipped = (d_lat > 0 && skip_north)
+            || (d_lat < 0 && skip_south)
+            || (d_lon > 0 && skip_east)
+            || (d_lon < 0 && skip_west);
+        let cell = neighbor(hash, step, d_lon, d_lat);
+        // Very large searches can wrap around to the same cell twice.
+        if !skipped && !cells.contains(&cell) {
+            cells.push(cell);
+        }
+    }
+    let shift = 2 * (GEO_STEP_MAX - step);
+    cells
+        .into_iter()
+        .map(|cell| (cell << shift, (cell + 1) << shift))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let hash = encode(13.361389, 38.115556, GEO_STEP_MAX);
+        assert_eq!(hash, 3479099956230698);
+        let (lon, lat) = decode(hash);
+        assert_eq!(format!("{lon:.17}"), "13.36138933897018433");
+        assert_eq!(format!("{lat:.17}"), "38.11555639549629859");
+        assert_eq!(to_string(hash), "sqc8b49rny0");
+        assert!(!valid(0.0, 86.0));
+    }
+
+    #[test]
+    fn test_distance_and_shapes() {
+        let palermo = decode(encode(13.361389, 38.115556, GEO_STEP_MAX));
+        let catania = decode(encode(15.087269, 37.502669, GEO_STEP_MAX));
+        let d = distance(palermo.0, palermo.1, catania.0, catania.1);
+        assert_eq!(format!("{d:.4}"), "166274.1516");
+        let shape = GeoShape::Radius(200_000.0);
+        assert!(shape.contains(palermo, catania.0, catania.1).is_some());
+        let shape = GeoShape::Box {
+            width: 400_000.0,
+            height: 10_000.0,
+        };
+        assert!(shape.contains(palermo, catania.0, catania.1).is_none());
+    }
+}
@@ -0,0 +1,119 @@
+/// Matches `string` against a Redis glob pattern: `*` matches any run of
+/// bytes, `?` any single byte, `[abc]`, `[a-z]` and `[^a-z]` a class of
+/// bytes, and `\` escapes the byte after it, inside classes too.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Where to resume after the most recent `*`, and how much of `string`
+    // it has swallowed so far. Earlier stars never need revisiting.
+    let mut star: Option<(usize, usize)> = None;
+    while s < string.len() {
+        if pattern.get(p) == Some(&b'*') {
+            while pattern.get(p) == Some(&b'*') {
+                p += 1;
+            }
+            star = Some((p, s));
+            continue;
+        }
+        if let Some(next) = match_single(pattern, p, string[s]) {
+            p = next;
+            s += 1;
+            continue;
+        }
+        match star {
+            Some((resume, swallowed)) => {
+                star = Some((resume, swallowed + 1));
+                p = resume;
+                s = swallowed + 1;
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Matches the pattern element starting at `p` against one byte, returning
+/// the index just past the element on success.
+fn match_single(pattern: &[u8], p: usize, c: u8) -> Option<usize> {
+    match *pattern.get(p)? {
+        b'?' => Some(p + 1),
+        b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        b'[' => {
+            let (matched, next) = match_class(pattern, p + 1, c);
+            matched.then_some(next)
+        }
+        other => (other == c).then_some(p + 1),
+    }
+}
+
+/// Matches the class whose body starts at `p`. An unterminated class runs to
+/// the end of the pattern, as in Redis.
+fn match_class(pattern: &[u8], mut p: usize, c: u8) -> (bool, usize) {
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+    let mut matched = false;
+    while let Some(&first) = pattern.get(p) {
+        match first {
+            b']' => {
+                p += 1;
+                break;
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                matched |= pattern[p + 1] == c;
+                p += 2;
+            }
+            _ if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                let (lo, hi) = (first.min(pattern[p + 2]), first.max(pattern[p + 2]));
+                matched |= (lo..=hi).contains(&c);
+                p += 3;
+            }
+            _ => {
+                matched |= first == c;
+                p += 1;
+            }
+        }
+    }
+    (matched != negate, p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, string: &str) -> bool {
+        glob_match(pattern.as_bytes(), string.as_bytes())
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("h?llo", "hello"));
+        assert!(!matches("h?llo", "hllo"));
+        assert!(matches("h*llo", "heeeello"));
+        assert!(matches("*a*b*", "xxaxxbxx"));
+        assert!(!matches("*a*b", "xxaxxbxx"));
+        assert!(matches("user:*:name", "user:1:2:name"));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(matches("h[ae]llo", "hallo"));
+        assert!(!matches("h[ae]llo", "hillo"));
+        assert!(matches("h[^e]llo", "hallo"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(matches("h[a-b]llo", "hbllo"));
+        assert!(matches("h[z-a]llo", "hmllo"));
+        assert!(matches("[\\]]", "]"));
+        assert!(!matches("[]", "a"));
+        assert!(matches("a[bc", "ab"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert!(matches("a\\*b", "a*b"));
+        assert!(!matches("a\\*b", "axb"));
+        assert!(matches("a\\?", "a?"));
+        assert!(matches("a\\", "a\\"));
+    }
+}
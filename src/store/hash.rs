@@ -0,0 +1,229 @@
+use std::{collections::HashMap, ops::Deref};
+
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, Keyspace},
+    notify::EventClass,
+    numeric::{format_float, parse_float},
+    random,
+    scan::ScanIndex,
+};
+
+/// A hash value. Reads go straight to the map; writes go through here so
+/// the HSCAN order stays in step with it.
+#[derive(Debug, Clone, Default)]
+pub struct HashValue {
+    map: HashMap<Bytes, Bytes>,
+    pub(super) index: ScanIndex,
+}
+
+impl HashValue {
+    pub fn insert(&mut self, field: Bytes, value: Bytes) -> Option<Bytes> {
+        let previous = self.map.insert(field.clone(), value);
+        if previous.is_none() {
+            self.index.insert(field);
+        }
+        previous
+    }
+
+    pub fn remove(&mut self, field: &[u8]) -> Option<Bytes> {
+        let removed = self.map.remove(field);
+        if removed.is_some() {
+            self.index.remove(field);
+        }
+        removed
+    }
+}
+
+impl Deref for HashValue {
+    type Target = HashMap<Bytes, Bytes>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl Keyspace {
+    /// Sets every field/value pair and returns how many fields were new.
+    pub fn hset(&mut self, key: &[u8], pairs: &[(Bytes, Bytes)]) -> Result<i64, DbError> {
+        let hash = self.hash_entry(key)?;
+        let mut added = 0;
+        for (field, value) in pairs {
+            if hash.insert(field.clone(), value.clone()).is_none() {
+                added += 1;
+            }
+        }
+        self.notify(EventClass::Hash, "hset", key);
+        Ok(added)
+    }
+
+    pub fn hsetnx(&mut self, key: &[u8], field: &[u8], value: &[u8]) -> Result<bool, DbError> {
+        let hash = self.hash_entry(key)?;
+        if hash.contains_key(field) {
+            return Ok(false);
+        }
+        hash.insert(Bytes::copy_from_slice(field), Bytes::copy_from_slice(value));
+        self.notify(EventClass::Hash, "hset", key);
+        Ok(true)
+    }
+
+    pub fn hget(&mut self, key: &[u8], field: &[u8]) -> Result<Option<Bytes>, DbError> {
+        Ok(self.get_hash(key)?.and_then(|h| h.get(field).cloned()))
+    }
+
+    pub fn hmget(&mut self, key: &[u8], fields: &[Bytes]) -> Result<Vec<Option<Bytes>>, DbError> {
+        let hash = self.get_hash(key)?;
+        Ok(fields
+            .iter()
+            .map(|f| hash.and_then(|h| h.get(f).cloned()))
+            .collect())
+    }
+
+    pub fn hgetall(&mut self, key: &[u8]) -> Result<Vec<(Bytes, Bytes)>, DbError> {
+        Ok(self
+            .get_hash(key)?
+            .map(|h| h.iter().map(|(f, v)| (f.clone(), v.clone())).collect())
+            .unwrap_or_default())
+    }
+
+    pub fn hdel(&mut self, key: &[u8], fields: &[Bytes]) -> Result<i64, DbError> {
+        let removed = match self.get_hash_mut(key)? {
+            Some(hash) => fields.iter().filter(|f| hash.remove(f).is_some()).count(),
+            None => 0,
+        };
+        if removed > 0 {
+            self.watched.touch(key);
+            self.notify(EventClass::Hash, "hdel", key);
+        }
+        self.remove_if_empty(key);
+        Ok(removed as i64)
+    }
+
+    pub fn hexists(&mut self, key: &[u8], field: &[u8]) -> Result<bool, DbError> {
+        Ok(self.get_hash(key)?.is_some_and(|h| h.contains_key(field)))
+    }
+
+    pub fn hlen(&mut self, key: &[u8]) -> Result<i64, DbError> {
+        Ok(self.get_hash(key)?.map_or(0, |h| h.len() as i64))
+    }
+
+    pub fn hstrlen(&mut self, key: &[u8], field: &[u8]) -> Result<i64, DbError> {
+        Ok(self
+            .get_hash(key)?
+            .and_then(|h| h.get(field))
+            .map_or(0, |v| v.len() as i64))
+    }
+
+    pub fn hincrby(&mut self, key: &[u8], field: &[u8], delta: i64) -> Result<i64, DbError> {
+        let hash = self.hash_entry(key)?;
+        let current = match hash.get(field) {
+            Some(v) => std::str::from_utf8(v)
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .ok_or(DbError::HashValueNotInteger)?,
+            None => 0,
+        };
+        let next = current.checked_add(delta).ok_or(DbError::Overflow)?;
+        hash.insert(Bytes::copy_from_slice(field), Bytes::from(next.to_string()));
+        self.notify(EventClass::Hash, "hincrby", key);
+        Ok(next)
+    }
+
+    pub fn hincrbyfloat(&mut self, key: &[u8], field: &[u8], delta: f64) -> Result<Bytes, DbError> {
+        let hash = self.hash_entry(key)?;
+        let current = match hash.get(field) {
+            Some(v) => parse_float(v).ok_or(DbError::HashValueNotFloat)?,
+            None => 0.0,
+        };
+        let next = current + delta;
+        if next.is_nan() || next.is_infinite() {
+            return Err(DbError::NanOrInfinity);
+        }
+        let formatted = Bytes::from(format_float(next));
+        hash.insert(Bytes::copy_from_slice(field), formatted.clone());
+        self.notify(EventClass::Hash, "hincrbyfloat", key);
+        Ok(formatted)
+    }
+
+    /// Picks random fields. A positive `count` returns distinct fields, a
+    /// negative one may repeat fields and always returns `|count|` of them.
+    pub fn hrandfield(&mut self, key: &[u8], count: i64) -> Result<Vec<(Bytes, Bytes)>, DbError> {
+        let hash = match self.get_hash(key)? {
+            Some(hash) if !hash.is_empty() => hash,
+            _ => return Ok(vec![]),
+        };
+        let pairs: Vec<(&Bytes, &Bytes)> = hash.iter().collect();
+        let indices = if count >= 0 {
+            random::distinct_indices(pairs.len(), count as usize)
+        } else {
+            (0..count.unsigned_abs())
+                .map(|_| random::below(pairs.len()))
+                .collect()
+        };
+        Ok(indices
+            .into_iter()
+            .map(|i| (pairs[i].0.clone(), pairs[i].1.clone()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(Bytes, Bytes)> {
+        items
+            .iter()
+            .map(|(f, v)| {
+                (
+                    Bytes::copy_from_slice(f.as_bytes()),
+                    Bytes::copy_from_slice(v.as_bytes()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hset_counts_new_fields() {
+        let mut ks = Keyspace::default();
+        assert_eq!(ks.hset(b"h", &pairs(&[("a", "1"), ("b", "2")])), Ok(2));
+        assert_eq!(ks.hset(b"h", &pairs(&[("a", "3"), ("c", "4")])), Ok(1));
+        assert_eq!(ks.hget(b"h", b"a"), Ok(Some(Bytes::from("3"))));
+        assert_eq!(ks.hlen(b"h"), Ok(3));
+    }
+
+    #[test]
+    fn test_hdel_removes_empty_key() {
+        let mut ks = Keyspace::default();
+        ks.hset(b"h", &pairs(&[("a", "1")])).unwrap();
+        assert_eq!(ks.hdel(b"h", &[Bytes::from("a"), Bytes::from("x")]), Ok(1));
+        assert!(ks.get(b"h").is_none());
+    }
+
+    #[test]
+    fn test_hincrby_errors() {
+        let mut ks = Keyspace::default();
+        ks.hset(b"h", &pairs(&[("s", "abc"), ("m", &i64::MAX.to_string())]))
+            .unwrap();
+        assert_eq!(ks.hincrby(b"h", b"n", 5), Ok(5));
+        assert_eq!(ks.hincrby(b"h", b"s", 1), Err(DbError::HashValueNotInteger));
+        assert_eq!(ks.hincrby(b"h", b"m", 1), Err(DbError::Overflow));
+        assert_eq!(ks.hincrbyfloat(b"h", b"n", 0.5), Ok(Bytes::from("5.5")));
+        assert_eq!(
+            ks.hincrbyfloat(b"h", b"s", 1.0),
+            Err(DbError::HashValueNotFloat)
+        );
+    }
+
+    #[test]
+    fn test_hrandfield_counts() {
+        let mut ks = Keyspace::default();
+        ks.hset(b"h", &pairs(&[("a", "1"), ("b", "2"), ("c", "3")]))
+            .unwrap();
+        assert_eq!(ks.hrandfield(b"h", 10).unwrap().len(), 3);
+        assert_eq!(ks.hrandfield(b"h", 2).unwrap().len(), 2);
+        assert_eq!(ks.hrandfield(b"h", -7).unwrap().len(), 7);
+        assert!(ks.hrandfield(b"missing", 3).unwrap().is_empty());
+    }
+}
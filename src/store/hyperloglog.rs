@@ -0,0 +1,416 @@
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, Keyspace},
+    notify::EventClass,
+};
+
+const HLL_P: u32 = 14;
+const HLL_Q: u32 = 64 - HLL_P;
+const HLL_REGISTERS: usize = 1 << HLL_P;
+const HLL_BITS: usize = 6;
+const HLL_HEADER_LEN: usize = 16;
+const HLL_DENSE_LEN: usize = HLL_HEADER_LEN + (HLL_REGISTERS * HLL_BITS).div_ceil(8);
+const HLL_MAGIC: &[u8] = b"HYLL";
+const HLL_DENSE: u8 = 0;
+const HLL_SPARSE: u8 = 1;
+/// The largest register value a sparse VAL opcode can hold.
+const HLL_SPARSE_VAL_MAX: u8 = 32;
+/// Past this size, header included, a sparse representation is promoted
+/// to dense, as with Redis' default `hll-sparse-max-bytes`.
+const HLL_SPARSE_MAX_BYTES: usize = 3000;
+const HLL_ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+const HLL_HASH_SEED: u64 = 0xadc8_3b19;
+
+/// MurmurHash64A, the hash Redis feeds HyperLogLog elements through.
+fn murmur64a(data: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (data.len() as u64).wrapping_mul(M);
+    let mut blocks = data.chunks_exact(8);
+    for block in &mut blocks {
+        let mut k = u64::from_le_bytes(block.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        for (i, &byte) in tail.iter().enumerate() {
+            h ^= (byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+/// Reads dense register `index`: six bits, packed least significant first.
+fn dense_get(registers: &[u8], index: usize) -> u8 {
+    let (byte, shift) = (index * HLL_BITS / 8, (index * HLL_BITS) & 7);
+    let low = registers[byte] as u16;
+    let high = registers.get(byte + 1).copied().unwrap_or(0) as u16;
+    ((low >> shift | high << (8 - shift)) & 0x3f) as u8
+}
+
+fn dense_set(registers: &mut [u8], index: usize, value: u8) {
+    let (byte, shift) = (index * HLL_BITS / 8, (index * HLL_BITS) & 7);
+    let value = value as u16;
+    registers[byte] &= !(0x3f_u16 << shift) as u8;
+    registers[byte] |= (value << shift) as u8;
+    if let Some(next) = registers.get_mut(byte + 1) {
+        *next &= !(0x3f_u16 >> (8 - shift)) as u8;
+        *next |= (value >> (8 - shift)) as u8;
+    }
+}
+
+/// A HyperLogLog decoded to one byte per register. Stored, it is a plain
+/// string laid out exactly as Redis lays it out, so GET and SET move it
+/// between the two servers unchanged: a 16 byte header (`HYLL`, the
+/// encoding, three unused bytes and the cached cardinality) followed by the
+/// registers, either sparse or dense.
+#[derive(Debug, Clone, PartialEq)]
+struct Hll {
+    registers: Vec<u8>,
+    /// Whether the value is already dense. Like Redis, a HyperLogLog is
+    /// never demoted back to sparse.
+    dense: bool,
+    /// The cardinality cached in the header, if still valid.
+    cached: Option<u64>,
+}
+
+impl Default for Hll {
+    fn default() -> Self {
+        Hll {
+            registers: vec![0; HLL_REGISTERS],
+            dense: false,
+            cached: Some(0),
+        }
+    }
+}
+
+impl Hll {
+    fn decode(bytes: &[u8]) -> Result<Self, DbError> {
+        if bytes.len() < HLL_HEADER_LEN || &bytes[..4] != HLL_MAGIC {
+            return Err(DbError::InvalidHll);
+        }
+        let card = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let cached = (card >> 63 == 0).then_some(card);
+        let payload = &bytes[HLL_HEADER_LEN..];
+        let registers = match bytes[4] {
+            HLL_DENSE if bytes.len() == HLL_DENSE_LEN => (0..HLL_REGISTERS)
+                .map(|index| dense_get(payload, index))
+                .collect(),
+            HLL_SPARSE => Self::decode_sparse(payload)?,
+            _ => return Err(DbError::InvalidHll),
+        };
+        Ok(Hll {
+            registers,
+            dense: bytes[4] == HLL_DENSE,
+            cached,
+        })
+    }
+
+    /// Expands the sparse opcodes: ZERO (`00xxxxxx`) and XZERO
+    /// (`01xxxxxx yyyyyyyy`) are runs of empty registers, VAL (`1vvvvvxx`) a
+    /// run of up to four registers holding the same value.
+    fn decode_sparse(ops: &[u8]) -> Result<Vec<u8>, DbError> {
+        let mut registers = Vec::with_capacity(HLL_REGISTERS);
+        let mut ops = ops.iter();
+        while let Some(&op) = ops.next() {
+            let (value, run) = match op >> 6 {
+                0b00 => (0, (op & 0x3f) as usize + 1),
+                0b01 => {
+                    let low = *ops.next().ok_or(DbError::CorruptHll)?;
+                    (0, ((op as usize & 0x3f) << 8 | low as usize) + 1)
+                }
+                _ => ((op >> 2 & 0x1f) + 1, (op & 0x03) as usize + 1),
+            };
+            if registers.len() + run > HLL_REGISTERS {
+                return Err(DbError::CorruptHll);
+            }
+            registers.resize(registers.len() + run, value);
+        }
+        if registers.len() != HLL_REGISTERS {
+            return Err(DbError::CorruptHll);
+        }
+        Ok(registers)
+    }
+
+    /// The sparse opcodes for the registers, or `None` if they cannot be
+    /// represented sparsely within the size limit.
+    fn encode_sparse(&self) -> Option<Vec<u8>> {
+        let mut ops = Vec::new();
+        let mut index = 0;
+        while index < HLL_REGISTERS {
+            let value = self.registers[index];
+            let end = self.registers[index..]
+                .iter()
+                .position(|&register| register != value)
+                .map_or(HLL_REGISTERS, |len| index + len);
+            let mut run = end - index;
+            if value > HLL_SPARSE_VAL_MAX {
+                return None;
+            }
+            while run > 0 {
+                let len = match value {
+                    0 if run > 64 => {
+                        let len = run.min(HLL_REGISTERS);
+                        ops.extend([0x40 | ((len - 1) >> 8) as u8, (len - 1) as u8]);
+                        len
+                    }
+                    0 => {
+                        ops.push((run - 1) as u8);
+                        run
+                    }
+                    _ => {
+                        let len = run.min(4);
+                        ops.push(0x80 | (value - 1) << 2 | (len - 1) as u8);
+                        len
+                    }
+                };
+                run -= len;
+            }
+            if HLL_HEADER_LEN + ops.len() > HLL_SPARSE_MAX_BYTES {
+                return None;
+            }
+            index = end;
+        }
+        Some(ops)
+    }
+
+    /// Serializes the value, sparse while it can be and dense otherwise.
+    fn encode(&mut self) -> Bytes {
+        let sparse = if self.dense {
+            None
+        } else {
+            self.encode_sparse()
+        };
+        self.dense = sparse.is_none();
+        let payload = sparse.unwrap_or_else(|| {
+            let mut registers = vec![0; HLL_DENSE_LEN - HLL_HEADER_LEN];
+            for (index, &value) in self.registers.iter().enumerate() {
+                dense_set(&mut registers, index, value);
+            }
+            registers
+        });
+        let mut bytes = Vec::with_capacity(HLL_HEADER_LEN + payload.len());
+        bytes.extend_from_slice(HLL_MAGIC);
+        bytes.extend([if self.dense { HLL_DENSE } else { HLL_SPARSE }, 0, 0, 0]);
+        bytes.extend(self.cached.unwrap_or(1 << 63).to_le_bytes());
+        bytes.extend(payload);
+        Bytes::from(bytes)
+    }
+
+    /// Adds an element, returning whether any register changed.
+    fn add(&mut self, element: &[u8]) -> bool {
+        let hash = murmur64a(element, HLL_HASH_SEED);
+        let index = (hash & (HLL_REGISTERS as u64 - 1)) as usize;
+        let rank = ((hash >> HLL_P) | 1 << HLL_Q).trailing_zeros() as u8 + 1;
+        if rank <= self.registers[index] {
+            return false;
+        }
+        self.registers[index] = rank;
+        self.cached = None;
+        true
+    }
+
+    /// Takes the register-wise maximum with `other`.
+    fn merge(&mut self, other: &Hll) {
+        for (register, &value) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(value);
+        }
+        self.dense |= other.dense;
+        self.cached = None;
+    }
+
+    /// Estimates the cardinality with the same estimator as Redis (Otmar
+    /// Ertl's improved raw estimator), so both report identical counts.
+    fn count(&self) -> u64 {
+        let mut histogram = [0u32; 64];
+        for &value in &self.registers {
+            histogram[value as usize] += 1;
+        }
+        let m = HLL_REGISTERS as f64;
+        let mut z = m * tau((m - histogram[HLL_Q as usize + 1] as f64) / m);
+        for &count in histogram[1..=HLL_Q as usize].iter().rev() {
+            z += count as f64;
+            z *= 0.5;
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+        (HLL_ALPHA_INF * m * m / z).round() as u64
+    }
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let (mut y, mut z) = (1.0, x);
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let (mut y, mut z) = (1.0, 1.0 - x);
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+impl Keyspace {
+    fn get_hll(&mut self, key: &[u8]) -> Result<Option<Hll>, DbError> {
+        match self.str_bytes(key)? {
+            Some(bytes) => Hll::decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// PFADD: returns whether the key was created or any register changed.
+    pub fn pfadd(&mut self, key: &[u8], elements: &[Bytes]) -> Result<bool, DbError> {
+        let (mut hll, created) = match self.get_hll(key)? {
+            Some(hll) => (hll, false),
+            None => (Hll::default(), true),
+        };
+        let mut updated = false;
+        for element in elements {
+            updated |= hll.add(element);
+        }
+        if created || updated {
+            self.overwrite_str(key, hll.encode().into());
+            self.notify(EventClass::String, "pfadd", key);
+        }
+        Ok(created || updated)
+    }
+
+    /// PFCOUNT. A single key answers from, or refreshes, the cardinality
+    /// cached in its header; several keys are counted as their union.
+    pub fn pfcount(&mut self, keys: &[Bytes]) -> Result<u64, DbError> {
+        if let [key] = keys {
+            let Some(bytes) = self.str_bytes(key)? else {
+                return Ok(0);
+            };
+            let hll = Hll::decode(&bytes)?;
+            if let Some(count) = hll.cached {
+                return Ok(count);
+            }
+            let count = hll.count();
+            let mut bytes = Vec::from(bytes);
+            bytes[8..HLL_HEADER_LEN].copy_from_slice(&count.to_le_bytes());
+            self.overwrite_str(key, Bytes::from(bytes).into());
+            return Ok(count);
+        }
+        let mut union = Hll::default();
+        for key in keys {
+            if let Some(hll) = self.get_hll(key)? {
+                union.merge(&hll);
+            }
+        }
+        Ok(union.count())
+    }
+
+    /// PFMERGE: folds every source into `destination`, creating it if
+    /// needed. The result is dense if any input was.
+    pub fn pfmerge(&mut self, destination: &[u8], sources: &[Bytes]) -> Result<(), DbError> {
+        let mut merged = self.get_hll(destination)?.unwrap_or_default();
+        for key in sources {
+            if let Some(hll) = self.get_hll(key)? {
+                merged.merge(&hll);
+            }
+        }
+        merged.cached = None;
+        self.overwrite_str(destination, merged.encode().into());
+        self.notify(EventClass::String, "pfadd", destination);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elements(range: std::ops::Range<u32>) -> Vec<Bytes> {
+        range.map(|i| Bytes::from(format!("element:{i}"))).collect()
+    }
+
+    fn encoding(ks: &mut Keyspace, key: &[u8]) -> u8 {
+        ks.str_bytes(key).unwrap().unwrap()[4]
+    }
+
+    #[test]
+    fn test_pfadd_pfcount() {
+        let mut ks = Keyspace::default();
+        assert_eq!(ks.pfadd(b"h", &[]), Ok(true));
+        assert_eq!(ks.pfadd(b"h", &[]), Ok(false));
+        let letters: Vec<Bytes> = "abcdefg".bytes().map(|b| Bytes::from(vec![b])).collect();
+        assert_eq!(ks.pfadd(b"h", &letters), Ok(true));
+        assert_eq!(ks.pfadd(b"h", &letters[..3]), Ok(false));
+        assert_eq!(ks.pfcount(&[Bytes::from("h")]), Ok(7));
+        assert_eq!(ks.pfcount(&[Bytes::from("missing")]), Ok(0));
+        // The count is now cached in the header.
+        let bytes = ks.str_bytes(b"h").unwrap().unwrap();
+        assert_eq!(&bytes[8..16], &7u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_promotion_to_dense() {
+        let mut ks = Keyspace::default();
+        ks.pfadd(b"h", &elements(0..100)).unwrap();
+        assert_eq!(encoding(&mut ks, b"h"), HLL_SPARSE);
+        // A sparse value, header included, never outgrows the limit.
+        let mut next = 100;
+        while encoding(&mut ks, b"h") == HLL_SPARSE {
+            assert!(ks.strlen(b"h").unwrap() <= HLL_SPARSE_MAX_BYTES);
+            ks.pfadd(b"h", &elements(next..next + 1)).unwrap();
+            next += 1;
+        }
+        ks.pfadd(b"h", &elements(next..20_000)).unwrap();
+        assert_eq!(encoding(&mut ks, b"h"), HLL_DENSE);
+        assert_eq!(ks.strlen(b"h"), Ok(HLL_DENSE_LEN));
+        let count = ks.pfcount(&[Bytes::from("h")]).unwrap();
+        assert!((19_600..=20_400).contains(&count), "count {count}");
+        // Decoding and re-encoding dense registers is lossless.
+        let bytes = ks.str_bytes(b"h").unwrap().unwrap();
+        assert_eq!(Hll::decode(&bytes).unwrap().encode(), bytes);
+    }
+
+    #[test]
+    fn test_pfmerge_and_invalid_values() {
+        let mut ks = Keyspace::default();
+        ks.pfadd(b"a", &elements(0..300)).unwrap();
+        ks.pfadd(b"b", &elements(200..500)).unwrap();
+        ks.pfmerge(b"dst", &[Bytes::from("a"), Bytes::from("b")])
+            .unwrap();
+        let union = ks.pfcount(&[Bytes::from("a"), Bytes::from("b")]).unwrap();
+        assert_eq!(ks.pfcount(&[Bytes::from("dst")]), Ok(union));
+        assert!((490..=510).contains(&union), "count {union}");
+        ks.set(b"s", Bytes::from("not an hll"), &Default::default())
+            .unwrap();
+        assert_eq!(ks.pfadd(b"s", &[]), Err(DbError::InvalidHll));
+        let mut corrupt = ks.str_bytes(b"a").unwrap().unwrap().to_vec();
+        corrupt.push(0x00);
+        ks.set(b"c", Bytes::from(corrupt), &Default::default())
+            .unwrap();
+        assert_eq!(ks.pfcount(&[Bytes::from("c")]), Err(DbError::CorruptHll));
+    }
+}
@@ -0,0 +1,160 @@
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, EntryValue, Keyspace},
+    glob::glob_match,
+    notify::EventClass,
+    random,
+};
+
+impl Keyspace {
+    /// Removes the given keys, returning how many existed.
+    pub fn del(&mut self, keys: &[Bytes]) -> usize {
+        keys.iter().filter(|key| self.delete(key)).count()
+    }
+
+    /// Removes `key` as DEL does, raising a `del` event if it existed.
+    pub fn delete(&mut self, key: &[u8]) -> bool {
+        let existed = self.remove(key).is_some();
+        if existed {
+            self.notify(EventClass::Generic, "del", key);
+        }
+        existed
+    }
+
+    /// Counts the given keys that exist; a key named twice counts twice.
+    pub fn exists(&mut self, keys: &[Bytes]) -> usize {
+        keys.iter().filter(|key| self.get(key).is_some()).count()
+    }
+
+    /// The name TYPE reports for the value at `key`.
+    pub fn type_name(&mut self, key: &[u8]) -> &'static str {
+        match self.get(key).map(|entry| &entry.value) {
+            None => "none",
+            Some(EntryValue::Str(_)) => "string",
+            Some(EntryValue::List(_)) => "list",
+            Some(EntryValue::Hash(_)) => "hash",
+            Some(EntryValue::Set(_)) => "set",
+            Some(EntryValue::ZSet(_)) => "zset",
+            Some(EntryValue::Stream(_)) => "stream",
+        }
+    }
+
+    pub fn keys(&self, pattern: &[u8]) -> Vec<Bytes> {
+        self.live_keys()
+            .filter(|key| glob_match(pattern, key))
+            .cloned()
+            .collect()
+    }
+
+    pub fn random_key(&self) -> Option<Bytes> {
+        let keys: Vec<&Bytes> = self.live_keys().collect();
+        match keys.len() {
+            0 => None,
+            n => Some(keys[random::below(n)].clone()),
+        }
+    }
+
+    /// Moves the value, TTL included, from `src` to `dst`. With `nx` an
+    /// existing `dst` is left alone and `false` returned.
+    pub fn rename(&mut self, src: &[u8], dst: &[u8], nx: bool) -> Result<bool, DbError> {
+        if self.get(src).is_none() {
+            return Err(DbError::NoSuchKey);
+        }
+        if nx && self.get(dst).is_some() {
+            return Ok(false);
+        }
+        if src != dst {
+            let entry = self.remove(src).expect("checked above");
+            self.notify(EventClass::Generic, "rename_from", src);
+            self.insert(Bytes::copy_from_slice(dst), entry);
+            self.blocked.signal(dst);
+            self.notify(EventClass::Generic, "rename_to", dst);
+        }
+        Ok(true)
+    }
+
+    /// Copies the value, TTL included, from `src` to `dst`. An existing
+    /// `dst` is only overwritten with `replace`.
+    pub fn copy(&mut self, src: &[u8], dst: &[u8], replace: bool) -> bool {
+        let Some(entry) = self.get(src).cloned() else {
+            return false;
+        };
+        if !replace && self.get(dst).is_some() {
+            return false;
+        }
+        self.insert(Bytes::copy_from_slice(dst), entry);
+        self.blocked.signal(dst);
+        self.notify(EventClass::Generic, "copy_to", dst);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::store::{
+        expire::KeyTtl,
+        string::{SetExpiry, SetOptions, StrValue},
+    };
+
+    fn keys(names: &[&str]) -> Vec<Bytes> {
+        names.iter().map(|s| Bytes::from(s.to_string())).collect()
+    }
+
+    #[test]
+    fn test_del_exists_type() {
+        let mut ks = Keyspace::default();
+        ks.set(b"a", Bytes::from("1"), &SetOptions::default())
+            .unwrap();
+        ks.sadd(b"s", &keys(&["x"])).unwrap();
+        assert_eq!(ks.exists(&keys(&["a", "a", "s", "nope"])), 3);
+        assert_eq!(ks.type_name(b"s"), "set");
+        assert_eq!(ks.type_name(b"nope"), "none");
+        assert_eq!(ks.del(&keys(&["a", "nope", "a"])), 1);
+        assert_eq!(ks.type_name(b"a"), "none");
+    }
+
+    #[test]
+    fn test_rename_and_copy_keep_ttl() {
+        let mut ks = Keyspace::default();
+        let ex = SetOptions {
+            expiry: SetExpiry::After(Duration::from_secs(100)),
+            ..Default::default()
+        };
+        ks.set(b"a", Bytes::from("1"), &ex).unwrap();
+        ks.set(b"b", Bytes::from("2"), &SetOptions::default())
+            .unwrap();
+        assert_eq!(ks.rename(b"nope", b"x", false), Err(DbError::NoSuchKey));
+        assert_eq!(ks.rename(b"a", b"b", true), Ok(false));
+        assert_eq!(ks.rename(b"a", b"c", false), Ok(true));
+        assert!(matches!(ks.ttl(b"c"), KeyTtl::ExpiresAt(_)));
+        assert_eq!(ks.exists(&keys(&["a"])), 0);
+        assert!(!ks.copy(b"c", b"b", false));
+        assert!(ks.copy(b"c", b"b", true));
+        assert_eq!(ks.get_str(b"b"), Ok(Some(&StrValue::Int(1))));
+        assert_eq!(ks.ttl(b"b"), ks.ttl(b"c"));
+    }
+
+    #[test]
+    fn test_keys_skips_expired() {
+        let mut ks = Keyspace::default();
+        for key in ["user:1", "user:2", "item:1"] {
+            ks.set(key.as_bytes(), Bytes::from("v"), &SetOptions::default())
+                .unwrap();
+        }
+        let gone = SetOptions {
+            expiry: SetExpiry::After(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        ks.set(b"user:3", Bytes::from("v"), &gone).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        let mut found = ks.keys(b"user:*");
+        found.sort();
+        assert_eq!(found, keys(&["user:1", "user:2"]));
+        assert_eq!(ks.key_count(), 4);
+        assert!(ks.random_key().is_some_and(|key| key != b"user:3"[..]));
+    }
+}
@@ -0,0 +1,107 @@
+use std::ops::RangeInclusive;
+
+/// A run of bytes common to both strings, by its position in each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LcsMatch {
+    pub a: RangeInclusive<usize>,
+    pub b: RangeInclusive<usize>,
+}
+
+impl LcsMatch {
+    pub fn len(&self) -> usize {
+        self.a.end() - self.a.start() + 1
+    }
+}
+
+/// The longest common subsequence of two strings, and the runs it is made
+/// of, last run first as LCS IDX reports them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lcs {
+    pub sequence: Vec<u8>,
+    pub matches: Vec<LcsMatch>,
+}
+
+/// Computes the LCS with the classic dynamic programming table, then walks
+/// it back from the end, collecting contiguous runs as Redis does.
+pub fn lcs(a: &[u8], b: &[u8]) -> Lcs {
+    let width = b.len() + 1;
+    // table[i * width + j] is the LCS length of a[..i] and b[..j].
+    let mut table = vec![0u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            table[i * width + j] = if a[i - 1] == b[j - 1] {
+                table[(i - 1) * width + j - 1] + 1
+            } else {
+                table[(i - 1) * width + j].max(table[i * width + j - 1])
+            };
+        }
+    }
+    let mut sequence = vec![0u8; table[a.len() * width + b.len()] as usize];
+    let mut idx = sequence.len();
+    let mut matches = Vec::new();
+    // The run being built, as (a_start, a_end, b_start, b_end).
+    let mut run: Option<(usize, usize, usize, usize)> = None;
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        let mut emit = false;
+        if a[i - 1] == b[j - 1] {
+            idx -= 1;
+            sequence[idx] = a[i - 1];
+            run = match run {
+                Some((a_start, a_end, b_start, b_end)) if a_start == i && b_start == j => {
+                    Some((a_start - 1, a_end, b_start - 1, b_end))
+                }
+                Some(_) => {
+                    emit = true;
+                    run
+                }
+                None => Some((i - 1, i - 1, j - 1, j - 1)),
+            };
+            // A run touching the start of either string cannot grow further.
+            emit |= run.is_some_and(|(a_start, _, b_start, _)| a_start == 0 || b_start == 0);
+            i -= 1;
+            j -= 1;
+        } else {
+            if table[(i - 1) * width + j] > table[i * width + j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+            emit = run.is_some();
+        }
+        if emit {
+            if let Some((a_start, a_end, b_start, b_end)) = run.take() {
+                matches.push(LcsMatch {
+                    a: a_start..=a_end,
+                    b: b_start..=b_end,
+                });
+            }
+        }
+    }
+    Lcs { sequence, matches }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lcs_sequence() {
+        assert_eq!(lcs(b"ohmytext", b"mynewtext").sequence, b"mytext");
+        assert_eq!(lcs(b"", b"abc").sequence, b"");
+        assert_eq!(lcs(b"abc", b"xyz").matches, vec![]);
+    }
+
+    #[test]
+    fn test_lcs_matches() {
+        let result = lcs(b"ohmytext", b"mynewtext");
+        assert_eq!(
+            result.matches,
+            vec![
+                LcsMatch { a: 4..=7, b: 5..=8 },
+                LcsMatch { a: 2..=3, b: 0..=1 },
+            ]
+        );
+        assert_eq!(result.matches[0].len(), 4);
+    }
+}
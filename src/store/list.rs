@@ -0,0 +1,346 @@
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, Keyspace},
+    notify::EventClass,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+/// Converts a Redis style `start..=stop` pair (negative values count from the
+/// tail) into a half-open range clamped to `len`. `None` means empty.
+pub fn normalize_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start as usize, stop as usize + 1))
+}
+
+fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let idx = if index < 0 { len as i64 + index } else { index };
+    if idx < 0 || idx >= len as i64 {
+        None
+    } else {
+        Some(idx as usize)
+    }
+}
+
+impl Keyspace {
+    pub fn push(&mut self, key: &[u8], end: ListEnd, elements: &[Bytes]) -> Result<i64, DbError> {
+        let list = self.list_entry(key)?;
+        for elem in elements {
+            match end {
+                ListEnd::Left => list.push_front(elem.clone()),
+                ListEnd::Right => list.push_back(elem.clone()),
+            }
+        }
+        let len = list.len() as i64;
+        self.blocked.signal(key);
+        let event = match end {
+            ListEnd::Left => "lpush",
+            ListEnd::Right => "rpush",
+        };
+        self.notify(EventClass::List, event, key);
+        Ok(len)
+    }
+
+    pub fn pop(
+        &mut self,
+        key: &[u8],
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<Vec<Bytes>>, DbError> {
+        let popped = match self.get_list_mut(key)? {
+            Some(list) => {
+                let n = count.min(list.len());
+                let popped = match end {
+                    ListEnd::Left => list.drain(..n).collect(),
+                    ListEnd::Right => list.drain(list.len() - n..).rev().collect(),
+                };
+                Some(popped)
+            }
+            None => None,
+        };
+        if popped.as_ref().is_some_and(|p: &Vec<Bytes>| !p.is_empty()) {
+            let event = match end {
+                ListEnd::Left => "lpop",
+                ListEnd::Right => "rpop",
+            };
+            self.watched.touch(key);
+            self.notify(EventClass::List, event, key);
+        }
+        self.remove_if_empty(key);
+        Ok(popped)
+    }
+
+    pub fn llen(&mut self, key: &[u8]) -> Result<i64, DbError> {
+        Ok(self.get_list(key)?.map_or(0, |l| l.len() as i64))
+    }
+
+    pub fn lrange(&mut self, key: &[u8], start: i64, stop: i64) -> Result<Vec<Bytes>, DbError> {
+        let list = match self.get_list(key)? {
+            Some(list) => list,
+            None => return Ok(vec![]),
+        };
+        match normalize_range(start, stop, list.len()) {
+            Some((from, to)) => Ok(list.range(from..to).cloned().collect()),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn lindex(&mut self, key: &[u8], index: i64) -> Result<Option<Bytes>, DbError> {
+        let list = match self.get_list(key)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+        Ok(normalize_index(index, list.len()).map(|i| list[i].clone()))
+    }
+
+    pub fn lset(&mut self, key: &[u8], index: i64, element: Bytes) -> Result<(), DbError> {
+        let list = self.get_list_mut(key)?.ok_or(DbError::NoSuchKey)?;
+        let idx = normalize_index(index, list.len()).ok_or(DbError::IndexOutOfRange)?;
+        list[idx] = element;
+        self.watched.touch(key);
+        self.notify(EventClass::List, "lset", key);
+        Ok(())
+    }
+
+    /// Removes up to `count` occurrences of `element`; a positive count scans
+    /// from the head, a negative one from the tail and zero removes them all.
+    pub fn lrem(&mut self, key: &[u8], count: i64, element: &[u8]) -> Result<i64, DbError> {
+        let list = match self.get_list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(0),
+        };
+        let limit = if count == 0 {
+            usize::MAX
+        } else {
+            count.unsigned_abs() as usize
+        };
+        let mut removed = 0;
+        if count >= 0 {
+            let mut i = 0;
+            while i < list.len() && removed < limit {
+                if list[i] == element {
+                    list.remove(i);
+                    removed += 1;
+                } else {
+                    i += 1;
+                }
+            }
+        } else {
+            let mut i = list.len();
+            while i > 0 && removed < limit {
+                i -= 1;
+                if list[i] == element {
+                    list.remove(i);
+                    removed += 1;
+                }
+            }
+        }
+        if removed > 0 {
+            self.watched.touch(key);
+            self.notify(EventClass::List, "lrem", key);
+        }
+        self.remove_if_empty(key);
+        Ok(removed as i64)
+    }
+
+    pub fn ltrim(&mut self, key: &[u8], start: i64, stop: i64) -> Result<(), DbError> {
+        if let Some(list) = self.get_list_mut(key)? {
+            let len = list.len();
+            match normalize_range(start, stop, len) {
+                Some((from, to)) => {
+                    list.truncate(to);
+                    list.drain(..from);
+                }
+                None => list.clear(),
+            }
+            if list.len() != len {
+                self.watched.touch(key);
+            }
+            self.notify(EventClass::List, "ltrim", key);
+        }
+        self.remove_if_empty(key);
+        Ok(())
+    }
+
+    /// Returns the new length, `-1` when the pivot is missing and `0` when the
+    /// key does not exist.
+    pub fn linsert(
+        &mut self,
+        key: &[u8],
+        before: bool,
+        pivot: &[u8],
+        element: Bytes,
+    ) -> Result<i64, DbError> {
+        let list = match self.get_list_mut(key)? {
+            Some(list) => list,
+            None => return Ok(0),
+        };
+        match list.iter().position(|e| e == pivot) {
+            Some(pos) => {
+                list.insert(if before { pos } else { pos + 1 }, element);
+                let len = list.len() as i64;
+                self.watched.touch(key);
+                self.blocked.signal(key);
+                self.notify(EventClass::List, "linsert", key);
+                Ok(len)
+            }
+            None => Ok(-1),
+        }
+    }
+
+    /// Atomically pops from one end of `source` and pushes onto `to` of
+    /// `destination`, which may be the same list.
+    pub fn lmove(
+        &mut self,
+        source: &[u8],
+        destination: &[u8],
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<Bytes>, DbError> {
+        if self.get_list(source)?.is_none() {
+            return Ok(None);
+        }
+        // Fail before popping so a mistyped destination loses nothing.
+        self.get_list(destination)?;
+        let element = match self.pop(source, from, 1)? {
+            Some(mut popped) => popped.pop(),
+            None => None,
+        };
+        if let Some(element) = &element {
+            self.push(destination, to, std::slice::from_ref(element))?;
+        }
+        Ok(element)
+    }
+
+    /// Pops up to `count` elements from the first non-empty list in `keys`.
+    pub fn lmpop(
+        &mut self,
+        keys: &[Bytes],
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<(Bytes, Vec<Bytes>)>, DbError> {
+        for key in keys {
+            if let Some(popped) = self.pop(key, end, count)? {
+                return Ok(Some((key.clone(), popped)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<Bytes> {
+        items.iter().map(|s| Bytes::from(s.to_string())).collect()
+    }
+
+    #[test]
+    fn test_push_and_range() {
+        let mut ks = Keyspace::default();
+        assert_eq!(ks.push(b"l", ListEnd::Right, &strings(&["a", "b"])), Ok(2));
+        assert_eq!(ks.push(b"l", ListEnd::Left, &strings(&["z"])), Ok(3));
+        assert_eq!(ks.lrange(b"l", 0, -1), Ok(strings(&["z", "a", "b"])));
+        assert_eq!(ks.lrange(b"l", -2, 10), Ok(strings(&["a", "b"])));
+        assert_eq!(ks.lrange(b"l", 5, 10), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_pop_removes_empty_key() {
+        let mut ks = Keyspace::default();
+        ks.push(b"l", ListEnd::Right, &strings(&["a", "b", "c"]))
+            .unwrap();
+        assert_eq!(
+            ks.pop(b"l", ListEnd::Right, 2),
+            Ok(Some(strings(&["c", "b"])))
+        );
+        assert_eq!(ks.pop(b"l", ListEnd::Left, 5), Ok(Some(strings(&["a"]))));
+        assert!(ks.get(b"l").is_none());
+        assert_eq!(ks.pop(b"l", ListEnd::Left, 1), Ok(None));
+    }
+
+    #[test]
+    fn test_lrem_directions() {
+        let mut ks = Keyspace::default();
+        ks.push(b"l", ListEnd::Right, &strings(&["x", "a", "x", "b", "x"]))
+            .unwrap();
+        assert_eq!(ks.lrem(b"l", -2, b"x"), Ok(2));
+        assert_eq!(ks.lrange(b"l", 0, -1), Ok(strings(&["x", "a", "b"])));
+        assert_eq!(ks.lrem(b"l", 0, b"x"), Ok(1));
+        assert_eq!(ks.lrange(b"l", 0, -1), Ok(strings(&["a", "b"])));
+    }
+
+    #[test]
+    fn test_ltrim_and_linsert() {
+        let mut ks = Keyspace::default();
+        ks.push(b"l", ListEnd::Right, &strings(&["a", "b", "c", "d"]))
+            .unwrap();
+        ks.ltrim(b"l", 1, -2).unwrap();
+        assert_eq!(ks.lrange(b"l", 0, -1), Ok(strings(&["b", "c"])));
+        assert_eq!(ks.linsert(b"l", true, b"c", Bytes::from("x")), Ok(3));
+        assert_eq!(ks.linsert(b"l", false, b"nope", Bytes::from("y")), Ok(-1));
+        assert_eq!(ks.lrange(b"l", 0, -1), Ok(strings(&["b", "x", "c"])));
+    }
+
+    #[test]
+    fn test_lmove_and_lmpop() {
+        let mut ks = Keyspace::default();
+        ks.push(b"a", ListEnd::Right, &strings(&["1", "2", "3"]))
+            .unwrap();
+        assert_eq!(
+            ks.lmove(b"a", b"b", ListEnd::Right, ListEnd::Left),
+            Ok(Some(Bytes::from("3")))
+        );
+        assert_eq!(
+            ks.lmove(b"a", b"a", ListEnd::Left, ListEnd::Right),
+            Ok(Some(Bytes::from("1")))
+        );
+        assert_eq!(ks.lrange(b"a", 0, -1), Ok(strings(&["2", "1"])));
+        assert_eq!(
+            ks.lmove(b"none", b"a", ListEnd::Left, ListEnd::Left),
+            Ok(None)
+        );
+        let keys = strings(&["none", "b", "a"]);
+        assert_eq!(
+            ks.lmpop(&keys, ListEnd::Left, 5),
+            Ok(Some((Bytes::from("b"), strings(&["3"]))))
+        );
+        assert!(ks.get(b"b").is_none());
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut ks = Keyspace::default();
+        ks.insert(
+            Bytes::from("s"),
+            crate::store::cache::RespEntry::new(
+                crate::store::cache::EntryValue::Str("v".into()),
+                None,
+            ),
+        );
+        assert_eq!(ks.llen(b"s"), Err(DbError::WrongType));
+        assert_eq!(
+            ks.push(b"s", ListEnd::Left, &strings(&["a"])),
+            Err(DbError::WrongType)
+        );
+    }
+}
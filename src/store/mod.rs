@@ -1,3 +1,27 @@
+pub mod bitmap;
+pub mod blocking;
 pub mod cache;
+pub mod expire;
+pub mod geo;
+pub mod geohash;
+pub mod glob;
+pub mod hash;
+pub mod hyperloglog;
+pub mod keys;
+pub mod lcs;
+pub mod list;
+pub mod notify;
+pub mod numeric;
+pub mod pubsub;
+pub mod random;
+pub mod scan;
+pub mod set;
+pub mod skiplist;
+pub mod slot;
+pub mod stream;
+pub mod stream_group;
+pub mod string;
+pub mod watch;
+pub mod zset;
 
 pub use cache::Db;
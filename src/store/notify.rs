@@ -0,0 +1,253 @@
+use std::{fmt, sync::Arc};
+
+use bytes::Bytes;
+
+use super::{cache::Keyspace, pubsub::Broker};
+
+/// The database index in notification channel names. There is only one.
+const DB_INDEX: u32 = 0;
+
+/// The classes a keyspace event belongs to, each enabled by a letter of
+/// `notify-keyspace-events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventClass {
+    /// `g`: DEL, EXPIRE, RENAME and other type-independent commands.
+    Generic,
+    /// `$`
+    String,
+    /// `l`
+    List,
+    /// `s`
+    Set,
+    /// `h`
+    Hash,
+    /// `z`
+    ZSet,
+    /// `x`: a key removed because its TTL passed.
+    Expired,
+    /// `e`: a key removed to free memory. Nothing is evicted without a
+    /// memory limit, so no such event is raised yet.
+    Evicted,
+    /// `t`
+    Stream,
+    /// `n`: a key created. Not part of `A`.
+    New,
+}
+
+impl EventClass {
+    const fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+const KEYSPACE: u16 = 1 << 12;
+const KEYEVENT: u16 = 1 << 13;
+/// What `A` stands for: every class but `n`.
+const ALL: u16 = EventClass::New.bit() - 1;
+/// Class letters in the order Redis prints them.
+const LETTERS: [(char, EventClass); 10] = [
+    ('g', EventClass::Generic),
+    ('$', EventClass::String),
+    ('l', EventClass::List),
+    ('s', EventClass::Set),
+    ('h', EventClass::Hash),
+    ('z', EventClass::ZSet),
+    ('x', EventClass::Expired),
+    ('e', EventClass::Evicted),
+    ('t', EventClass::Stream),
+    ('n', EventClass::New),
+];
+
+/// The `notify-keyspace-events` setting: which classes of events to
+/// publish, and whether on `__keyspace@0__:<key>` (`K`), on
+/// `__keyevent@0__:<event>` (`E`) or both. Without `K` or `E` nothing is
+/// published, whatever classes are on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NotifyFlags(u16);
+
+impl NotifyFlags {
+    /// Parses a flag string like `KEA` or `Elg`. `None` if it holds a
+    /// letter that names no class.
+    pub fn parse(flags: &str) -> Option<NotifyFlags> {
+        flags
+            .chars()
+            .try_fold(0, |bits, c| {
+                let bit = match c {
+                    'A' => ALL,
+                    'K' => KEYSPACE,
+                    'E' => KEYEVENT,
+                    // Key-miss events are accepted but never raised.
+                    'm' => 0,
+                    // Module events: there are no modules.
+                    'd' => 0,
+                    _ => LETTERS.iter().find(|(letter, _)| *letter == c)?.1.bit(),
+                };
+                Some(bits | bit)
+            })
+            .map(NotifyFlags)
+    }
+
+    fn enabled(self, class: EventClass) -> bool {
+        self.0 & class.bit() != 0 && self.0 & (KEYSPACE | KEYEVENT) != 0
+    }
+}
+
+impl fmt::Display for NotifyFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 & ALL == ALL {
+            f.write_str("A")?;
+            if self.0 & EventClass::New.bit() != 0 {
+                f.write_str("n")?;
+            }
+        } else {
+            for (letter, class) in LETTERS {
+                if self.0 & class.bit() != 0 {
+                    write!(f, "{letter}")?;
+                }
+            }
+        }
+        if self.0 & KEYSPACE != 0 {
+            f.write_str("K")?;
+        }
+        if self.0 & KEYEVENT != 0 {
+            f.write_str("E")?;
+        }
+        Ok(())
+    }
+}
+
+/// Publishes keyspace events through the broker, as the writes that raise
+/// them happen: subscribers see them in the order the key space changed.
+#[derive(Debug, Default)]
+pub struct Notifier {
+    pub flags: NotifyFlags,
+    broker: Arc<Broker>,
+}
+
+impl Notifier {
+    pub fn new(broker: Arc<Broker>) -> Self {
+        Notifier {
+            flags: NotifyFlags::default(),
+            broker,
+        }
+    }
+}
+
+impl Keyspace {
+    /// Raises `event` on `key`, if its class is enabled.
+    pub(super) fn notify(&self, class: EventClass, event: &str, key: &[u8]) {
+        let Notifier { flags, broker } = &self.notifier;
+        if !flags.enabled(class) {
+            return;
+        }
+        if flags.0 & KEYSPACE != 0 {
+            let mut channel = format!("__keyspace@{DB_INDEX}__:").into_bytes();
+            channel.extend_from_slice(key);
+            broker.publish(
+                &Bytes::from(channel),
+                &Bytes::copy_from_slice(event.as_bytes()),
+            );
+        }
+        if flags.0 & KEYEVENT != 0 {
+            let channel = format!("__keyevent@{DB_INDEX}__:{event}");
+            broker.publish(&Bytes::from(channel), &Bytes::copy_from_slice(key));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{
+        cache::DbError,
+        list::ListEnd,
+        pubsub::{Message, SubscriptionKind},
+        string::SetOptions,
+    };
+
+    #[test]
+    fn test_flags_round_trip() {
+        let flags = NotifyFlags::parse("KEA").unwrap();
+        assert_eq!(flags.to_string(), "AKE");
+        assert_eq!(NotifyFlags::parse("Elg$").unwrap().to_string(), "g$lE");
+        assert_eq!(NotifyFlags::parse("AnK").unwrap().to_string(), "AnK");
+        assert_eq!(NotifyFlags::parse("").unwrap().to_string(), "");
+        assert_eq!(NotifyFlags::parse("Q"), None);
+        assert!(!NotifyFlags::parse("A").unwrap().enabled(EventClass::String));
+    }
+
+    #[test]
+    fn test_events_follow_flags() {
+        let mut ks = Keyspace::default();
+        let broker = ks.notifier.broker.clone();
+        let (subscriber, mut mailbox) = broker.subscriber();
+        broker.subscribe(
+            &subscriber,
+            &Bytes::from("__key*__:*"),
+            SubscriptionKind::Pattern,
+        );
+        ks.notifier.flags = NotifyFlags::parse("Kg").unwrap();
+        ks.set(b"k", Bytes::from("v"), &SetOptions::default())
+            .unwrap();
+        assert!(mailbox.try_recv().is_err());
+        ks.del(&[Bytes::from("k")]);
+        let Message {
+            channel, payload, ..
+        } = mailbox.try_recv().unwrap();
+        assert_eq!(
+            (&channel[..], &payload[..]),
+            (&b"__keyspace@0__:k"[..], &b"del"[..])
+        );
+        assert!(mailbox.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_wrong_type_write_is_not_a_change() {
+        let mut ks = Keyspace::default();
+        ks.set(b"k", Bytes::from("v"), &SetOptions::default())
+            .unwrap();
+        let id = ks.watched.new_client();
+        ks.watch(id, &Bytes::from("k"));
+        let broker = ks.notifier.broker.clone();
+        let (subscriber, mut mailbox) = broker.subscriber();
+        broker.subscribe(&subscriber, &Bytes::from("*"), SubscriptionKind::Pattern);
+        ks.notifier.flags = NotifyFlags::parse("KEAn").unwrap();
+        assert_eq!(ks.sadd(b"k", &[Bytes::from("m")]), Err(DbError::WrongType));
+        assert!(!ks.watched.is_dirty(id));
+        assert!(mailbox.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_keyevent_channels() {
+        let mut ks = Keyspace::default();
+        let broker = ks.notifier.broker.clone();
+        let (subscriber, mut mailbox) = broker.subscriber();
+        broker.subscribe(
+            &subscriber,
+            &Bytes::from("__keyevent@0__:*"),
+            SubscriptionKind::Pattern,
+        );
+        ks.notifier.flags = NotifyFlags::parse("EAn").unwrap();
+        ks.push(b"l", ListEnd::Left, &[Bytes::from("a")]).unwrap();
+        ks.rename(b"l", b"m", false).unwrap();
+        let events: Vec<_> = std::iter::from_fn(|| mailbox.try_recv().ok())
+            .map(
+                |Message {
+                     channel, payload, ..
+                 }| (channel, payload),
+            )
+            .collect();
+        let expected = [
+            ("new", "l"),
+            ("lpush", "l"),
+            ("rename_from", "l"),
+            ("new", "m"),
+            ("rename_to", "m"),
+        ]
+        .map(|(event, key)| {
+            let channel = format!("__keyevent@0__:{event}");
+            (Bytes::from(channel), Bytes::from(key))
+        });
+        assert_eq!(events, expected);
+    }
+}
@@ -0,0 +1,21 @@
+/// Parses a float the way Redis does for scores and increments: `inf` and
+/// `-inf` are accepted, `nan` and surrounding whitespace are not.
+pub fn parse_float(s: &[u8]) -> Option<f64> {
+    let s = std::str::from_utf8(s).ok()?;
+    if s.is_empty() || s.trim() != s {
+        return None;
+    }
+    match s.parse::<f64>() {
+        Ok(f) if !f.is_nan() => Some(f),
+        _ => None,
+    }
+}
+
+/// Formats a float in the shortest form that round-trips, without exponent
+/// notation, matching the human readable output of Redis.
+pub fn format_float(f: f64) -> String {
+    if f.is_infinite() {
+        return if f > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    format!("{}", f)
+}
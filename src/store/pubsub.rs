@@ -0,0 +1,325 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use bytes::Bytes;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use super::{glob::glob_match, slot::key_hash_slot};
+
+/// What a subscription is to: a channel by name, every channel matching a
+/// glob-style pattern, or a shard channel. Shard channels live in a
+/// namespace of their own, scoped by the hash slot of their name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionKind {
+    Channel,
+    Pattern,
+    Shard,
+}
+
+/// A published message as delivered to one subscriber.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub route: Route,
+    pub channel: Bytes,
+    pub payload: Bytes,
+}
+
+/// How a message reached its subscriber.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    Channel,
+    Pattern(Bytes),
+    Shard,
+}
+
+/// A connection's mailbox: messages for any channel or pattern it
+/// subscribes to land in the receiving half it was handed with this.
+#[derive(Debug, Clone)]
+pub struct Subscriber {
+    pub id: u64,
+    sender: UnboundedSender<Message>,
+}
+
+type Subscribers = HashMap<u64, Subscriber>;
+
+#[derive(Debug, Default)]
+struct Registry {
+    next_id: u64,
+    channels: HashMap<Bytes, Subscribers>,
+    patterns: HashMap<Bytes, Subscribers>,
+    /// Shard channels grouped by slot, so a slot's can be found together.
+    shard_channels: HashMap<u16, HashMap<Bytes, Subscribers>>,
+}
+
+impl Registry {
+    /// The channels of `kind` that `channel` is filed among.
+    fn table(
+        &mut self,
+        kind: SubscriptionKind,
+        channel: &[u8],
+    ) -> &mut HashMap<Bytes, Subscribers> {
+        match kind {
+            SubscriptionKind::Channel => &mut self.channels,
+            SubscriptionKind::Pattern => &mut self.patterns,
+            SubscriptionKind::Shard => self
+                .shard_channels
+                .entry(key_hash_slot(channel))
+                .or_default(),
+        }
+    }
+
+    fn shard_table(&self, channel: &[u8]) -> Option<&HashMap<Bytes, Subscribers>> {
+        self.shard_channels.get(&key_hash_slot(channel))
+    }
+}
+
+/// Fans published messages out to the connections subscribed to them.
+///
+/// Publishing never waits on a subscriber: each has an unbounded mailbox
+/// its connection drains while it is idle, so a slow reader only grows
+/// its own backlog. The broker has its own lock, independent of the key
+/// space, and never holds it across an await.
+#[derive(Debug, Default)]
+pub struct Broker {
+    registry: Mutex<Registry>,
+}
+
+impl Broker {
+    fn registry(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().expect("broker lock poisoned")
+    }
+
+    /// A new subscriber, subscribed to nothing yet, and its mailbox.
+    pub fn subscriber(&self) -> (Subscriber, UnboundedReceiver<Message>) {
+        let mut registry = self.registry();
+        registry.next_id += 1;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let subscriber = Subscriber {
+            id: registry.next_id,
+            sender,
+        };
+        (subscriber, receiver)
+    }
+
+    /// Subscribes to `channel`, a pattern if `kind` says so. Returns
+    /// whether the subscription is new.
+    pub fn subscribe(
+        &self,
+        subscriber: &Subscriber,
+        channel: &Bytes,
+        kind: SubscriptionKind,
+    ) -> bool {
+        self.registry()
+            .table(kind, channel)
+            .entry(channel.clone())
+            .or_default()
+            .insert(subscriber.id, subscriber.clone())
+            .is_none()
+    }
+
+    /// Returns whether the subscriber was subscribed.
+    pub fn unsubscribe(&self, id: u64, channel: &[u8], kind: SubscriptionKind) -> bool {
+        let mut registry = self.registry();
+        let table = registry.table(kind, channel);
+        let removed = match table.get_mut(channel) {
+            Some(subscribers) => subscribers.remove(&id).is_some(),
+            None => false,
+        };
+        if table.get(channel).is_some_and(HashMap::is_empty) {
+            table.remove(channel);
+        }
+        if table.is_empty() && kind == SubscriptionKind::Shard {
+            registry.shard_channels.remove(&key_hash_slot(channel));
+        }
+        removed
+    }
+
+    /// PUBLISH. Returns how many subscribers were handed the message, a
+    /// connection counting once per subscription that matched.
+    pub fn publish(&self, channel: &Bytes, payload: &Bytes) -> usize {
+        let registry = self.registry();
+        let mut receivers = 0;
+        for subscriber in registry
+            .channels
+            .get(channel)
+            .into_iter()
+            .flat_map(|s| s.values())
+        {
+            let message = Message {
+                route: Route::Channel,
+                channel: channel.clone(),
+                payload: payload.clone(),
+            };
+            // A closed mailbox belongs to a connection on its way out.
+            if subscriber.sender.send(message).is_ok() {
+                receivers += 1;
+            }
+        }
+        for (pattern, subscribers) in &registry.patterns {
+            if !glob_match(pattern, channel) {
+                continue;
+            }
+            for subscriber in subscribers.values() {
+                let message = Message {
+                    route: Route::Pattern(pattern.clone()),
+                    channel: channel.clone(),
+                    payload: payload.clone(),
+                };
+                if subscriber.sender.send(message).is_ok() {
+                    receivers += 1;
+                }
+            }
+        }
+        receivers
+    }
+
+    /// SPUBLISH: like PUBLISH, but only to the subscribers of the shard
+    /// channel; patterns never match shard channels.
+    pub fn spublish(&self, channel: &Bytes, payload: &Bytes) -> usize {
+        let registry = self.registry();
+        let subscribers = registry
+            .shard_table(channel)
+            .and_then(|table| table.get(channel));
+        let mut receivers = 0;
+        for subscriber in subscribers.into_iter().flat_map(|s| s.values()) {
+            let message = Message {
+                route: Route::Shard,
+                channel: channel.clone(),
+                payload: payload.clone(),
+            };
+            if subscriber.sender.send(message).is_ok() {
+                receivers += 1;
+            }
+        }
+        receivers
+    }
+
+    /// PUBSUB CHANNELS and SHARDCHANNELS: the channels of `kind` with at
+    /// least one subscriber, matching `pattern` if given.
+    pub fn channels(&self, kind: SubscriptionKind, pattern: Option<&[u8]>) -> Vec<Bytes> {
+        let registry = self.registry();
+        let tables: Vec<_> = match kind {
+            SubscriptionKind::Shard => registry.shard_channels.values().collect(),
+            _ => vec![&registry.channels],
+        };
+        tables
+            .into_iter()
+            .flat_map(HashMap::keys)
+            .filter(|channel| match pattern {
+                Some(pattern) => glob_match(pattern, channel),
+                None => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// PUBSUB NUMSUB or SHARDNUMSUB for one channel.
+    pub fn numsub(&self, kind: SubscriptionKind, channel: &[u8]) -> usize {
+        let registry = self.registry();
+        let table = match kind {
+            SubscriptionKind::Shard => registry.shard_table(channel),
+            _ => Some(&registry.channels),
+        };
+        table
+            .and_then(|table| table.get(channel))
+            .map_or(0, HashMap::len)
+    }
+
+    /// PUBSUB NUMPAT: distinct patterns subscribed to, by anyone.
+    pub fn numpat(&self) -> usize {
+        self.registry().patterns.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_to_channels_and_patterns() {
+        let broker = Broker::default();
+        let (a, mut a_box) = broker.subscriber();
+        let (b, mut b_box) = broker.subscriber();
+        let news = Bytes::from("news.tech");
+        assert!(broker.subscribe(&a, &news, SubscriptionKind::Channel));
+        assert!(!broker.subscribe(&a, &news, SubscriptionKind::Channel));
+        assert!(broker.subscribe(&b, &Bytes::from("news.*"), SubscriptionKind::Pattern));
+        assert_eq!(broker.publish(&news, &Bytes::from("hi")), 2);
+        assert_eq!(
+            a_box.try_recv(),
+            Ok(Message {
+                route: Route::Channel,
+                channel: news.clone(),
+                payload: Bytes::from("hi"),
+            })
+        );
+        let message = b_box.try_recv().unwrap();
+        assert_eq!(message.route, Route::Pattern(Bytes::from("news.*")));
+        assert_eq!(broker.publish(&Bytes::from("sports"), &Bytes::from("x")), 0);
+        assert!(a_box.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_unsubscribe_forgets_empty_channels() {
+        let broker = Broker::default();
+        let (a, _a_box) = broker.subscriber();
+        let (b, _b_box) = broker.subscriber();
+        let channel = Bytes::from("c");
+        broker.subscribe(&a, &channel, SubscriptionKind::Channel);
+        broker.subscribe(&b, &channel, SubscriptionKind::Channel);
+        assert_eq!(broker.numsub(SubscriptionKind::Channel, b"c"), 2);
+        assert!(broker.unsubscribe(a.id, b"c", SubscriptionKind::Channel));
+        assert!(!broker.unsubscribe(a.id, b"c", SubscriptionKind::Channel));
+        assert_eq!(
+            broker.channels(SubscriptionKind::Channel, None),
+            vec![channel]
+        );
+        assert!(broker.unsubscribe(b.id, b"c", SubscriptionKind::Channel));
+        assert!(broker.channels(SubscriptionKind::Channel, None).is_empty());
+        assert_eq!(broker.numsub(SubscriptionKind::Channel, b"c"), 0);
+    }
+
+    #[test]
+    fn test_pubsub_introspection() {
+        let broker = Broker::default();
+        let (a, _a_box) = broker.subscriber();
+        for channel in ["news.tech", "news.art", "weather"] {
+            broker.subscribe(&a, &Bytes::from(channel), SubscriptionKind::Channel);
+        }
+        broker.subscribe(&a, &Bytes::from("w*"), SubscriptionKind::Pattern);
+        let mut channels = broker.channels(SubscriptionKind::Channel, Some(b"news.*"));
+        channels.sort();
+        assert_eq!(
+            channels,
+            [Bytes::from("news.art"), Bytes::from("news.tech")]
+        );
+        assert_eq!(broker.numpat(), 1);
+    }
+
+    #[test]
+    fn test_shard_channels_are_separate() {
+        let broker = Broker::default();
+        let (a, mut a_box) = broker.subscriber();
+        let (b, mut b_box) = broker.subscriber();
+        let channel = Bytes::from("{user1}.events");
+        broker.subscribe(&a, &channel, SubscriptionKind::Shard);
+        broker.subscribe(&b, &channel, SubscriptionKind::Channel);
+        broker.subscribe(&b, &Bytes::from("*"), SubscriptionKind::Pattern);
+        assert_eq!(broker.spublish(&channel, &Bytes::from("s")), 1);
+        assert_eq!(a_box.try_recv().unwrap().route, Route::Shard);
+        assert!(b_box.try_recv().is_err());
+        assert_eq!(broker.publish(&channel, &Bytes::from("p")), 2);
+        assert!(a_box.try_recv().is_err());
+        assert_eq!(
+            broker.channels(SubscriptionKind::Shard, Some(b"{user1}*")),
+            vec![channel.clone()]
+        );
+        assert_eq!(broker.numsub(SubscriptionKind::Shard, &channel), 1);
+        broker.unsubscribe(a.id, &channel, SubscriptionKind::Shard);
+        assert!(broker.channels(SubscriptionKind::Shard, None).is_empty());
+        assert_eq!(broker.numsub(SubscriptionKind::Channel, &channel), 1);
+        assert_eq!(broker.numpat(), 1);
+    }
+}
@@ -0,0 +1,46 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+thread_local! {
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0x9E37_79B9_7F4A_7C15);
+    hasher.finish() | 1
+}
+
+/// xorshift64* seeded per thread; good enough for picking random members,
+/// not for anything security related.
+pub fn next_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+/// Returns a random index in `0..n`. `n` must be non-zero.
+pub fn below(n: usize) -> usize {
+    (next_u64() % n as u64) as usize
+}
+
+/// Picks `count` distinct indices out of `0..n` (all of them when
+/// `count >= n`) using a partial Fisher-Yates shuffle.
+pub fn distinct_indices(n: usize, count: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..n).collect();
+    let count = count.min(n);
+    for i in 0..count {
+        let j = i + below(n - i);
+        indices.swap(i, j);
+    }
+    indices.truncate(count);
+    indices
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, Keyspace},
+    glob::glob_match,
+};
+
+/// Position of a name in scan order: FNV-1a with a final avalanche step.
+/// Unlike the hash the tables use internally it is fixed, so it does not
+/// move when a table grows or shrinks.
+pub fn scan_hash(name: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in name {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h
+}
+
+/// One page of a cursor scan and the cursor to continue from, 0 once the
+/// scan is complete.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanPage<T> {
+    pub cursor: u64,
+    pub items: Vec<T>,
+}
+
+impl<T> Default for ScanPage<T> {
+    fn default() -> Self {
+        ScanPage {
+            cursor: 0,
+            items: Vec::new(),
+        }
+    }
+}
+
+/// The names of a collection in scan order, kept up to date alongside it so
+/// a page costs O(COUNT + log N) rather than a pass over the collection.
+/// Names sharing a hash share a bucket.
+#[derive(Debug, Clone, Default)]
+pub struct ScanIndex {
+    buckets: BTreeMap<u64, Vec<Bytes>>,
+}
+
+impl ScanIndex {
+    /// Adds `name`, which the caller knows is not indexed yet.
+    pub fn insert(&mut self, name: Bytes) {
+        self.buckets.entry(scan_hash(&name)).or_default().push(name);
+    }
+
+    pub fn remove(&mut self, name: &[u8]) {
+        let hash = scan_hash(name);
+        if let Some(bucket) = self.buckets.get_mut(&hash) {
+            bucket.retain(|n| n != name);
+            if bucket.is_empty() {
+                self.buckets.remove(&hash);
+            }
+        }
+    }
+
+    /// Returns the names of the first buckets at or after `cursor`, at least
+    /// `count` of them unless the scan runs out. Whole buckets go on a page,
+    /// so every name present for the whole scan is returned at least once no
+    /// matter how the collection changes in between.
+    fn page(&self, cursor: u64, count: usize) -> ScanPage<Bytes> {
+        let mut page = ScanPage::default();
+        for (&hash, bucket) in self.buckets.range(cursor..) {
+            if page.items.len() >= count.max(1) {
+                page.cursor = hash;
+                break;
+            }
+            page.items.extend(bucket.iter().cloned());
+        }
+        page
+    }
+}
+
+fn matches(pattern: Option<&[u8]>, name: &[u8]) -> bool {
+    pattern.is_none_or(|pattern| glob_match(pattern, name))
+}
+
+impl Keyspace {
+    /// SCAN over the top-level keys, optionally only those of `type_name`
+    /// as TYPE reports it.
+    pub fn scan(
+        &mut self,
+        cursor: u64,
+        count: usize,
+        pattern: Option<&[u8]>,
+        type_name: Option<&str>,
+    ) -> ScanPage<Bytes> {
+        let mut page = self.scan_index.page(cursor, count);
+        page.items.retain(|key| matches(pattern, key));
+        // Looking a key up evicts it if it expired since it was indexed.
+        page.items.retain(|key| match type_name {
+            Some(type_name) => self.type_name(key) == type_name,
+            None => self.get(key).is_some(),
+        });
+        page
+    }
+
+    pub fn hscan(
+        &mut self,
+        key: &[u8],
+        cursor: u64,
+        count: usize,
+        pattern: Option<&[u8]>,
+    ) -> Result<ScanPage<(Bytes, Bytes)>, DbError> {
+        let Some(hash) = self.get_hash(key)? else {
+            return Ok(ScanPage::default());
+        };
+        let page = hash.index.page(cursor, count);
+        let items = page
+            .items
+            .into_iter()
+            .filter(|field| matches(pattern, field))
+            .filter_map(|field| {
+                let value = hash.get(&field)?.clone();
+                Some((field, value))
+            })
+            .collect();
+        Ok(ScanPage {
+            cursor: page.cursor,
+            items,
+        })
+    }
+
+    pub fn sscan(
+        &mut self,
+        key: &[u8],
+        cursor: u64,
+        count: usize,
+        pattern: Option<&[u8]>,
+    ) -> Result<ScanPage<Bytes>, DbError> {
+        let Some(set) = self.get_set(key)? else {
+            return Ok(ScanPage::default());
+        };
+        let mut page = set.index.page(cursor, count);
+        page.items.retain(|member| matches(pattern, member));
+        Ok(page)
+    }
+
+    pub fn zscan(
+        &mut self,
+        key: &[u8],
+        cursor: u64,
+        count: usize,
+        pattern: Option<&[u8]>,
+    ) -> Result<ScanPage<(Bytes, f64)>, DbError> {
+        let Some(zset) = self.get_zset(key)? else {
+            return Ok(ScanPage::default());
+        };
+        let page = zset.index.page(cursor, count);
+        let items = page
+            .items
+            .into_iter()
+            .filter(|member| matches(pattern, member))
+            .filter_map(|member| {
+                let score = zset.score(&member)?;
+                Some((member, score))
+            })
+            .collect();
+        Ok(ScanPage {
+            cursor: page.cursor,
+            items,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn scan_all(ks: &mut Keyspace, key: &[u8], count: usize) -> Vec<Bytes> {
+        let mut found = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let page = ks.sscan(key, cursor, count, None).unwrap();
+            found.extend(page.items);
+            if page.cursor == 0 {
+                return found;
+            }
+            cursor = page.cursor;
+        }
+    }
+
+    #[test]
+    fn test_full_scan_returns_everything_once() {
+        let mut ks = Keyspace::default();
+        let members: Vec<Bytes> = (0..500).map(|i| format!("m{i}").into()).collect();
+        ks.sadd(b"s", &members).unwrap();
+        let mut found = scan_all(&mut ks, b"s", 7);
+        found.sort();
+        let mut expected = members.clone();
+        expected.sort();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_scan_survives_rehash() {
+        let mut ks = Keyspace::default();
+        let members: Vec<Bytes> = (0..100).map(|i| format!("m{i}").into()).collect();
+        ks.sadd(b"s", &members).unwrap();
+        let mut found = HashSet::new();
+        let mut cursor = 0;
+        for round in 0.. {
+            let page = ks.sscan(b"s", cursor, 10, None).unwrap();
+            found.extend(page.items);
+            // Grow the set well past its capacity so it rehashes mid-scan,
+            // and drop some members that were already returned.
+            if round < 3 {
+                let extra: Vec<Bytes> = (0..1000).map(|i| format!("x{round}-{i}").into()).collect();
+                ks.sadd(b"s", &extra).unwrap();
+                ks.srem(b"s", &members[..10 * round]).unwrap();
+            }
+            if page.cursor == 0 {
+                break;
+            }
+            cursor = page.cursor;
+        }
+        assert!(members[20..].iter().all(|m| found.contains(m)));
+    }
+
+    #[test]
+    fn test_keyspace_scan_while_modified() {
+        let mut ks = Keyspace::default();
+        let keys: Vec<Bytes> = (0..300).map(|i| format!("k{i}").into()).collect();
+        for key in &keys {
+            ks.sadd(key, &[Bytes::from("m")]).unwrap();
+        }
+        let mut found = HashSet::new();
+        let mut doomed = keys[..10].iter();
+        let mut cursor = 0;
+        for round in 0.. {
+            let page = ks.scan(cursor, 10, None, None);
+            found.extend(page.items);
+            // Add keys and delete some that come both before and after the
+            // cursor in scan order.
+            for i in 0..50 {
+                ks.sadd(format!("new{round}-{i}").as_bytes(), &[Bytes::from("m")])
+                    .unwrap();
+            }
+            if let Some(key) = doomed.next() {
+                ks.delete(key);
+            }
+            if page.cursor == 0 {
+                break;
+            }
+            cursor = page.cursor;
+        }
+        assert!(keys[10..].iter().all(|key| found.contains(key)));
+    }
+
+    #[test]
+    fn test_match_and_type_filters() {
+        let mut ks = Keyspace::default();
+        ks.sadd(b"set:1", &[Bytes::from("a")]).unwrap();
+        ks.sadd(b"set:2", &[Bytes::from("a")]).unwrap();
+        ks.hset(b"hash:1", &[(Bytes::from("f"), Bytes::from("v"))])
+            .unwrap();
+        let page = ks.scan(0, 100, Some(b"set:*"), None);
+        assert_eq!((page.cursor, page.items.len()), (0, 2));
+        let page = ks.scan(0, 100, None, Some("hash"));
+        assert_eq!(page.items, vec![Bytes::from("hash:1")]);
+        assert_eq!(ks.hscan(b"nope", 0, 10, None), Ok(ScanPage::default()));
+    }
+}
@@ -0,0 +1,317 @@
+use std::{collections::HashSet, ops::Deref};
+
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, EntryValue, Keyspace, RespEntry},
+    notify::EventClass,
+    random,
+    scan::ScanIndex,
+};
+
+/// A set value. Reads go straight to the set; writes go through here so the
+/// SSCAN order stays in step with it.
+#[derive(Debug, Clone, Default)]
+pub struct SetValue {
+    set: HashSet<Bytes>,
+    pub(super) index: ScanIndex,
+}
+
+impl SetValue {
+    pub fn insert(&mut self, member: Bytes) -> bool {
+        let added = self.set.insert(member.clone());
+        if added {
+            self.index.insert(member);
+        }
+        added
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        let removed = self.set.remove(member);
+        if removed {
+            self.index.remove(member);
+        }
+        removed
+    }
+}
+
+impl Deref for SetValue {
+    type Target = HashSet<Bytes>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.set
+    }
+}
+
+impl From<HashSet<Bytes>> for SetValue {
+    fn from(set: HashSet<Bytes>) -> Self {
+        let mut index = ScanIndex::default();
+        for member in &set {
+            index.insert(member.clone());
+        }
+        SetValue { set, index }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+impl Keyspace {
+    pub fn sadd(&mut self, key: &[u8], members: &[Bytes]) -> Result<i64, DbError> {
+        let set = self.set_entry(key)?;
+        let added = members.iter().filter(|m| set.insert((*m).clone())).count();
+        if added > 0 {
+            self.notify(EventClass::Set, "sadd", key);
+        }
+        Ok(added as i64)
+    }
+
+    pub fn srem(&mut self, key: &[u8], members: &[Bytes]) -> Result<i64, DbError> {
+        let removed = match self.get_set_mut(key)? {
+            Some(set) => members.iter().filter(|m| set.remove(m)).count(),
+            None => 0,
+        };
+        if removed > 0 {
+            self.watched.touch(key);
+            self.notify(EventClass::Set, "srem", key);
+        }
+        self.remove_if_empty(key);
+        Ok(removed as i64)
+    }
+
+    pub fn smembers(&mut self, key: &[u8]) -> Result<Vec<Bytes>, DbError> {
+        Ok(self
+            .get_set(key)?
+            .map(|s| s.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    pub fn sismember(&mut self, key: &[u8], member: &[u8]) -> Result<bool, DbError> {
+        Ok(self.get_set(key)?.is_some_and(|s| s.contains(member)))
+    }
+
+    pub fn smismember(&mut self, key: &[u8], members: &[Bytes]) -> Result<Vec<bool>, DbError> {
+        let set = self.get_set(key)?;
+        Ok(members
+            .iter()
+            .map(|m| set.is_some_and(|s| s.contains(m)))
+            .collect())
+    }
+
+    pub fn scard(&mut self, key: &[u8]) -> Result<i64, DbError> {
+        Ok(self.get_set(key)?.map_or(0, |s| s.len() as i64))
+    }
+
+    /// Removes and returns up to `count` random members.
+    pub fn spop(&mut self, key: &[u8], count: usize) -> Result<Vec<Bytes>, DbError> {
+        let popped = match self.get_set_mut(key)? {
+            Some(set) => {
+                let members: Vec<&Bytes> = set.iter().collect();
+                let picked: Vec<Bytes> = random::distinct_indices(members.len(), count)
+                    .into_iter()
+                    .map(|i| members[i].clone())
+                    .collect();
+                for member in &picked {
+                    set.remove(member);
+                }
+                picked
+            }
+            None => vec![],
+        };
+        if !popped.is_empty() {
+            self.watched.touch(key);
+            self.notify(EventClass::Set, "spop", key);
+        }
+        self.remove_if_empty(key);
+        Ok(popped)
+    }
+
+    /// Same contract as `hrandfield`: positive counts are distinct, negative
+    /// counts may repeat members.
+    pub fn srandmember(&mut self, key: &[u8], count: i64) -> Result<Vec<Bytes>, DbError> {
+        let set = match self.get_set(key)? {
+            Some(set) if !set.is_empty() => set,
+            _ => return Ok(vec![]),
+        };
+        let members: Vec<&Bytes> = set.iter().collect();
+        let indices = if count >= 0 {
+            random::distinct_indices(members.len(), count as usize)
+        } else {
+            (0..count.unsigned_abs())
+                .map(|_| random::below(members.len()))
+                .collect()
+        };
+        Ok(indices.into_iter().map(|i| members[i].clone()).collect())
+    }
+
+    pub fn smove(
+        &mut self,
+        source: &[u8],
+        destination: &[u8],
+        member: &[u8],
+    ) -> Result<bool, DbError> {
+        // Type check both sides before mutating anything.
+        self.get_set(destination)?;
+        let moved = match self.get_set_mut(source)? {
+            Some(set) => set.remove(member),
+            None => false,
+        };
+        if !moved {
+            return Ok(false);
+        }
+        self.watched.touch(source);
+        self.notify(EventClass::Set, "srem", source);
+        self.remove_if_empty(source);
+        if self
+            .set_entry(destination)?
+            .insert(Bytes::copy_from_slice(member))
+        {
+            self.notify(EventClass::Set, "sadd", destination);
+        }
+        Ok(true)
+    }
+
+    /// Computes the intersection, union or difference of the sets at `keys`.
+    /// Missing keys behave like empty sets.
+    pub fn set_algebra(&mut self, op: SetOp, keys: &[Bytes]) -> Result<HashSet<Bytes>, DbError> {
+        let mut sets = Vec::with_capacity(keys.len());
+        for key in keys {
+            sets.push(
+                self.get_set(key)?
+                    .map(|set| set.set.clone())
+                    .unwrap_or_default(),
+            );
+        }
+        let mut iter = sets.into_iter();
+        let first = iter.next().unwrap_or_default();
+        Ok(iter.fold(first, |acc, set| match op {
+            SetOp::Inter => acc.intersection(&set).cloned().collect(),
+            SetOp::Union => acc.union(&set).cloned().collect(),
+            SetOp::Diff => acc.difference(&set).cloned().collect(),
+        }))
+    }
+
+    /// Stores the result of `set_algebra` at `destination`, replacing whatever
+    /// was there, and returns its cardinality.
+    pub fn set_algebra_store(
+        &mut self,
+        op: SetOp,
+        destination: &[u8],
+        keys: &[Bytes],
+    ) -> Result<i64, DbError> {
+        let result = self.set_algebra(op, keys)?;
+        let len = result.len() as i64;
+        if result.is_empty() {
+            self.delete(destination);
+        } else {
+            self.insert(
+                Bytes::copy_from_slice(destination),
+                RespEntry::new(EntryValue::Set(result.into()), None),
+            );
+            let event = match op {
+                SetOp::Inter => "sinterstore",
+                SetOp::Union => "sunionstore",
+                SetOp::Diff => "sdiffstore",
+            };
+            self.notify(EventClass::Set, event, destination);
+        }
+        Ok(len)
+    }
+
+    /// Cardinality of the intersection, stopping early once `limit` is hit
+    /// (zero means no limit).
+    pub fn sintercard(&mut self, keys: &[Bytes], limit: usize) -> Result<i64, DbError> {
+        let mut sets = Vec::with_capacity(keys.len());
+        for key in keys {
+            match self.get_set(key)? {
+                Some(set) => sets.push(set.set.clone()),
+                None => sets.push(HashSet::new()),
+            }
+        }
+        sets.sort_by_key(|s| s.len());
+        let (smallest, rest) = match sets.split_first() {
+            Some(split) => split,
+            None => return Ok(0),
+        };
+        let mut count = 0;
+        for member in smallest {
+            if rest.iter().all(|s| s.contains(member)) {
+                count += 1;
+                if limit != 0 && count >= limit {
+                    break;
+                }
+            }
+        }
+        Ok(count as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<Bytes> {
+        items.iter().map(|s| Bytes::from(s.to_string())).collect()
+    }
+
+    fn sorted(set: HashSet<Bytes>) -> Vec<Bytes> {
+        let mut items: Vec<Bytes> = set.into_iter().collect();
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let mut ks = Keyspace::default();
+        ks.sadd(b"a", &strings(&["1", "2", "3"])).unwrap();
+        ks.sadd(b"b", &strings(&["2", "3", "4"])).unwrap();
+        let keys = strings(&["a", "b"]);
+        assert_eq!(
+            sorted(ks.set_algebra(SetOp::Inter, &keys).unwrap()),
+            strings(&["2", "3"])
+        );
+        assert_eq!(
+            sorted(ks.set_algebra(SetOp::Union, &keys).unwrap()),
+            strings(&["1", "2", "3", "4"])
+        );
+        assert_eq!(
+            sorted(ks.set_algebra(SetOp::Diff, &keys).unwrap()),
+            strings(&["1"])
+        );
+        assert_eq!(ks.sintercard(&keys, 1), Ok(1));
+        assert_eq!(ks.sintercard(&strings(&["a", "missing"]), 0), Ok(0));
+    }
+
+    #[test]
+    fn test_store_overwrites_and_deletes() {
+        let mut ks = Keyspace::default();
+        ks.sadd(b"a", &strings(&["1"])).unwrap();
+        ks.push(b"dst", crate::store::list::ListEnd::Left, &strings(&["x"]))
+            .unwrap();
+        assert_eq!(
+            ks.set_algebra_store(SetOp::Union, b"dst", &strings(&["a"])),
+            Ok(1)
+        );
+        assert_eq!(ks.scard(b"dst"), Ok(1));
+        assert_eq!(
+            ks.set_algebra_store(SetOp::Inter, b"dst", &strings(&["a", "none"])),
+            Ok(0)
+        );
+        assert!(ks.get(b"dst").is_none());
+    }
+
+    #[test]
+    fn test_smove_and_spop() {
+        let mut ks = Keyspace::default();
+        ks.sadd(b"src", &strings(&["m", "n"])).unwrap();
+        assert_eq!(ks.smove(b"src", b"dst", b"m"), Ok(true));
+        assert_eq!(ks.smove(b"src", b"dst", b"m"), Ok(false));
+        assert_eq!(ks.sismember(b"dst", b"m"), Ok(true));
+        assert_eq!(ks.spop(b"src", 5), Ok(strings(&["n"])));
+        assert!(ks.get(b"src").is_none());
+    }
+}
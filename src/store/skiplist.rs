@@ -0,0 +1,361 @@
+use std::cmp::Ordering;
+
+use bytes::Bytes;
+
+use super::random;
+
+const MAX_LEVEL: usize = 32;
+/// Probability (out of 2^16) that a node is promoted one more level, the
+/// same 1/4 Redis uses.
+const LEVEL_PROMOTE: u64 = 0xFFFF / 4;
+const HEAD: usize = 0;
+
+#[derive(Debug, Clone, Default)]
+struct Level {
+    forward: Option<usize>,
+    span: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    member: Bytes,
+    score: f64,
+    backward: Option<usize>,
+    levels: Vec<Level>,
+}
+
+/// An indexable skiplist ordered by `(score, member)`, modelled on the one
+/// backing Redis sorted sets. Each forward link records how many nodes it
+/// skips, which makes rank lookups O(log n).
+///
+/// Nodes live in an arena and link to each other by index; slot 0 is the
+/// header and freed slots are recycled.
+#[derive(Debug, Clone)]
+pub struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    level: usize,
+    len: usize,
+    tail: Option<usize>,
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn cmp_entry(score: f64, member: &[u8], other_score: f64, other_member: &[u8]) -> Ordering {
+    score
+        .partial_cmp(&other_score)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| member.cmp(other_member))
+}
+
+fn random_level() -> usize {
+    let mut level = 1;
+    while level < MAX_LEVEL && (random::next_u64() & 0xFFFF) < LEVEL_PROMOTE {
+        level += 1;
+    }
+    level
+}
+
+impl SkipList {
+    pub fn new() -> Self {
+        let head = Node {
+            member: Bytes::new(),
+            score: 0.0,
+            backward: None,
+            levels: vec![Level::default(); MAX_LEVEL],
+        };
+        SkipList {
+            nodes: vec![head],
+            free: Vec::new(),
+            level: 1,
+            len: 0,
+            tail: None,
+        }
+    }
+
+    pub fn first(&self) -> Option<usize> {
+        self.nodes[HEAD].levels[0].forward
+    }
+
+    pub fn last(&self) -> Option<usize> {
+        self.tail
+    }
+
+    pub fn next(&self, node: usize) -> Option<usize> {
+        self.nodes[node].levels[0].forward
+    }
+
+    pub fn prev(&self, node: usize) -> Option<usize> {
+        self.nodes[node].backward
+    }
+
+    pub fn member(&self, node: usize) -> &[u8] {
+        &self.nodes[node].member
+    }
+
+    pub fn score(&self, node: usize) -> f64 {
+        self.nodes[node].score
+    }
+
+    fn is_before(&self, node: usize, score: f64, member: &[u8]) -> bool {
+        let n = &self.nodes[node];
+        cmp_entry(n.score, &n.member, score, member) == Ordering::Less
+    }
+
+    fn alloc(&mut self, member: Bytes, score: f64, level: usize) -> usize {
+        let node = Node {
+            member,
+            score,
+            backward: None,
+            levels: vec![Level::default(); level],
+        };
+        match self.free.pop() {
+            Some(idx) => {
+                self.nodes[idx] = node;
+                idx
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Inserts a new element. The caller guarantees the member is not
+    /// already present.
+    pub fn insert(&mut self, score: f64, member: Bytes) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0usize; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !self.is_before(next, score, &member) {
+                    break;
+                }
+                rank[i] += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+        let level = random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+        let new = self.alloc(member, score, level);
+        for i in 0..level {
+            let prev = update[i];
+            self.nodes[new].levels[i].forward = self.nodes[prev].levels[i].forward;
+            self.nodes[prev].levels[i].forward = Some(new);
+            self.nodes[new].levels[i].span = self.nodes[prev].levels[i].span - (rank[0] - rank[i]);
+            self.nodes[prev].levels[i].span = rank[0] - rank[i] + 1;
+        }
+        for (i, prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[*prev].levels[i].span += 1;
+        }
+        self.nodes[new].backward = if update[0] == HEAD {
+            None
+        } else {
+            Some(update[0])
+        };
+        match self.nodes[new].levels[0].forward {
+            Some(next) => self.nodes[next].backward = Some(new),
+            None => self.tail = Some(new),
+        }
+        self.len += 1;
+    }
+
+    /// Removes the element with exactly this score and member, returning
+    /// whether it was found.
+    pub fn remove(&mut self, score: f64, member: &[u8]) -> bool {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if !self.is_before(next, score, member) {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+        match self.nodes[x].levels[0].forward {
+            Some(target)
+                if self.nodes[target].score == score && self.nodes[target].member == member =>
+            {
+                self.unlink(target, &update);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn unlink(&mut self, target: usize, update: &[usize; MAX_LEVEL]) {
+        for (i, prev) in update.iter().enumerate().take(self.level) {
+            if self.nodes[*prev].levels[i].forward == Some(target) {
+                self.nodes[*prev].levels[i].span += self.nodes[target].levels[i].span;
+                self.nodes[*prev].levels[i].span -= 1;
+                self.nodes[*prev].levels[i].forward = self.nodes[target].levels[i].forward;
+            } else {
+                self.nodes[*prev].levels[i].span -= 1;
+            }
+        }
+        match self.nodes[target].levels[0].forward {
+            Some(next) => self.nodes[next].backward = self.nodes[target].backward,
+            None => self.tail = self.nodes[target].backward,
+        }
+        while self.level > 1 && self.nodes[HEAD].levels[self.level - 1].forward.is_none() {
+            self.level -= 1;
+        }
+        self.nodes[target].member = Bytes::new();
+        self.nodes[target].levels = Vec::new();
+        self.free.push(target);
+        self.len -= 1;
+    }
+
+    /// 1-based rank of the element, or `None` if absent.
+    pub fn rank(&self, score: f64, member: &[u8]) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                let n = &self.nodes[next];
+                if cmp_entry(n.score, &n.member, score, member) == Ordering::Greater {
+                    break;
+                }
+                rank += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if x != HEAD && self.nodes[x].member == member {
+                return Some(rank);
+            }
+        }
+        None
+    }
+
+    /// Node at the 1-based `rank`.
+    pub fn by_rank(&self, rank: usize) -> Option<usize> {
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                if traversed + self.nodes[x].levels[i].span > rank {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if traversed == rank && x != HEAD {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    /// First node for which `below` is false. `below` must hold for a prefix
+    /// of the list and fail for the rest.
+    pub fn first_not(&self, below: impl Fn(f64, &[u8]) -> bool) -> Option<usize> {
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                let n = &self.nodes[next];
+                if !below(n.score, &n.member) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        self.nodes[x].levels[0].forward
+    }
+
+    /// Last node for which `within` holds, with the same prefix contract as
+    /// `first_not`.
+    pub fn last_where(&self, within: impl Fn(f64, &[u8]) -> bool) -> Option<usize> {
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward {
+                let n = &self.nodes[next];
+                if !within(n.score, &n.member) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        if x == HEAD {
+            None
+        } else {
+            Some(x)
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], f64)> + '_ {
+        std::iter::successors(self.first(), move |&n| self.next(n))
+            .map(move |n| (self.member(n), self.score(n)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordering_and_ranks() {
+        let mut zsl = SkipList::new();
+        for (i, m) in ["e", "d", "c", "b", "a"].iter().enumerate() {
+            zsl.insert(i as f64, Bytes::from(*m));
+        }
+        zsl.insert(2.0, Bytes::from("bb"));
+        let order: Vec<&[u8]> = zsl.iter().map(|(m, _)| m).collect();
+        assert_eq!(order, vec![&b"e"[..], b"d", b"bb", b"c", b"b", b"a"]);
+        assert_eq!(zsl.rank(2.0, b"c"), Some(4));
+        assert_eq!(zsl.rank(2.0, b"zz"), None);
+        assert_eq!(zsl.by_rank(3).map(|n| zsl.member(n)), Some(&b"bb"[..]));
+        assert_eq!(zsl.by_rank(7), None);
+    }
+
+    #[test]
+    fn test_remove_keeps_spans_consistent() {
+        let mut zsl = SkipList::new();
+        for i in 0..500 {
+            zsl.insert(i as f64, Bytes::from(format!("m{}", i)));
+        }
+        for i in (0..500).step_by(3) {
+            assert!(zsl.remove(i as f64, format!("m{}", i).as_bytes()));
+        }
+        assert!(!zsl.remove(0.0, b"m0"));
+        let members: Vec<Bytes> = zsl.iter().map(|(m, _)| Bytes::copy_from_slice(m)).collect();
+        assert_eq!(zsl.len, members.len());
+        for (rank, member) in members.iter().enumerate() {
+            let score: f64 = std::str::from_utf8(&member[1..]).unwrap().parse().unwrap();
+            assert_eq!(zsl.rank(score, member), Some(rank + 1));
+            assert_eq!(
+                zsl.by_rank(rank + 1).map(|n| zsl.member(n)),
+                Some(&member[..])
+            );
+        }
+        assert_eq!(zsl.last().map(|n| zsl.member(n)), Some(&b"m499"[..]));
+    }
+
+    #[test]
+    fn test_range_bounds() {
+        let mut zsl = SkipList::new();
+        for i in 0..10 {
+            zsl.insert(i as f64, Bytes::from(format!("m{}", i)));
+        }
+        let first = zsl.first_not(|score, _| score < 3.5).unwrap();
+        assert_eq!(zsl.member(first), b"m4");
+        let last = zsl.last_where(|score, _| score <= 6.0).unwrap();
+        assert_eq!(zsl.member(last), b"m6");
+        assert_eq!(zsl.first_not(|score, _| score < 100.0), None);
+        assert_eq!(zsl.last_where(|score, _| score < 0.0), None);
+    }
+}
@@ -0,0 +1,48 @@
+/// Cluster hash slots: a key belongs to slot `CRC16(key) mod 16384`, as in
+/// Redis Cluster, hashing only the hash tag if the key has one.
+pub const CLUSTER_SLOTS: u16 = 16384;
+
+/// CRC16/XMODEM, the variant Redis Cluster uses: polynomial 0x1021, zero
+/// initial value, no reflection.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// The slot `key` hashes to. If the key holds a non-empty `{...}` hash
+/// tag, only the tag is hashed, so keys sharing one share a slot.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|&b| b == b'{').and_then(|open| {
+        let rest = &key[open + 1..];
+        rest.iter()
+            .position(|&b| b == b'}')
+            .filter(|&close| close > 0)
+            .map(|close| &rest[..close])
+    });
+    crc16(tag.unwrap_or(key)) & (CLUSTER_SLOTS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_hash_slot() {
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(key_hash_slot(b"foo"), 12182);
+        assert_eq!(
+            key_hash_slot(b"{user1000}.following"),
+            key_hash_slot(b"user1000")
+        );
+        // Only the first tag counts, and an empty one is no tag at all.
+        assert_eq!(key_hash_slot(b"foo{bar}{zap}"), key_hash_slot(b"bar"));
+        assert_eq!(key_hash_slot(b"foo{}{bar}"), crc16(b"foo{}{bar}") & 16383);
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, Keyspace},
+    notify::EventClass,
+    stream_group::ConsumerGroup,
+};
+
+pub type StreamFields = Vec<(Bytes, Bytes)>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        StreamId { ms, seq }
+    }
+
+    /// Parses `<ms>-<seq>` or a bare `<ms>`, in which case the sequence is
+    /// `default_seq`.
+    pub fn parse(s: &str, default_seq: u64) -> Option<StreamId> {
+        match s.split_once('-') {
+            Some((ms, seq)) => Some(StreamId::new(ms.parse().ok()?, seq.parse().ok()?)),
+            None => Some(StreamId::new(s.parse().ok()?, default_seq)),
+        }
+    }
+
+    pub fn next(&self) -> Option<StreamId> {
+        match (self.seq.checked_add(1), self.ms.checked_add(1)) {
+            (Some(seq), _) => Some(StreamId::new(self.ms, seq)),
+            (None, Some(ms)) => Some(StreamId::new(ms, 0)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn prev(&self) -> Option<StreamId> {
+        match (self.seq.checked_sub(1), self.ms.checked_sub(1)) {
+            (Some(seq), _) => Some(StreamId::new(self.ms, seq)),
+            (None, Some(ms)) => Some(StreamId::new(ms, u64::MAX)),
+            (None, None) => None,
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// The ID argument of XADD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XAddId {
+    /// `*`: both parts are generated.
+    Auto,
+    /// `<ms>-*`: the sequence is generated.
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimStrategy {
+    MaxLen(u64),
+    MinId(StreamId),
+}
+
+/// MAXLEN / MINID trimming. Entries are always trimmed exactly; `~` only
+/// matters because it is the one mode that accepts a LIMIT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrimSpec {
+    pub strategy: TrimStrategy,
+    pub approx: bool,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stream {
+    pub entries: BTreeMap<StreamId, StreamFields>,
+    pub last_id: StreamId,
+    pub max_deleted_id: StreamId,
+    pub entries_added: u64,
+    pub groups: BTreeMap<Bytes, ConsumerGroup>,
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl Stream {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn first_id(&self) -> Option<StreamId> {
+        self.entries.keys().next().copied()
+    }
+
+    /// Resolves the XADD ID against the last generated one, enforcing that
+    /// IDs only ever grow.
+    fn next_id(&self, id: XAddId) -> Result<StreamId, DbError> {
+        let last = self.last_id;
+        let id = match id {
+            XAddId::Auto => {
+                let ms = now_ms();
+                if ms > last.ms {
+                    StreamId::new(ms, 0)
+                } else {
+                    last.next().ok_or(DbError::StreamIdTooSmall)?
+                }
+            }
+            XAddId::AutoSeq(ms) => {
+                if ms == last.ms {
+                    let seq = last.seq.checked_add(1).ok_or(DbError::StreamIdTooSmall)?;
+                    StreamId::new(ms, seq)
+                } else if ms > last.ms {
+                    StreamId::new(ms, 0)
+                } else {
+                    return Err(DbError::StreamIdTooSmall);
+                }
+            }
+            XAddId::Explicit(id) => id,
+        };
+        if id == StreamId::MIN {
+            return Err(DbError::StreamIdZero);
+        }
+        if id <= last {
+            return Err(DbError::StreamIdTooSmall);
+        }
+        Ok(id)
+    }
+
+    pub fn add(&mut self, id: XAddId, fields: StreamFields) -> Result<StreamId, DbError> {
+        let id = self.next_id(id)?;
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+        Ok(id)
+    }
+
+    pub fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> Vec<(StreamId, StreamFields)> {
+        if start > end {
+            return vec![];
+        }
+        let range = self.entries.range(start..=end);
+        let count = count.unwrap_or(usize::MAX);
+        let clone = |(id, fields): (&StreamId, &StreamFields)| (*id, fields.clone());
+        if rev {
+            range.rev().take(count).map(clone).collect()
+        } else {
+            range.take(count).map(clone).collect()
+        }
+    }
+
+    pub fn delete(&mut self, ids: &[StreamId]) -> usize {
+        let mut deleted = 0;
+        for id in ids {
+            if self.entries.remove(id).is_some() {
+                deleted += 1;
+                self.max_deleted_id = self.max_deleted_id.max(*id);
+            }
+        }
+        deleted
+    }
+
+    pub fn trim(&mut self, spec: &TrimSpec) -> usize {
+        let limit = spec.limit.filter(|_| spec.approx).unwrap_or(u64::MAX) as usize;
+        let mut removed = 0;
+        while removed < limit {
+            let first = match self.entries.keys().next() {
+                Some(id) => *id,
+                None => break,
+            };
+            let evict = match spec.strategy {
+                TrimStrategy::MaxLen(max) => self.entries.len() as u64 > max,
+                TrimStrategy::MinId(min) => first < min,
+            };
+            if !evict {
+                break;
+            }
+            self.entries.remove(&first);
+            self.max_deleted_id = self.max_deleted_id.max(first);
+            removed += 1;
+        }
+        removed
+    }
+}
+
+impl Keyspace {
+    /// Appends an entry, creating the stream unless `no_mkstream` is set.
+    /// Returns `None` when the key is missing and may not be created.
+    pub fn xadd(
+        &mut self,
+        key: &[u8],
+        id: XAddId,
+        fields: StreamFields,
+        no_mkstream: bool,
+        trim: Option<&TrimSpec>,
+    ) -> Result<Option<StreamId>, DbError> {
+        let existed = self.get_stream(key)?.is_some();
+        if no_mkstream && !existed {
+            return Ok(None);
+        }
+        let stream = self.stream_entry(key)?;
+        let id = match stream.add(id, fields) {
+            Ok(id) => id,
+            Err(err) => {
+                if !existed {
+                    self.remove(key);
+                }
+                return Err(err);
+            }
+        };
+        let trimmed = trim.map_or(0, |spec| stream.trim(spec));
+        self.blocked.signal(key);
+        self.notify(EventClass::Stream, "xadd", key);
+        if trimmed > 0 {
+            self.notify(EventClass::Stream, "xtrim", key);
+        }
+        Ok(Some(id))
+    }
+
+    pub fn xlen(&mut self, key: &[u8]) -> Result<i64, DbError> {
+        Ok(self.get_stream(key)?.map_or(0, |s| s.len() as i64))
+    }
+
+    pub fn xrange(
+        &mut self,
+        key: &[u8],
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        rev: bool,
+    ) -> Result<Vec<(StreamId, StreamFields)>, DbError> {
+        Ok(self
+            .get_stream(key)?
+            .map(|s| s.range(start, end, count, rev))
+            .unwrap_or_default())
+    }
+
+    /// The ID `$` stands for: the last one generated, or 0-0 when the
+    /// stream does not exist yet.
+    pub fn xlast_id(&mut self, key: &[u8]) -> Result<StreamId, DbError> {
+        Ok(self.get_stream(key)?.map_or(StreamId::MIN, |s| s.last_id))
+    }
+
+    /// Entries strictly after `after`, as XREAD serves them.
+    pub fn xread(
+        &mut self,
+        key: &[u8],
+        after: StreamId,
+        count: Option<usize>,
+    ) -> Result<Vec<(StreamId, StreamFields)>, DbError> {
+        match after.next() {
+            Some(start) => self.xrange(key, start, StreamId::MAX, count, false),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn xdel(&mut self, key: &[u8], ids: &[StreamId]) -> Result<i64, DbError> {
+        let deleted = self.get_stream_mut(key)?.map_or(0, |s| s.delete(ids));
+        if deleted > 0 {
+            self.watched.touch(key);
+            self.notify(EventClass::Stream, "xdel", key);
+        }
+        Ok(deleted as i64)
+    }
+
+    pub fn xtrim(&mut self, key: &[u8], spec: &TrimSpec) -> Result<i64, DbError> {
+        let trimmed = self.get_stream_mut(key)?.map_or(0, |s| s.trim(spec));
+        if trimmed > 0 {
+            self.watched.touch(key);
+            self.notify(EventClass::Stream, "xtrim", key);
+        }
+        Ok(trimmed as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> StreamFields {
+        vec![(Bytes::from("f"), Bytes::from("v"))]
+    }
+
+    #[test]
+    fn test_id_generation() {
+        let mut stream = Stream::default();
+        let explicit = StreamId::new(5, 3);
+        assert_eq!(
+            stream.add(XAddId::Explicit(explicit), fields()),
+            Ok(explicit)
+        );
+        assert_eq!(
+            stream.add(XAddId::AutoSeq(5), fields()),
+            Ok(StreamId::new(5, 4))
+        );
+        assert_eq!(
+            stream.add(XAddId::AutoSeq(4), fields()),
+            Err(DbError::StreamIdTooSmall)
+        );
+        assert_eq!(
+            stream.add(XAddId::Explicit(StreamId::new(5, 4)), fields()),
+            Err(DbError::StreamIdTooSmall)
+        );
+        assert!(stream.add(XAddId::Auto, fields()).unwrap() > StreamId::new(5, 4));
+    }
+
+    #[test]
+    fn test_zero_id_rejected() {
+        let mut stream = Stream::default();
+        assert_eq!(
+            stream.add(XAddId::Explicit(StreamId::MIN), fields()),
+            Err(DbError::StreamIdZero)
+        );
+        assert_eq!(
+            stream.add(XAddId::AutoSeq(0), fields()),
+            Ok(StreamId::new(0, 1))
+        );
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut stream = Stream::default();
+        for ms in 1..=10 {
+            stream
+                .add(XAddId::Explicit(StreamId::new(ms, 0)), fields())
+                .unwrap();
+        }
+        let maxlen = TrimSpec {
+            strategy: TrimStrategy::MaxLen(7),
+            approx: false,
+            limit: None,
+        };
+        assert_eq!(stream.trim(&maxlen), 3);
+        let minid = TrimSpec {
+            strategy: TrimStrategy::MinId(StreamId::new(8, 0)),
+            approx: true,
+            limit: Some(2),
+        };
+        assert_eq!(stream.trim(&minid), 2);
+        assert_eq!(stream.entries.keys().next(), Some(&StreamId::new(6, 0)));
+        assert_eq!(stream.max_deleted_id, StreamId::new(5, 0));
+    }
+}
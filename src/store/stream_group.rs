@@ -0,0 +1,663 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, Keyspace},
+    notify::EventClass,
+    stream::{now_ms, Stream, StreamFields, StreamId},
+};
+
+/// An entry delivered to a consumer but not yet acknowledged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEntry {
+    pub consumer: Bytes,
+    /// Unix time in milliseconds of the last delivery.
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Consumer {
+    /// Last time the consumer issued any command against the group.
+    pub seen_time: u64,
+    /// Last time the consumer actually read or claimed an entry.
+    pub active_time: Option<u64>,
+    pub pending: BTreeSet<StreamId>,
+}
+
+impl Consumer {
+    fn new(now: u64) -> Self {
+        Consumer {
+            seen_time: now,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConsumerGroup {
+    pub last_id: StreamId,
+    /// Logical read counter used to compute the lag; `None` once it can no
+    /// longer be derived, e.g. after deletions in the unread part.
+    pub entries_read: Option<u64>,
+    pub pel: BTreeMap<StreamId, PendingEntry>,
+    pub consumers: BTreeMap<Bytes, Consumer>,
+}
+
+impl ConsumerGroup {
+    fn consumer(&mut self, name: &[u8], now: u64) -> &mut Consumer {
+        let consumer = self
+            .consumers
+            .entry(Bytes::copy_from_slice(name))
+            .or_insert_with(|| Consumer::new(now));
+        consumer.seen_time = now;
+        consumer
+    }
+
+    /// Drops a PEL entry, detaching it from whichever consumer owns it.
+    fn unpend(&mut self, id: &StreamId) -> bool {
+        match self.pel.remove(id) {
+            Some(pending) => {
+                if let Some(owner) = self.consumers.get_mut(&pending.consumer) {
+                    owner.pending.remove(id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Assigns a PEL entry to `consumer`, creating it if needed.
+    fn pend(&mut self, id: StreamId, consumer: &[u8], delivery_time: u64, delivery_count: u64) {
+        self.unpend(&id);
+        self.pel.insert(
+            id,
+            PendingEntry {
+                consumer: Bytes::copy_from_slice(consumer),
+                delivery_time,
+                delivery_count,
+            },
+        );
+        if let Some(owner) = self.consumers.get_mut(consumer) {
+            owner.pending.insert(id);
+        }
+    }
+}
+
+/// Where a group starts reading: `$` or an explicit ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupStart {
+    Last,
+    Id(StreamId),
+}
+
+/// The per-stream ID argument of XREADGROUP: `>` or a position in the
+/// consumer's own history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupRead {
+    New,
+    History(StreamId),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClaimOptions {
+    pub idle: Option<u64>,
+    pub time: Option<u64>,
+    pub retry_count: Option<u64>,
+    pub force: bool,
+    pub just_id: bool,
+    pub last_id: Option<StreamId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingSummary {
+    pub count: usize,
+    pub bounds: Option<(StreamId, StreamId)>,
+    pub consumers: Vec<(Bytes, usize)>,
+}
+
+/// One row of the extended XPENDING form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingInfo {
+    pub id: StreamId,
+    pub consumer: Bytes,
+    pub idle: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoClaim {
+    pub next: StreamId,
+    pub claimed: Vec<(StreamId, StreamFields)>,
+    pub deleted: Vec<StreamId>,
+}
+
+/// XAUTOCLAIM inspects at most this many PEL entries per requested one.
+const AUTOCLAIM_ATTEMPTS_FACTOR: usize = 10;
+
+impl Stream {
+    /// Whether an entry at or after `start` has been deleted, which makes
+    /// counting entries by ID difference unreliable.
+    fn has_tombstones_from(&self, start: StreamId) -> bool {
+        !self.entries.is_empty()
+            && self.max_deleted_id != StreamId::MIN
+            && start <= self.max_deleted_id
+    }
+
+    /// Number of entries ever added up to and including `id`, if it can be
+    /// worked out without walking the stream.
+    pub fn entries_read_at(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        if self.entries.is_empty() && id <= self.last_id {
+            return Some(self.entries_added);
+        }
+        if id == self.last_id {
+            return Some(self.entries_added);
+        }
+        if id > self.last_id {
+            return None;
+        }
+        let first = self.first_id()?;
+        if self.max_deleted_id == StreamId::MIN || self.max_deleted_id < first {
+            let before_first = self.entries_added - self.entries.len() as u64;
+            if id < first {
+                return Some(before_first);
+            }
+            if id == first {
+                return Some(before_first + 1);
+            }
+        }
+        None
+    }
+
+    /// Entries the group has yet to be delivered, or `None` if unknown.
+    pub fn group_lag(&self, group: &ConsumerGroup) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        let read = match group.entries_read {
+            Some(read) if !self.has_tombstones_from(group.last_id) => Some(read),
+            _ => self.entries_read_at(group.last_id),
+        };
+        read.map(|read| self.entries_added.saturating_sub(read))
+    }
+
+    fn resolve_start(&self, start: GroupStart) -> StreamId {
+        match start {
+            GroupStart::Last => self.last_id,
+            GroupStart::Id(id) => id,
+        }
+    }
+}
+
+impl Keyspace {
+    /// The stream at `key`, provided it has consumer group `group`;
+    /// otherwise `missing` builds the error.
+    fn group_stream(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        missing: impl FnOnce(String, String) -> DbError,
+    ) -> Result<&mut Stream, DbError> {
+        match self.get_stream_mut(key)? {
+            Some(stream) if stream.groups.contains_key(group) => Ok(stream),
+            _ => Err(missing(
+                String::from_utf8_lossy(key).into_owned(),
+                String::from_utf8_lossy(group).into_owned(),
+            )),
+        }
+    }
+
+    pub fn xgroup_create(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        start: GroupStart,
+        mkstream: bool,
+        entries_read: Option<u64>,
+    ) -> Result<(), DbError> {
+        let stream = match self.get_stream_mut(key)? {
+            Some(stream) => stream,
+            None if mkstream => self.stream_entry(key)?,
+            None => return Err(DbError::XGroupKeyMissing),
+        };
+        if stream.groups.contains_key(group) {
+            return Err(DbError::BusyGroup);
+        }
+        let last_id = stream.resolve_start(start);
+        let entries_read = entries_read.or_else(|| stream.entries_read_at(last_id));
+        stream.groups.insert(
+            Bytes::copy_from_slice(group),
+            ConsumerGroup {
+                last_id,
+                entries_read,
+                ..Default::default()
+            },
+        );
+        self.watched.touch(key);
+        self.notify(EventClass::Stream, "xgroup-create", key);
+        Ok(())
+    }
+
+    pub fn xgroup_setid(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        start: GroupStart,
+        entries_read: Option<u64>,
+    ) -> Result<(), DbError> {
+        if self.get_stream(key)?.is_none() {
+            return Err(DbError::XGroupKeyMissing);
+        }
+        let stream = self.group_stream(key, group, DbError::NoGroupForKey)?;
+        let last_id = stream.resolve_start(start);
+        let entries_read = entries_read.or_else(|| stream.entries_read_at(last_id));
+        let cg = stream.groups.get_mut(group).expect("group checked above");
+        cg.last_id = last_id;
+        cg.entries_read = entries_read;
+        self.watched.touch(key);
+        self.notify(EventClass::Stream, "xgroup-setid", key);
+        Ok(())
+    }
+
+    pub fn xgroup_destroy(&mut self, key: &[u8], group: &[u8]) -> Result<bool, DbError> {
+        let stream = self.get_stream_mut(key)?.ok_or(DbError::XGroupKeyMissing)?;
+        let destroyed = stream.groups.remove(group).is_some();
+        // Readers blocked on the group must find out it is gone.
+        self.blocked.signal(key);
+        if destroyed {
+            self.watched.touch(key);
+            self.notify(EventClass::Stream, "xgroup-destroy", key);
+        }
+        Ok(destroyed)
+    }
+
+    pub fn xgroup_create_consumer(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &[u8],
+    ) -> Result<bool, DbError> {
+        if self.get_stream(key)?.is_none() {
+            return Err(DbError::XGroupKeyMissing);
+        }
+        let stream = self.group_stream(key, group, DbError::NoGroupForKey)?;
+        let cg = stream.groups.get_mut(group).expect("group checked above");
+        if cg.consumers.contains_key(consumer) {
+            return Ok(false);
+        }
+        cg.consumers
+            .insert(Bytes::copy_from_slice(consumer), Consumer::new(now_ms()));
+        self.watched.touch(key);
+        self.notify(EventClass::Stream, "xgroup-createconsumer", key);
+        Ok(true)
+    }
+
+    /// Deletes a consumer along with its pending entries, returning how many
+    /// were pending.
+    pub fn xgroup_del_consumer(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &[u8],
+    ) -> Result<i64, DbError> {
+        if self.get_stream(key)?.is_none() {
+            return Err(DbError::XGroupKeyMissing);
+        }
+        let stream = self.group_stream(key, group, DbError::NoGroupForKey)?;
+        let cg = stream.groups.get_mut(group).expect("group checked above");
+        let Some(removed) = cg.consumers.remove(consumer) else {
+            return Ok(0);
+        };
+        for id in &removed.pending {
+            cg.pel.remove(id);
+        }
+        self.watched.touch(key);
+        self.notify(EventClass::Stream, "xgroup-delconsumer", key);
+        Ok(removed.pending.len() as i64)
+    }
+
+    /// Serves one stream of an XREADGROUP. New entries advance the group and
+    /// enter the PEL unless `no_ack`; a history read replays the consumer's
+    /// own pending entries, with `None` for those since deleted.
+    pub fn xreadgroup(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &[u8],
+        read: GroupRead,
+        count: Option<usize>,
+        no_ack: bool,
+    ) -> Result<Vec<(StreamId, Option<StreamFields>)>, DbError> {
+        let stream = self.group_stream(key, group, DbError::NoGroupRead)?;
+        let now = now_ms();
+        let count = count.filter(|c| *c > 0).unwrap_or(usize::MAX);
+        let Stream {
+            entries, groups, ..
+        } = &mut *stream;
+        let cg = groups.get_mut(group).expect("group checked above");
+        cg.consumer(consumer, now);
+        match read {
+            GroupRead::History(after) => {
+                let ids: Vec<StreamId> = cg.consumers[consumer]
+                    .pending
+                    .iter()
+                    .filter(|id| **id > after)
+                    .take(count)
+                    .copied()
+                    .collect();
+                let mut out = Vec::with_capacity(ids.len());
+                for id in ids {
+                    let fields = entries.get(&id).cloned();
+                    if fields.is_some() {
+                        let pending = cg.pel.get_mut(&id).expect("consumer PEL mirrors group");
+                        pending.delivery_time = now;
+                        pending.delivery_count += 1;
+                    }
+                    out.push((id, fields));
+                }
+                Ok(out)
+            }
+            GroupRead::New => {
+                let Some(start) = cg.last_id.next() else {
+                    return Ok(vec![]);
+                };
+                let delivered: Vec<(StreamId, StreamFields)> = entries
+                    .range(start..)
+                    .take(count)
+                    .map(|(id, fields)| (*id, fields.clone()))
+                    .collect();
+                let Some(&(last, _)) = delivered.last() else {
+                    return Ok(vec![]);
+                };
+                let tombstones = stream.has_tombstones_from(last);
+                let entries_read = match stream.groups[group].entries_read {
+                    Some(read) if !tombstones => Some(read + delivered.len() as u64),
+                    _ => stream.entries_read_at(last),
+                };
+                let cg = stream.groups.get_mut(group).expect("group checked above");
+                cg.last_id = last;
+                cg.entries_read = entries_read;
+                cg.consumer(consumer, now).active_time = Some(now);
+                if !no_ack {
+                    for (id, _) in &delivered {
+                        cg.pend(*id, consumer, now, 1);
+                    }
+                }
+                Ok(delivered
+                    .into_iter()
+                    .map(|(id, fields)| (id, Some(fields)))
+                    .collect())
+            }
+        }
+    }
+
+    pub fn xack(&mut self, key: &[u8], group: &[u8], ids: &[StreamId]) -> Result<i64, DbError> {
+        let Some(cg) = self
+            .get_stream_mut(key)?
+            .and_then(|stream| stream.groups.get_mut(group))
+        else {
+            return Ok(0);
+        };
+        Ok(ids.iter().filter(|id| cg.unpend(id)).count() as i64)
+    }
+
+    pub fn xpending_summary(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+    ) -> Result<PendingSummary, DbError> {
+        let stream = self.group_stream(key, group, DbError::NoGroup)?;
+        let cg = &stream.groups[group];
+        let bounds = cg
+            .pel
+            .keys()
+            .next()
+            .zip(cg.pel.keys().next_back())
+            .map(|(min, max)| (*min, *max));
+        let consumers = cg
+            .consumers
+            .iter()
+            .filter(|(_, c)| !c.pending.is_empty())
+            .map(|(name, c)| (name.clone(), c.pending.len()))
+            .collect();
+        Ok(PendingSummary {
+            count: cg.pel.len(),
+            bounds,
+            consumers,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn xpending_range(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        min_idle: u64,
+        start: StreamId,
+        end: StreamId,
+        count: usize,
+        consumer: Option<&[u8]>,
+    ) -> Result<Vec<PendingInfo>, DbError> {
+        let stream = self.group_stream(key, group, DbError::NoGroup)?;
+        let cg = &stream.groups[group];
+        if start > end {
+            return Ok(vec![]);
+        }
+        let now = now_ms();
+        Ok(cg
+            .pel
+            .range(start..=end)
+            .filter(|(_, p)| consumer.is_none() || consumer == Some(&p.consumer[..]))
+            .filter(|(_, p)| now.saturating_sub(p.delivery_time) >= min_idle)
+            .take(count)
+            .map(|(id, p)| PendingInfo {
+                id: *id,
+                consumer: p.consumer.clone(),
+                idle: now.saturating_sub(p.delivery_time),
+                delivery_count: p.delivery_count,
+            })
+            .collect())
+    }
+
+    /// Transfers ownership of pending entries idle for at least `min_idle`
+    /// milliseconds. Entries no longer in the stream are dropped from the
+    /// PEL instead of being claimed.
+    pub fn xclaim(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &[u8],
+        min_idle: u64,
+        ids: &[StreamId],
+        opts: &ClaimOptions,
+    ) -> Result<Vec<(StreamId, StreamFields)>, DbError> {
+        let stream = self.group_stream(key, group, DbError::NoGroup)?;
+        let now = now_ms();
+        let delivery_time = match (opts.idle, opts.time) {
+            (Some(idle), _) => now.saturating_sub(idle),
+            (None, Some(time)) => time,
+            (None, None) => now,
+        };
+        let Stream {
+            entries, groups, ..
+        } = &mut *stream;
+        let cg = groups.get_mut(group).expect("group checked above");
+        if let Some(last_id) = opts.last_id {
+            cg.last_id = cg.last_id.max(last_id);
+        }
+        cg.consumer(consumer, now);
+        let mut claimed = Vec::new();
+        for id in ids {
+            let delivered = match cg.pel.get(id) {
+                Some(p) if min_idle > 0 && now.saturating_sub(p.delivery_time) < min_idle => {
+                    continue
+                }
+                Some(p) => p.delivery_count,
+                None if opts.force && entries.contains_key(id) => 0,
+                None => continue,
+            };
+            let Some(fields) = entries.get(id) else {
+                cg.unpend(id);
+                continue;
+            };
+            let delivery_count = opts
+                .retry_count
+                .unwrap_or(delivered + u64::from(!opts.just_id));
+            cg.pend(*id, consumer, delivery_time, delivery_count);
+            claimed.push((*id, fields.clone()));
+        }
+        if !claimed.is_empty() {
+            cg.consumer(consumer, now).active_time = Some(now);
+        }
+        Ok(claimed)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn xautoclaim(
+        &mut self,
+        key: &[u8],
+        group: &[u8],
+        consumer: &[u8],
+        min_idle: u64,
+        start: StreamId,
+        count: usize,
+        just_id: bool,
+    ) -> Result<AutoClaim, DbError> {
+        let stream = self.group_stream(key, group, DbError::NoGroup)?;
+        let now = now_ms();
+        let Stream {
+            entries, groups, ..
+        } = &mut *stream;
+        let cg = groups.get_mut(group).expect("group checked above");
+        cg.consumer(consumer, now);
+        let attempts = count.saturating_mul(AUTOCLAIM_ATTEMPTS_FACTOR);
+        // One id past the attempt budget is enough to know the next cursor.
+        let scan: Vec<StreamId> = cg
+            .pel
+            .range(start..)
+            .map(|(id, _)| *id)
+            .take(attempts + 1)
+            .collect();
+        let mut claimed = Vec::new();
+        let mut deleted = Vec::new();
+        let mut next = StreamId::MIN;
+        for (examined, id) in scan.into_iter().enumerate() {
+            if examined == attempts || claimed.len() == count {
+                next = id;
+                break;
+            }
+            let pending = &cg.pel[&id];
+            if now.saturating_sub(pending.delivery_time) < min_idle {
+                continue;
+            }
+            let Some(fields) = entries.get(&id) else {
+                cg.unpend(&id);
+                deleted.push(id);
+                continue;
+            };
+            let delivery_count = pending.delivery_count + u64::from(!just_id);
+            cg.pend(id, consumer, now, delivery_count);
+            claimed.push((id, fields.clone()));
+        }
+        if !claimed.is_empty() {
+            cg.consumer(consumer, now).active_time = Some(now);
+        }
+        Ok(AutoClaim {
+            next,
+            claimed,
+            deleted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::stream::XAddId;
+
+    fn setup(ks: &mut Keyspace, n: u64) {
+        for ms in 1..=n {
+            ks.xadd(
+                b"s",
+                XAddId::Explicit(StreamId::new(ms, 0)),
+                vec![(Bytes::from("f"), Bytes::from(ms.to_string()))],
+                false,
+                None,
+            )
+            .unwrap();
+        }
+        ks.xgroup_create(b"s", b"g", GroupStart::Id(StreamId::MIN), false, None)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_readgroup_tracks_pel_and_lag() {
+        let mut ks = Keyspace::default();
+        setup(&mut ks, 3);
+        let read = ks
+            .xreadgroup(b"s", b"g", b"alice", GroupRead::New, Some(2), false)
+            .unwrap();
+        assert_eq!(read.len(), 2);
+        let stream = ks.get_stream(b"s").unwrap().unwrap();
+        let cg = &stream.groups[&b"g"[..]];
+        assert_eq!(cg.last_id, StreamId::new(2, 0));
+        assert_eq!(cg.pel.len(), 2);
+        assert_eq!(stream.group_lag(cg), Some(1));
+        assert_eq!(ks.xack(b"s", b"g", &[StreamId::new(1, 0)]), Ok(1));
+        let history = ks
+            .xreadgroup(
+                b"s",
+                b"g",
+                b"alice",
+                GroupRead::History(StreamId::MIN),
+                None,
+                false,
+            )
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].0, StreamId::new(2, 0));
+        assert_eq!(
+            ks.xreadgroup(b"s", b"nope", b"alice", GroupRead::New, None, false),
+            Err(DbError::NoGroupRead("s".to_string(), "nope".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_claim_moves_ownership() {
+        let mut ks = Keyspace::default();
+        setup(&mut ks, 2);
+        ks.xreadgroup(b"s", b"g", b"alice", GroupRead::New, None, false)
+            .unwrap();
+        let ids = [StreamId::new(1, 0), StreamId::new(2, 0)];
+        let claimed = ks
+            .xclaim(b"s", b"g", b"bob", 0, &ids, &ClaimOptions::default())
+            .unwrap();
+        assert_eq!(claimed.len(), 2);
+        let summary = ks.xpending_summary(b"s", b"g").unwrap();
+        assert_eq!(summary.consumers, vec![(Bytes::from("bob"), 2)]);
+        let info = ks
+            .xpending_range(b"s", b"g", 0, StreamId::MIN, StreamId::MAX, 10, None)
+            .unwrap();
+        assert!(info.iter().all(|p| p.delivery_count == 2));
+    }
+
+    #[test]
+    fn test_autoclaim_reports_deleted() {
+        let mut ks = Keyspace::default();
+        setup(&mut ks, 3);
+        ks.xreadgroup(b"s", b"g", b"alice", GroupRead::New, None, false)
+            .unwrap();
+        ks.xdel(b"s", &[StreamId::new(2, 0)]).unwrap();
+        let result = ks
+            .xautoclaim(b"s", b"g", b"bob", 0, StreamId::MIN, 2, false)
+            .unwrap();
+        assert_eq!(result.claimed.len(), 2);
+        assert_eq!(result.deleted, vec![StreamId::new(2, 0)]);
+        assert_eq!(result.next, StreamId::MIN);
+        assert_eq!(ks.xpending_summary(b"s", b"g").unwrap().count, 2);
+    }
+}
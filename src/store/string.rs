@@ -0,0 +1,481 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, EntryValue, Keyspace, RespEntry},
+    expire::ExpireCondition,
+    lcs::{lcs, Lcs},
+    notify::EventClass,
+    numeric::{self, format_float},
+};
+
+/// Largest string SETRANGE and APPEND may produce, as in Redis.
+pub(super) const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+/// A string value. One that is the canonical spelling of an `i64` is kept
+/// as the integer itself, like the `int` encoding of Redis, so counters
+/// neither allocate nor get reparsed on every INCR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StrValue {
+    Int(i64),
+    Raw(Bytes),
+}
+
+impl StrValue {
+    /// Parses the value as an integer the way Redis does: no sign other
+    /// than a leading `-`, no leading zeros, no whitespace.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            StrValue::Int(n) => Some(*n),
+            StrValue::Raw(raw) => std::str::from_utf8(raw)
+                .ok()?
+                .parse::<i64>()
+                .ok()
+                .filter(|n| n.to_string().as_bytes() == &raw[..]),
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            StrValue::Int(n) => Some(*n as f64),
+            StrValue::Raw(raw) => numeric::parse_float(raw),
+        }
+    }
+
+    /// The value as it is sent to clients.
+    pub fn to_bytes(&self) -> Bytes {
+        match self {
+            StrValue::Int(n) => Bytes::from(n.to_string()),
+            StrValue::Raw(raw) => raw.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            StrValue::Int(n) => n.to_string().len(),
+            StrValue::Raw(raw) => raw.len(),
+        }
+    }
+}
+
+impl From<Bytes> for StrValue {
+    fn from(raw: Bytes) -> Self {
+        let raw = StrValue::Raw(raw);
+        raw.as_int().map_or(raw, StrValue::Int)
+    }
+}
+
+impl From<&'static str> for StrValue {
+    fn from(s: &'static str) -> Self {
+        StrValue::from(Bytes::from(s))
+    }
+}
+
+/// What a write does with the key's time to live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetExpiry {
+    /// Drop any TTL, as a plain SET does.
+    Clear,
+    /// KEEPTTL: retain whatever TTL the key already has.
+    Keep,
+    /// EX / PX: relative to the moment the write happens.
+    After(Duration),
+    /// EXAT / PXAT: time since the Unix epoch.
+    At(Duration),
+}
+
+impl SetExpiry {
+    fn deadline(&self, now: SystemTime, current: Option<SystemTime>) -> Option<SystemTime> {
+        match self {
+            SetExpiry::Clear => None,
+            SetExpiry::Keep => current,
+            SetExpiry::After(ttl) => now.checked_add(*ttl),
+            SetExpiry::At(at) => UNIX_EPOCH.checked_add(*at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    Always,
+    /// NX: only if the key does not exist.
+    IfAbsent,
+    /// XX: only if the key already exists.
+    IfPresent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetOptions {
+    pub expiry: SetExpiry,
+    pub condition: SetCondition,
+    /// GET: also return the previous value, which must be a string.
+    pub get: bool,
+}
+
+impl Default for SetOptions {
+    fn default() -> Self {
+        SetOptions {
+            expiry: SetExpiry::Clear,
+            condition: SetCondition::Always,
+            get: false,
+        }
+    }
+}
+
+/// Result of a SET: whether the value was written, and the old value when
+/// GET was requested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetOutcome {
+    pub written: bool,
+    pub previous: Option<Bytes>,
+}
+
+impl Keyspace {
+    /// SET with its full option set. Overwrites a value of any type unless
+    /// GET is given, in which case the old value must be a string.
+    pub fn set(
+        &mut self,
+        key: &[u8],
+        value: Bytes,
+        opts: &SetOptions,
+    ) -> Result<SetOutcome, DbError> {
+        let current = self.get(key);
+        let exists = current.is_some();
+        let current_expiry = current.and_then(|entry| entry.expiry);
+        let previous = if opts.get {
+            self.get_str(key)?.map(StrValue::to_bytes)
+        } else {
+            None
+        };
+        let allowed = match opts.condition {
+            SetCondition::Always => true,
+            SetCondition::IfAbsent => !exists,
+            SetCondition::IfPresent => exists,
+        };
+        if allowed {
+            let expiry = opts.expiry.deadline(SystemTime::now(), current_expiry);
+            self.insert(
+                Bytes::copy_from_slice(key),
+                RespEntry::new(EntryValue::Str(value.into()), expiry),
+            );
+            self.notify(EventClass::String, "set", key);
+            if matches!(opts.expiry, SetExpiry::After(_) | SetExpiry::At(_)) {
+                self.notify(EventClass::Generic, "expire", key);
+            }
+        }
+        Ok(SetOutcome {
+            written: allowed,
+            previous,
+        })
+    }
+
+    /// Replaces the string at `key` with `value`, keeping its TTL, or
+    /// creates it without one.
+    pub(super) fn overwrite_str(&mut self, key: &[u8], value: StrValue) {
+        match self.get_mut(key) {
+            Some(entry) => {
+                entry.value = EntryValue::Str(value);
+                self.watched.touch(key);
+            }
+            None => self.insert(
+                Bytes::copy_from_slice(key),
+                RespEntry::new(EntryValue::Str(value), None),
+            ),
+        }
+    }
+
+    /// INCRBY and friends: a missing key counts as 0.
+    pub fn incr_by(&mut self, key: &[u8], delta: i64) -> Result<i64, DbError> {
+        let current = match self.get_str(key)? {
+            Some(value) => value.as_int().ok_or(DbError::NotInteger)?,
+            None => 0,
+        };
+        let updated = current.checked_add(delta).ok_or(DbError::Overflow)?;
+        self.overwrite_str(key, StrValue::Int(updated));
+        self.notify(EventClass::String, "incrby", key);
+        Ok(updated)
+    }
+
+    /// INCRBYFLOAT. Returns the new value as it is stored.
+    pub fn incr_by_float(&mut self, key: &[u8], delta: f64) -> Result<Bytes, DbError> {
+        let current = match self.get_str(key)? {
+            Some(value) => value.as_float().ok_or(DbError::NotFloat)?,
+            None => 0.0,
+        };
+        let updated = current + delta;
+        if !updated.is_finite() {
+            return Err(DbError::NanOrInfinity);
+        }
+        let formatted = Bytes::from(format_float(updated));
+        self.overwrite_str(key, StrValue::from(formatted.clone()));
+        self.notify(EventClass::String, "incrbyfloat", key);
+        Ok(formatted)
+    }
+
+    /// The string at `key` as raw bytes, empty if the key is missing.
+    pub(super) fn str_bytes(&mut self, key: &[u8]) -> Result<Option<Bytes>, DbError> {
+        Ok(self.get_str(key)?.map(StrValue::to_bytes))
+    }
+
+    /// Returns the length of the string after appending.
+    pub fn append(&mut self, key: &[u8], suffix: &[u8]) -> Result<usize, DbError> {
+        let current = self.str_bytes(key)?.unwrap_or_default();
+        if current.len() + suffix.len() > MAX_STRING_LEN {
+            return Err(DbError::StringTooLong);
+        }
+        let mut value = Vec::with_capacity(current.len() + suffix.len());
+        value.extend_from_slice(&current);
+        value.extend_from_slice(suffix);
+        let len = value.len();
+        self.overwrite_str(key, Bytes::from(value).into());
+        self.notify(EventClass::String, "append", key);
+        Ok(len)
+    }
+
+    pub fn strlen(&mut self, key: &[u8]) -> Result<usize, DbError> {
+        Ok(self.get_str(key)?.map_or(0, StrValue::len))
+    }
+
+    /// GETRANGE: bytes `start..=end`, where negative offsets count from the
+    /// end and out of range ones are clamped.
+    pub fn getrange(&mut self, key: &[u8], start: i64, end: i64) -> Result<Bytes, DbError> {
+        let Some(value) = self.str_bytes(key)? else {
+            return Ok(Bytes::new());
+        };
+        let len = value.len() as i64;
+        let resolve = |idx: i64| if idx < 0 { (len + idx).max(0) } else { idx };
+        let (start, end) = (resolve(start), resolve(end).min(len - 1));
+        if len == 0 || start > end {
+            return Ok(Bytes::new());
+        }
+        Ok(value.slice(start as usize..=end as usize))
+    }
+
+    /// SETRANGE: overwrites from byte `offset`, zero-padding the string if it
+    /// is shorter. Returns the new length.
+    pub fn setrange(&mut self, key: &[u8], offset: usize, patch: &[u8]) -> Result<usize, DbError> {
+        let current = self.str_bytes(key)?;
+        if patch.is_empty() {
+            // Nothing to write, and a missing key is not created.
+            return Ok(current.map_or(0, |value| value.len()));
+        }
+        if offset + patch.len() > MAX_STRING_LEN {
+            return Err(DbError::StringTooLong);
+        }
+        let mut bytes = current.map(Vec::from).unwrap_or_default();
+        let end = offset + patch.len();
+        if bytes.len() < end {
+            bytes.resize(end, 0);
+        }
+        bytes[offset..end].copy_from_slice(patch);
+        let len = bytes.len();
+        self.overwrite_str(key, Bytes::from(bytes).into());
+        self.notify(EventClass::String, "setrange", key);
+        Ok(len)
+    }
+
+    pub fn getdel(&mut self, key: &[u8]) -> Result<Option<Bytes>, DbError> {
+        let value = self.str_bytes(key)?;
+        if value.is_some() {
+            self.delete(key);
+        }
+        Ok(value)
+    }
+
+    /// GETEX: reads the string and updates its TTL, `Keep` leaving it alone
+    /// and `Clear` making the key persistent.
+    pub fn getex(&mut self, key: &[u8], expiry: SetExpiry) -> Result<Option<Bytes>, DbError> {
+        let Some(value) = self.str_bytes(key)? else {
+            return Ok(None);
+        };
+        match expiry {
+            SetExpiry::Keep => {}
+            SetExpiry::Clear => {
+                self.persist(key);
+            }
+            _ => {
+                if let Some(deadline) = expiry.deadline(SystemTime::now(), None) {
+                    self.expire_at(key, deadline, ExpireCondition::default());
+                }
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// MGET: values that are missing or not strings come back as `None`.
+    pub fn mget(&mut self, keys: &[Bytes]) -> Vec<Option<Bytes>> {
+        keys.iter()
+            .map(|key| self.str_bytes(key).ok().flatten())
+            .collect()
+    }
+
+    /// MSET, or with `nx` MSETNX, which writes nothing unless none of the
+    /// keys exist. Returns whether the pairs were written.
+    pub fn mset(&mut self, pairs: &[(Bytes, Bytes)], nx: bool) -> bool {
+        if nx && pairs.iter().any(|(key, _)| self.get(key).is_some()) {
+            return false;
+        }
+        for (key, value) in pairs {
+            self.insert(
+                key.clone(),
+                RespEntry::new(EntryValue::Str(value.clone().into()), None),
+            );
+            self.notify(EventClass::String, "set", key);
+        }
+        true
+    }
+
+    /// LCS of the strings at two keys, missing ones counting as empty.
+    pub fn lcs(&mut self, key1: &[u8], key2: &[u8]) -> Result<Lcs, DbError> {
+        let mut read = |key: &[u8]| match self.str_bytes(key) {
+            Ok(value) => Ok(value.unwrap_or_default()),
+            Err(_) => Err(DbError::LcsNotString),
+        };
+        let (a, b) = (read(key1)?, read(key2)?);
+        Ok(lcs(&a, &b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(expiry: SetExpiry, condition: SetCondition, get: bool) -> SetOptions {
+        SetOptions {
+            expiry,
+            condition,
+            get,
+        }
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut ks = Keyspace::default();
+        let nx = opts(SetExpiry::Clear, SetCondition::IfAbsent, false);
+        let xx = opts(SetExpiry::Clear, SetCondition::IfPresent, false);
+        assert!(!ks.set(b"k", Bytes::from("a"), &xx).unwrap().written);
+        assert!(ks.get(b"k").is_none());
+        assert!(ks.set(b"k", Bytes::from("a"), &nx).unwrap().written);
+        assert!(!ks.set(b"k", Bytes::from("b"), &nx).unwrap().written);
+        assert!(ks.set(b"k", Bytes::from("c"), &xx).unwrap().written);
+        assert_eq!(ks.get_str(b"k"), Ok(Some(&StrValue::from("c"))));
+    }
+
+    #[test]
+    fn test_expiry_modes() {
+        let mut ks = Keyspace::default();
+        let ex = opts(
+            SetExpiry::After(Duration::from_secs(100)),
+            SetCondition::Always,
+            false,
+        );
+        ks.set(b"k", Bytes::from("a"), &ex).unwrap();
+        let ttl = ks.get(b"k").unwrap().expiry;
+        assert!(ttl.is_some());
+        let keep = opts(SetExpiry::Keep, SetCondition::Always, false);
+        ks.set(b"k", Bytes::from("b"), &keep).unwrap();
+        assert_eq!(ks.get(b"k").unwrap().expiry, ttl);
+        ks.set(b"k", Bytes::from("c"), &SetOptions::default())
+            .unwrap();
+        assert_eq!(ks.get(b"k").unwrap().expiry, None);
+        let past = opts(
+            SetExpiry::At(Duration::from_secs(1)),
+            SetCondition::Always,
+            false,
+        );
+        ks.set(b"k", Bytes::from("d"), &past).unwrap();
+        assert!(ks.get(b"k").is_none());
+    }
+
+    #[test]
+    fn test_get_returns_previous() {
+        let mut ks = Keyspace::default();
+        let get_nx = opts(SetExpiry::Clear, SetCondition::IfAbsent, true);
+        let outcome = ks.set(b"k", Bytes::from("a"), &get_nx).unwrap();
+        assert_eq!(outcome.previous, None);
+        let outcome = ks.set(b"k", Bytes::from("b"), &get_nx).unwrap();
+        assert_eq!(
+            (outcome.written, outcome.previous),
+            (false, Some(Bytes::from("a")))
+        );
+        ks.push(b"l", crate::store::list::ListEnd::Left, &[Bytes::from("x")])
+            .unwrap();
+        assert_eq!(
+            ks.set(b"l", Bytes::from("v"), &get_nx),
+            Err(DbError::WrongType)
+        );
+        assert!(ks
+            .set(b"l", Bytes::from("v"), &SetOptions::default())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_int_encoding() {
+        assert_eq!(StrValue::from("-42"), StrValue::Int(-42));
+        for raw in ["042", "+1", " 1", "-0", "1.0", "99999999999999999999"] {
+            assert_eq!(StrValue::from(raw), StrValue::Raw(Bytes::from(raw)));
+        }
+        assert_eq!(StrValue::Int(7).to_bytes(), Bytes::from("7"));
+    }
+
+    #[test]
+    fn test_incr() {
+        let mut ks = Keyspace::default();
+        let ex = opts(
+            SetExpiry::After(Duration::from_secs(100)),
+            SetCondition::Always,
+            false,
+        );
+        ks.set(b"n", Bytes::from("10"), &ex).unwrap();
+        assert_eq!(ks.incr_by(b"n", 5), Ok(15));
+        assert!(ks.get(b"n").unwrap().expiry.is_some());
+        assert_eq!(ks.incr_by(b"fresh", -1), Ok(-1));
+        assert_eq!(ks.incr_by(b"n", i64::MAX), Err(DbError::Overflow));
+        ks.set(b"s", Bytes::from("abc"), &SetOptions::default())
+            .unwrap();
+        assert_eq!(ks.incr_by(b"s", 1), Err(DbError::NotInteger));
+        assert_eq!(ks.incr_by_float(b"n", 0.5), Ok(Bytes::from("15.5")));
+        assert_eq!(ks.incr_by_float(b"n", 0.5), Ok(Bytes::from("16")));
+        assert_eq!(ks.get_str(b"n"), Ok(Some(&StrValue::Int(16))));
+        assert_eq!(ks.incr_by_float(b"s", 1.0), Err(DbError::NotFloat));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut ks = Keyspace::default();
+        assert_eq!(ks.append(b"k", b"Hello"), Ok(5));
+        assert_eq!(ks.append(b"k", b" World"), Ok(11));
+        assert_eq!(ks.getrange(b"k", 0, 3), Ok(Bytes::from("Hell")));
+        assert_eq!(ks.getrange(b"k", -3, -1), Ok(Bytes::from("rld")));
+        assert_eq!(ks.getrange(b"k", 5, 2), Ok(Bytes::new()));
+        assert_eq!(ks.getrange(b"k", 0, 100), Ok(Bytes::from("Hello World")));
+        assert_eq!(ks.setrange(b"k", 6, b"Redis"), Ok(11));
+        assert_eq!(ks.setrange(b"pad", 3, b"x"), Ok(4));
+        assert_eq!(ks.get_str(b"pad"), Ok(Some(&StrValue::from("\0\0\0x"))));
+        assert_eq!(ks.setrange(b"missing", 5, b""), Ok(0));
+        assert!(ks.get(b"missing").is_none());
+        assert_eq!(ks.strlen(b"k"), Ok(11));
+    }
+
+    #[test]
+    fn test_msetnx_is_all_or_nothing() {
+        let mut ks = Keyspace::default();
+        let pairs = |names: &[&'static str]| -> Vec<(Bytes, Bytes)> {
+            names
+                .iter()
+                .map(|n| (Bytes::from(*n), Bytes::from("v")))
+                .collect()
+        };
+        assert!(ks.mset(&pairs(&["a", "b"]), true));
+        assert!(!ks.mset(&pairs(&["b", "c"]), true));
+        assert!(ks.get(b"c").is_none());
+        assert!(ks.mset(&pairs(&["b", "c"]), false));
+        ks.push(b"l", crate::store::list::ListEnd::Left, &[Bytes::from("x")])
+            .unwrap();
+        assert_eq!(
+            ks.mget(&[Bytes::from("a"), Bytes::from("l"), Bytes::from("z")]),
+            vec![Some(Bytes::from("v")), None, None]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{HashMap, HashSet};
+
+use bytes::Bytes;
+
+use super::cache::Keyspace;
+
+/// Keys WATCHed by connections about to run a transaction, and which of
+/// those connections saw one of their keys change since.
+///
+/// Any mutable access to a key counts as a change: a write, a deletion, an
+/// expiry or a FLUSHDB. EXEC then aborts, as in Redis; a false alarm only
+/// costs the client a retry.
+#[derive(Debug, Default)]
+pub struct WatchedKeys {
+    next_id: u64,
+    watchers: HashMap<Bytes, Vec<u64>>,
+    dirty: HashSet<u64>,
+}
+
+impl WatchedKeys {
+    /// Hands out the id a connection watches keys under.
+    pub fn new_client(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn watch(&mut self, id: u64, key: &Bytes) {
+        let watchers = self.watchers.entry(key.clone()).or_default();
+        if !watchers.contains(&id) {
+            watchers.push(id);
+        }
+    }
+
+    /// Forgets `id`'s watches on `keys`, and whether any of them changed.
+    pub fn unwatch(&mut self, id: u64, keys: &[Bytes]) {
+        for key in keys {
+            if let Some(watchers) = self.watchers.get_mut(key) {
+                watchers.retain(|&w| w != id);
+                if watchers.is_empty() {
+                    self.watchers.remove(key);
+                }
+            }
+        }
+        self.dirty.remove(&id);
+    }
+
+    /// Records that `key` changed, dooming the transactions watching it.
+    pub fn touch(&mut self, key: &[u8]) {
+        if let Some(watchers) = self.watchers.get(key) {
+            self.dirty.extend(watchers);
+        }
+    }
+
+    /// Touches every watched key `existed` says was there.
+    pub fn touch_all(&mut self, existed: impl Fn(&[u8]) -> bool) {
+        for (key, watchers) in &self.watchers {
+            if existed(key) {
+                self.dirty.extend(watchers);
+            }
+        }
+    }
+
+    /// Whether a key `id` watches changed since it was watched.
+    pub fn is_dirty(&self, id: u64) -> bool {
+        self.dirty.contains(&id)
+    }
+}
+
+impl Keyspace {
+    /// WATCH. A key whose TTL already passed is evicted first, so that its
+    /// lazy eviction later on does not count as a change.
+    pub fn watch(&mut self, id: u64, key: &Bytes) {
+        self.expire_if_needed(key);
+        self.watched.watch(id, key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{
+        cache::{EntryValue, RespEntry},
+        list::ListEnd,
+        string::StrValue,
+    };
+    use std::time::{Duration, SystemTime};
+
+    fn string(expiry: Option<SystemTime>) -> RespEntry {
+        RespEntry::new(EntryValue::Str(StrValue::from("v")), expiry)
+    }
+
+    #[test]
+    fn test_writes_dirty_watchers() {
+        let mut ks = Keyspace::default();
+        let (a, b) = (ks.watched.new_client(), ks.watched.new_client());
+        ks.watch(a, &Bytes::from("k"));
+        ks.watch(b, &Bytes::from("other"));
+        assert!(ks.get(b"k").is_none());
+        assert!(!ks.watched.is_dirty(a));
+        ks.insert(Bytes::from("k"), string(None));
+        assert!(ks.watched.is_dirty(a));
+        assert!(!ks.watched.is_dirty(b));
+        ks.watched.unwatch(a, &[Bytes::from("k")]);
+        assert!(!ks.watched.is_dirty(a));
+        ks.remove(b"k");
+        assert!(!ks.watched.is_dirty(a));
+    }
+
+    #[test]
+    fn test_expiry_dirties_watchers() {
+        let mut ks = Keyspace::default();
+        let past = SystemTime::now() - Duration::from_secs(1);
+        ks.insert(Bytes::from("gone"), string(Some(past)));
+        let id = ks.watched.new_client();
+        // Already expired when watched: its eviction is not a change.
+        ks.watch(id, &Bytes::from("gone"));
+        assert!(ks.get(b"gone").is_none());
+        assert!(!ks.watched.is_dirty(id));
+        let soon = SystemTime::now() + Duration::from_millis(10);
+        ks.insert(Bytes::from("k"), string(Some(soon)));
+        let other = ks.watched.new_client();
+        ks.watch(other, &Bytes::from("k"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(ks.get(b"k").is_none());
+        assert!(ks.watched.is_dirty(other));
+    }
+
+    #[test]
+    fn test_noop_writes_leave_watchers_alone() {
+        let mut ks = Keyspace::default();
+        let member = [Bytes::from("a")];
+        ks.push(b"l", ListEnd::Right, &member).unwrap();
+        ks.sadd(b"s", &member).unwrap();
+        ks.hset(b"h", &[(Bytes::from("a"), Bytes::from("v"))])
+            .unwrap();
+        let id = ks.watched.new_client();
+        for key in ["l", "s", "h"] {
+            ks.watch(id, &Bytes::from(key));
+        }
+        ks.lrem(b"l", 0, b"missing").unwrap();
+        ks.ltrim(b"l", 0, -1).unwrap();
+        ks.srem(b"s", &[Bytes::from("missing")]).unwrap();
+        ks.hdel(b"h", &[Bytes::from("missing")]).unwrap();
+        assert!(!ks.watched.is_dirty(id));
+        ks.srem(b"s", &member).unwrap();
+        assert!(ks.watched.is_dirty(id));
+    }
+
+    #[test]
+    fn test_flush_dirties_existing_keys() {
+        let mut ks = Keyspace::default();
+        ks.insert(Bytes::from("k"), string(None));
+        let (a, b) = (ks.watched.new_client(), ks.watched.new_client());
+        ks.watch(a, &Bytes::from("k"));
+        ks.watch(b, &Bytes::from("missing"));
+        ks.flush();
+        assert!(ks.watched.is_dirty(a));
+        assert!(!ks.watched.is_dirty(b));
+        assert_eq!(ks.key_count(), 0);
+    }
+}
@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use super::{
+    cache::{DbError, EntryValue, Keyspace, RespEntry},
+    list::normalize_range,
+    notify::EventClass,
+    scan::ScanIndex,
+    skiplist::SkipList,
+};
+
+/// A sorted set: the dict gives O(1) score lookups by member, the skiplist
+/// keeps the `(score, member)` order and answers rank queries, and the index
+/// keeps the ZSCAN order.
+#[derive(Debug, Clone, Default)]
+pub struct SortedSet {
+    dict: HashMap<Bytes, f64>,
+    zsl: SkipList,
+    pub(super) index: ScanIndex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreRange {
+    pub min: f64,
+    pub max: f64,
+    pub min_exclusive: bool,
+    pub max_exclusive: bool,
+}
+
+impl ScoreRange {
+    fn above_min(&self, score: f64) -> bool {
+        if self.min_exclusive {
+            score > self.min
+        } else {
+            score >= self.min
+        }
+    }
+
+    fn below_max(&self, score: f64) -> bool {
+        if self.max_exclusive {
+            score < self.max
+        } else {
+            score <= self.max
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min > self.max || (self.min == self.max && (self.min_exclusive || self.max_exclusive))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexBound {
+    NegInf,
+    PosInf,
+    Inclusive(Bytes),
+    Exclusive(Bytes),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexRange {
+    pub min: LexBound,
+    pub max: LexBound,
+}
+
+impl LexRange {
+    fn above_min(&self, member: &[u8]) -> bool {
+        match &self.min {
+            LexBound::NegInf => true,
+            LexBound::PosInf => false,
+            LexBound::Inclusive(m) => member >= &m[..],
+            LexBound::Exclusive(m) => member > &m[..],
+        }
+    }
+
+    fn below_max(&self, member: &[u8]) -> bool {
+        match &self.max {
+            LexBound::NegInf => false,
+            LexBound::PosInf => true,
+            LexBound::Inclusive(m) => member <= &m[..],
+            LexBound::Exclusive(m) => member < &m[..],
+        }
+    }
+}
+
+/// `ZRANGE ... LIMIT offset count`; a negative count means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeLimit {
+    pub offset: i64,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn combine(&self, acc: f64, score: f64) -> f64 {
+        match self {
+            Aggregate::Sum => {
+                let sum = acc + score;
+                // inf + -inf: Redis settles on zero rather than NaN.
+                if sum.is_nan() {
+                    0.0
+                } else {
+                    sum
+                }
+            }
+            Aggregate::Min => acc.min(score),
+            Aggregate::Max => acc.max(score),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZAddFlags {
+    pub nx: bool,
+    pub xx: bool,
+    pub gt: bool,
+    pub lt: bool,
+    pub ch: bool,
+    pub incr: bool,
+}
+
+/// What a single ZADD element did to the set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZAddOutcome {
+    Added(f64),
+    Updated(f64),
+    Unchanged(f64),
+    Skipped,
+}
+
+impl SortedSet {
+    pub fn new() -> Self {
+        SortedSet::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.dict.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dict.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.dict.get(member).copied()
+    }
+
+    /// Inserts or re-scores `member`, returning true if it was new.
+    pub fn insert(&mut self, member: &[u8], score: f64) -> bool {
+        match self.dict.get(member).copied() {
+            Some(current) => {
+                if current != score {
+                    self.zsl.remove(current, member);
+                    self.zsl.insert(score, Bytes::copy_from_slice(member));
+                    self.dict.insert(Bytes::copy_from_slice(member), score);
+                }
+                false
+            }
+            None => {
+                let member = Bytes::copy_from_slice(member);
+                self.zsl.insert(score, member.clone());
+                self.dict.insert(member.clone(), score);
+                self.index.insert(member);
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.dict.remove(member) {
+            Some(score) => {
+                self.zsl.remove(score, member);
+                self.index.remove(member);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 0-based rank, counted from the highest score when `rev` is set.
+    pub fn rank(&self, member: &[u8], rev: bool) -> Option<usize> {
+        let score = self.score(member)?;
+        let rank = self.zsl.rank(score, member)? - 1;
+        Some(if rev { self.len() - 1 - rank } else { rank })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], f64)> + '_ {
+        self.zsl.iter()
+    }
+
+    fn collect_from(
+        &self,
+        start: Option<usize>,
+        rev: bool,
+        limit: Option<RangeLimit>,
+        keep: impl Fn(f64, &[u8]) -> bool,
+    ) -> Vec<(Bytes, f64)> {
+        let (offset, count) = match limit {
+            Some(RangeLimit { offset, .. }) if offset < 0 => return vec![],
+            Some(RangeLimit { offset, count }) => (
+                offset as usize,
+                if count < 0 {
+                    usize::MAX
+                } else {
+                    count as usize
+                },
+            ),
+            None => (0, usize::MAX),
+        };
+        let step = |n: usize| {
+            if rev {
+                self.zsl.prev(n)
+            } else {
+                self.zsl.next(n)
+            }
+        };
+        std::iter::successors(start, |&n| step(n))
+            .map(|n| (self.zsl.member(n), self.zsl.score(n)))
+            .take_while(|(member, score)| keep(*score, member))
+            .skip(offset)
+            .take(count)
+            .map(|(member, score)| (Bytes::copy_from_slice(member), score))
+            .collect()
+    }
+
+    pub fn range_by_rank(&self, start: i64, stop: i64, rev: bool) -> Vec<(Bytes, f64)> {
+        let (from, to) = match normalize_range(start, stop, self.len()) {
+            Some(range) => range,
+            None => return vec![],
+        };
+        let first = if rev {
+            self.zsl.by_rank(self.len() - from)
+        } else {
+            self.zsl.by_rank(from + 1)
+        };
+        let limit = RangeLimit {
+            offset: 0,
+            count: (to - from) as i64,
+        };
+        self.collect_from(first, rev, Some(limit), |_, _| true)
+    }
+
+    pub fn range_by_score(
+        &self,
+        range: &ScoreRange,
+        rev: bool,
+        limit: Option<RangeLimit>,
+    ) -> Vec<(Bytes, f64)> {
+        if range.is_empty() {
+            return vec![];
+        }
+        if rev {
+            let start = self.zsl.last_where(|score, _| range.below_max(score));
+            self.collect_from(start, true, limit, |score, _| range.above_min(score))
+        } else {
+            let start = self.zsl.first_not(|score, _| !range.above_min(score));
+            self.collect_from(start, false, limit, |score, _| range.below_max(score))
+        }
+    }
+
+    pub fn range_by_lex(
+        &self,
+        range: &LexRange,
+        rev: bool,
+        limit: Option<RangeLimit>,
+    ) -> Vec<(Bytes, f64)> {
+        if rev {
+            let start = self.zsl.last_where(|_, member| range.below_max(member));
+            self.collect_from(start, true, limit, |_, member| range.above_min(member))
+        } else {
+            let start = self.zsl.first_not(|_, member| !range.above_min(member));
+            self.collect_from(start, false, limit, |_, member| range.below_max(member))
+        }
+    }
+
+    /// Number of elements in `range`, computed from the ranks of the two
+    /// boundary nodes.
+    pub fn count_by_score(&self, range: &ScoreRange) -> usize {
+        if range.is_empty() {
+            return 0;
+        }
+        let first = self.zsl.first_not(|score, _| !range.above_min(score));
+        let last = self.zsl.last_where(|score, _| range.below_max(score));
+        self.count_between(first, last)
+    }
+
+    pub fn count_by_lex(&self, range: &LexRange) -> usize {
+        let first = self.zsl.first_not(|_, member| !range.above_min(member));
+        let last = self.zsl.last_where(|_, member| range.below_max(member));
+        self.count_between(first, last)
+    }
+
+    fn count_between(&self, first: Option<usize>, last: Option<usize>) -> usize {
+        match (first, last) {
+            (Some(first), Some(last)) => {
+                let lo = self
+                    .zsl
+                    .rank(self.zsl.score(first), self.zsl.member(first))
+                    .unwrap_or(0);
+                let hi = self
+                    .zsl
+                    .rank(self.zsl.score(last), self.zsl.member(last))
+                    .unwrap_or(0);
+                if hi >= lo {
+                    hi - lo + 1
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    /// Pops up to `count` elements from the low (or high, if `max`) end.
+    pub fn pop(&mut self, count: usize, max: bool) -> Vec<(Bytes, f64)> {
+        let mut popped = Vec::new();
+        while popped.len() < count {
+            let node = if max {
+                self.zsl.last()
+            } else {
+                self.zsl.first()
+            };
+            let (member, score) = match node {
+                Some(n) => (
+                    Bytes::copy_from_slice(self.zsl.member(n)),
+                    self.zsl.score(n),
+                ),
+                None => break,
+            };
+            self.remove(&member);
+            popped.push((member, score));
+        }
+        popped
+    }
+}
+
+impl Keyspace {
+    pub fn zadd(
+        &mut self,
+        key: &[u8],
+        flags: ZAddFlags,
+        elements: &[(f64, Bytes)],
+    ) -> Result<Vec<ZAddOutcome>, DbError> {
+        // Check the type up front so XX against a missing key does not
+        // create an empty set.
+        if self.get_zset(key)?.is_none() && flags.xx {
+            return Ok(vec![ZAddOutcome::Skipped; elements.len()]);
+        }
+        let zset = self.zset_entry(key)?;
+        let mut outcomes = Vec::with_capacity(elements.len());
+        for (score, member) in elements {
+            let outcome = match zset.score(member) {
+                Some(current) => {
+                    if flags.nx {
+                        ZAddOutcome::Skipped
+                    } else {
+                        let next = if flags.incr { current + score } else { *score };
+                        if next.is_nan() {
+                            return Err(DbError::ScoreNaN);
+                        }
+                        if (flags.gt && next <= current) || (flags.lt && next >= current) {
+                            ZAddOutcome::Skipped
+                        } else if next != current {
+                            zset.insert(member, next);
+                            ZAddOutcome::Updated(next)
+                        } else {
+                            ZAddOutcome::Unchanged(next)
+                        }
+                    }
+                }
+                None if flags.xx => ZAddOutcome::Skipped,
+                None => {
+                    zset.insert(member, *score);
+                    ZAddOutcome::Added(*score)
+                }
+            };
+            outcomes.push(outcome);
+        }
+        let changed = outcomes
+            .iter()
+            .any(|o| matches!(o, ZAddOutcome::Added(_) | ZAddOutcome::Updated(_)));
+        if changed {
+            let event = if flags.incr { "zincr" } else { "zadd" };
+            self.notify(EventClass::ZSet, event, key);
+        }
+        self.remove_if_empty(key);
+        Ok(outcomes)
+    }
+
+    pub fn zincrby(&mut self, key: &[u8], member: &[u8], delta: f64) -> Result<f64, DbError> {
+        let zset = self.zset_entry(key)?;
+        let next = zset.score(member).unwrap_or(0.0) + delta;
+        if next.is_nan() {
+            self.remove_if_empty(key);
+            return Err(DbError::ScoreNaN);
+        }
+        zset.insert(member, next);
+        self.notify(EventClass::ZSet, "zincr", key);
+        Ok(next)
+    }
+
+    pub fn zrem(&mut self, key: &[u8], members: &[Bytes]) -> Result<i64, DbError> {
+        let removed = match self.get_zset_mut(key)? {
+            Some(zset) => members.iter().filter(|m| zset.remove(m)).count(),
+            None => 0,
+        };
+        if removed > 0 {
+            self.watched.touch(key);
+            self.notify(EventClass::ZSet, "zrem", key);
+        }
+        self.remove_if_empty(key);
+        Ok(removed as i64)
+    }
+
+    pub fn zcard(&mut self, key: &[u8]) -> Result<i64, DbError> {
+        Ok(self.get_zset(key)?.map_or(0, |z| z.len() as i64))
+    }
+
+    pub fn zscore(&mut self, key: &[u8], member: &[u8]) -> Result<Option<f64>, DbError> {
+        Ok(self.get_zset(key)?.and_then(|z| z.score(member)))
+    }
+
+    pub fn zmscore(&mut self, key: &[u8], members: &[Bytes]) -> Result<Vec<Option<f64>>, DbError> {
+        let zset = self.get_zset(key)?;
+        Ok(members
+            .iter()
+            .map(|m| zset.and_then(|z| z.score(m)))
+            .collect())
+    }
+
+    pub fn zrank(
+        &mut self,
+        key: &[u8],
+        member: &[u8],
+        rev: bool,
+    ) -> Result<Option<(usize, f64)>, DbError> {
+        Ok(self.get_zset(key)?.and_then(|z| {
+            let rank = z.rank(member, rev)?;
+            Some((rank, z.score(member)?))
+        }))
+    }
+
+    pub fn zcount(&mut self, key: &[u8], range: &ScoreRange) -> Result<i64, DbError> {
+        Ok(self
+            .get_zset(key)?
+            .map_or(0, |z| z.count_by_score(range) as i64))
+    }
+
+    pub fn zlexcount(&mut self, key: &[u8], range: &LexRange) -> Result<i64, DbError> {
+        Ok(self
+            .get_zset(key)?
+            .map_or(0, |z| z.count_by_lex(range) as i64))
+    }
+
+    pub fn zpop(
+        &mut self,
+        key: &[u8],
+        count: usize,
+        max: bool,
+    ) -> Result<Vec<(Bytes, f64)>, DbError> {
+        let popped = match self.get_zset_mut(key)? {
+            Some(zset) => zset.pop(count, max),
+            None => vec![],
+        };
+        if !popped.is_empty() {
+            let event = if max { "zpopmax" } else { "zpopmin" };
+            self.watched.touch(key);
+            self.notify(EventClass::ZSet, event, key);
+        }
+        self.remove_if_empty(key);
+        Ok(popped)
+    }
+
+    /// Removes every element returned by `select` and reports how many went.
+    /// `event` names the command for the keyspace event.
+    pub fn zremrange(
+        &mut self,
+        key: &[u8],
+        event: &str,
+        select: impl Fn(&SortedSet) -> Vec<(Bytes, f64)>,
+    ) -> Result<i64, DbError> {
+        let removed = match self.get_zset_mut(key)? {
+            Some(zset) => {
+                let doomed = select(zset);
+                for (member, _) in &doomed {
+                    zset.remove(member);
+                }
+                doomed.len()
+            }
+            None => 0,
+        };
+        if removed > 0 {
+            self.watched.touch(key);
+            self.notify(EventClass::ZSet, event, key);
+        }
+        self.remove_if_empty(key);
+        Ok(removed as i64)
+    }
+
+    /// Reads a ZUNIONSTORE/ZINTERSTORE source. Plain sets count as sorted
+    /// sets whose members all score 1.
+    fn zset_source(&mut self, key: &[u8]) -> Result<Option<Vec<(Bytes, f64)>>, DbError> {
+        match self.get(key).map(|e| &e.value) {
+            Some(EntryValue::ZSet(z)) => Ok(Some(
+                z.iter()
+                    .map(|(m, s)| (Bytes::copy_from_slice(m), s))
+                    .collect(),
+            )),
+            Some(EntryValue::Set(s)) => Ok(Some(s.iter().map(|m| (m.clone(), 1.0)).collect())),
+            Some(_) => Err(DbError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// ZUNIONSTORE (`union`) or ZINTERSTORE, returning the size of the stored
+    /// result. `weights` has one entry per key.
+    pub fn zstore_combine(
+        &mut self,
+        destination: &[u8],
+        keys: &[Bytes],
+        weights: &[f64],
+        aggregate: Aggregate,
+        union: bool,
+    ) -> Result<i64, DbError> {
+        let mut sources = Vec::with_capacity(keys.len());
+        for key in keys {
+            sources.push(self.zset_source(key)?);
+        }
+        let weighted = |score: f64, weight: f64| {
+            let product = score * weight;
+            if product.is_nan() {
+                0.0
+            } else {
+                product
+            }
+        };
+        let mut acc: HashMap<Bytes, (f64, usize)> = HashMap::new();
+        for (source, weight) in sources.iter().zip(weights) {
+            for (member, score) in source.iter().flatten() {
+                let score = weighted(*score, *weight);
+                acc.entry(member.clone())
+                    .and_modify(|(total, seen)| {
+                        *total = aggregate.combine(*total, score);
+                        *seen += 1;
+                    })
+                    .or_insert((score, 1));
+            }
+        }
+        let mut result = SortedSet::new();
+        for (member, (score, seen)) in acc {
+            if union || seen == sources.len() {
+                result.insert(&member, score);
+            }
+        }
+        let len = result.len() as i64;
+        if result.is_empty() {
+            self.delete(destination);
+        } else {
+            self.insert(
+                Bytes::copy_from_slice(destination),
+                RespEntry::new(EntryValue::ZSet(result), None),
+            );
+            let event = if union { "zunionstore" } else { "zinterstore" };
+            self.notify(EventClass::ZSet, event, destination);
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(items: &[(Bytes, f64)]) -> Vec<Bytes> {
+        items.iter().map(|(m, _)| m.clone()).collect()
+    }
+
+    fn sample() -> SortedSet {
+        let mut zset = SortedSet::new();
+        for (member, score) in [("a", 1.0), ("b", 2.0), ("c", 3.0), ("d", 4.0)] {
+            zset.insert(member.as_bytes(), score);
+        }
+        zset
+    }
+
+    #[test]
+    fn test_range_by_rank_and_rev() {
+        let zset = sample();
+        assert_eq!(members(&zset.range_by_rank(1, 2, false)), vec!["b", "c"]);
+        assert_eq!(members(&zset.range_by_rank(0, 1, true)), vec!["d", "c"]);
+        assert_eq!(zset.rank(b"a", true), Some(3));
+    }
+
+    #[test]
+    fn test_range_by_score_with_limit() {
+        let zset = sample();
+        let range = ScoreRange {
+            min: 1.0,
+            max: 4.0,
+            min_exclusive: true,
+            max_exclusive: false,
+        };
+        assert_eq!(
+            members(&zset.range_by_score(&range, false, None)),
+            vec!["b", "c", "d"]
+        );
+        let limit = Some(RangeLimit {
+            offset: 1,
+            count: 1,
+        });
+        assert_eq!(
+            members(&zset.range_by_score(&range, true, limit)),
+            vec!["c"]
+        );
+        assert_eq!(zset.count_by_score(&range), 3);
+    }
+
+    #[test]
+    fn test_zadd_flags() {
+        let mut ks = Keyspace::default();
+        let elems = vec![(5.0, Bytes::from("m"))];
+        ks.zadd(b"z", ZAddFlags::default(), &elems).unwrap();
+        let gt = ZAddFlags {
+            gt: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ks.zadd(b"z", gt, &[(3.0, Bytes::from("m"))]),
+            Ok(vec![ZAddOutcome::Skipped])
+        );
+        let incr = ZAddFlags {
+            incr: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ks.zadd(b"z", incr, &[(2.0, Bytes::from("m"))]),
+            Ok(vec![ZAddOutcome::Updated(7.0)])
+        );
+        let xx = ZAddFlags {
+            xx: true,
+            ..Default::default()
+        };
+        ks.zadd(b"other", xx, &elems).unwrap();
+        assert!(ks.get(b"other").is_none());
+    }
+
+    #[test]
+    fn test_zstore_combine() {
+        let mut ks = Keyspace::default();
+        ks.zadd(
+            b"a",
+            ZAddFlags::default(),
+            &[(1.0, Bytes::from("x")), (2.0, Bytes::from("y"))],
+        )
+        .unwrap();
+        ks.sadd(b"b", &[Bytes::from("y"), Bytes::from("z")])
+            .unwrap();
+        let keys = vec![Bytes::from("a"), Bytes::from("b")];
+        assert_eq!(
+            ks.zstore_combine(b"u", &keys, &[1.0, 2.0], Aggregate::Sum, true),
+            Ok(3)
+        );
+        assert_eq!(ks.zscore(b"u", b"y"), Ok(Some(4.0)));
+        assert_eq!(
+            ks.zstore_combine(b"i", &keys, &[1.0, 1.0], Aggregate::Max, false),
+            Ok(1)
+        );
+        assert_eq!(ks.zscore(b"i", b"y"), Ok(Some(2.0)));
+    }
+}